@@ -0,0 +1,37 @@
+ALTER TABLE camps
+    ADD COLUMN IF NOT EXISTS min_age int,
+    ADD COLUMN IF NOT EXISTS max_age int,
+    ADD COLUMN IF NOT EXISTS camp_type varchar(32),
+    ADD COLUMN IF NOT EXISTS gender_policy varchar(32),
+    ADD COLUMN IF NOT EXISTS price_min_cents bigint,
+    ADD COLUMN IF NOT EXISTS price_max_cents bigint,
+    ADD COLUMN IF NOT EXISTS currency char(3),
+    ADD CONSTRAINT camps_camp_type_check CHECK (camp_type IN ('day', 'overnight', 'virtual', 'travel')),
+    ADD CONSTRAINT camps_gender_policy_check CHECK (gender_policy IN ('coed', 'boys', 'girls')),
+    ADD CONSTRAINT camps_age_range_check CHECK (min_age <= max_age),
+    ADD CONSTRAINT camps_price_range_check CHECK (price_min_cents <= price_max_cents);
+
+ALTER TABLE camp_requests
+    ADD COLUMN IF NOT EXISTS min_age int,
+    ADD COLUMN IF NOT EXISTS max_age int,
+    ADD COLUMN IF NOT EXISTS camp_type varchar(32),
+    ADD COLUMN IF NOT EXISTS gender_policy varchar(32),
+    ADD COLUMN IF NOT EXISTS price_min_cents bigint,
+    ADD COLUMN IF NOT EXISTS price_max_cents bigint,
+    ADD COLUMN IF NOT EXISTS currency char(3),
+    ADD COLUMN IF NOT EXISTS sessions jsonb DEFAULT '[]'::jsonb NOT NULL;
+
+CREATE TABLE IF NOT EXISTS camp_sessions(
+    id bigserial primary key,
+    camp_id bigint NOT NULL,
+    name varchar(255) DEFAULT '' NOT NULL,
+    start_date date NOT NULL,
+    end_date date NOT NULL,
+    capacity int,
+    price_cents bigint,
+    currency char(3),
+
+    CONSTRAINT fk_camps FOREIGN KEY (camp_id) REFERENCES camps(id) ON DELETE CASCADE,
+    CONSTRAINT camp_sessions_date_range_check CHECK (start_date <= end_date)
+);
+CREATE INDEX IF NOT EXISTS camp_sessions_camp_id_idx ON camp_sessions(camp_id);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS admins(
+    user_id varchar(255) primary key,
+
+    CONSTRAINT fk_users FOREIGN KEY (user_id) REFERENCES users(supabase_id) ON DELETE CASCADE
+);
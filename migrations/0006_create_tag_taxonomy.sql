@@ -0,0 +1,105 @@
+CREATE TABLE IF NOT EXISTS tags(
+    id bigserial primary key,
+    slug varchar(255) NOT NULL UNIQUE,
+    display_name varchar(255) NOT NULL,
+    category varchar(32) NOT NULL,
+
+    CONSTRAINT tags_category_check CHECK (category IN ('activities', 'amenities', 'accessibility', 'dietary'))
+);
+CREATE TABLE IF NOT EXISTS tag_synonyms(
+    synonym varchar(255) primary key,
+    tag_id bigint NOT NULL,
+
+    CONSTRAINT fk_tags FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
+
+INSERT INTO tags (slug, display_name, category) VALUES
+    ('swimming', 'Swimming', 'activities'),
+    ('hiking', 'Hiking', 'activities'),
+    ('arts-and-crafts', 'Arts & Crafts', 'activities'),
+    ('horseback-riding', 'Horseback Riding', 'activities'),
+    ('boating', 'Boating', 'activities'),
+    ('sports', 'Sports', 'activities'),
+    ('music', 'Music', 'activities'),
+    ('stem', 'STEM', 'activities'),
+    ('pool', 'Pool', 'amenities'),
+    ('lake', 'Lake', 'amenities'),
+    ('air-conditioning', 'Air Conditioning', 'amenities'),
+    ('wheelchair-accessible', 'Wheelchair Accessible', 'accessibility'),
+    ('sensory-friendly', 'Sensory Friendly', 'accessibility'),
+    ('nut-free', 'Nut Free', 'dietary'),
+    ('vegetarian', 'Vegetarian', 'dietary'),
+    ('gluten-free', 'Gluten Free', 'dietary'),
+    ('kosher', 'Kosher', 'dietary'),
+    ('halal', 'Halal', 'dietary')
+ON CONFLICT (slug) DO NOTHING;
+
+INSERT INTO tag_synonyms (synonym, tag_id)
+SELECT synonym, tags.id FROM (VALUES
+    ('swim', 'swimming'),
+    ('swimming lessons', 'swimming'),
+    ('swimming pool', 'pool'),
+    ('hike', 'hiking'),
+    ('arts', 'arts-and-crafts'),
+    ('crafts', 'arts-and-crafts'),
+    ('arts and crafts', 'arts-and-crafts'),
+    ('horses', 'horseback-riding'),
+    ('horseback', 'horseback-riding'),
+    ('canoeing', 'boating'),
+    ('kayaking', 'boating'),
+    ('sailing', 'boating'),
+    ('science', 'stem'),
+    ('coding', 'stem'),
+    ('ac', 'air-conditioning'),
+    ('a/c', 'air-conditioning'),
+    ('wheelchair', 'wheelchair-accessible'),
+    ('ada accessible', 'wheelchair-accessible'),
+    ('peanut free', 'nut-free'),
+    ('vegan', 'vegetarian'),
+    ('celiac', 'gluten-free')
+) AS seed(synonym, slug)
+JOIN tags ON tags.slug = seed.slug
+ON CONFLICT (synonym) DO NOTHING;
+
+-- Map existing free-form tags onto the taxonomy, creating activity tags for unknown ones
+CREATE TEMPORARY TABLE legacy_tags AS
+SELECT DISTINCT
+    raw_tag,
+    trim(both '-' from regexp_replace(lower(trim(raw_tag)), '[^a-z0-9]+', '-', 'g')) AS slug,
+    regexp_replace(lower(trim(raw_tag)), '\s+', ' ', 'g') AS normalized
+FROM (
+    SELECT unnest(tags) AS raw_tag FROM camps
+    UNION
+    SELECT unnest(tags) AS raw_tag FROM camp_requests
+) AS existing
+WHERE trim(raw_tag) <> '';
+
+INSERT INTO tags (slug, display_name, category)
+SELECT DISTINCT ON (legacy_tags.slug) legacy_tags.slug, initcap(legacy_tags.normalized), 'activities'
+FROM legacy_tags
+WHERE legacy_tags.slug <> ''
+    AND NOT EXISTS (SELECT 1 FROM tags WHERE tags.slug = legacy_tags.slug OR lower(tags.display_name) = legacy_tags.normalized)
+    AND NOT EXISTS (SELECT 1 FROM tag_synonyms WHERE tag_synonyms.synonym = legacy_tags.normalized)
+ON CONFLICT (slug) DO NOTHING;
+
+CREATE TEMPORARY TABLE legacy_tag_mapping AS
+SELECT legacy_tags.raw_tag, COALESCE(
+    (SELECT tags.slug FROM tag_synonyms JOIN tags ON tags.id = tag_synonyms.tag_id WHERE tag_synonyms.synonym = legacy_tags.normalized),
+    (SELECT tags.slug FROM tags WHERE tags.slug = legacy_tags.slug OR lower(tags.display_name) = legacy_tags.normalized ORDER BY tags.slug = legacy_tags.slug DESC LIMIT 1)
+) AS slug
+FROM legacy_tags;
+
+UPDATE camps SET tags = COALESCE((
+    SELECT array_agg(DISTINCT legacy_tag_mapping.slug)
+    FROM unnest(camps.tags) AS existing(tag)
+    JOIN legacy_tag_mapping ON legacy_tag_mapping.raw_tag = existing.tag
+), array[]::varchar(255)[]);
+
+UPDATE camp_requests SET tags = COALESCE((
+    SELECT array_agg(DISTINCT legacy_tag_mapping.slug)
+    FROM unnest(camp_requests.tags) AS existing(tag)
+    JOIN legacy_tag_mapping ON legacy_tag_mapping.raw_tag = existing.tag
+), array[]::varchar(255)[]);
+
+DROP TABLE legacy_tag_mapping;
+DROP TABLE legacy_tags;
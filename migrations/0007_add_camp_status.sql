@@ -0,0 +1,5 @@
+ALTER TABLE camps
+    ADD COLUMN IF NOT EXISTS status varchar(32) DEFAULT 'active' NOT NULL,
+    ADD COLUMN IF NOT EXISTS deleted_at timestamp with time zone,
+    ADD CONSTRAINT camps_status_check CHECK (status IN ('active', 'temporarily_closed', 'permanently_closed', 'hidden'));
+CREATE INDEX IF NOT EXISTS camps_visible_idx ON camps(status) WHERE deleted_at IS NULL;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS camp_revisions(
+    id bigserial primary key,
+    camp_id bigint NOT NULL,
+    user_id varchar(255) NOT NULL,
+    ctime timestamp with time zone DEFAULT now() NOT NULL,
+    source varchar(32) NOT NULL,
+    source_id bigint,
+    snapshot jsonb NOT NULL,
+    diff jsonb DEFAULT '{}'::jsonb NOT NULL,
+
+    CONSTRAINT fk_camps FOREIGN KEY (camp_id) REFERENCES camps(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS camp_revisions_camp_id_idx ON camp_revisions(camp_id, ctime DESC);
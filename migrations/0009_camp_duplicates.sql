@@ -0,0 +1,11 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX IF NOT EXISTS camps_name_trgm_idx ON camps USING gin (lower(name) gin_trgm_ops);
+
+CREATE TABLE IF NOT EXISTS camp_redirects(
+    from_camp_id bigint primary key,
+    to_camp_id bigint NOT NULL,
+    ctime timestamp with time zone DEFAULT now() NOT NULL,
+
+    CONSTRAINT fk_from_camps FOREIGN KEY (from_camp_id) REFERENCES camps(id) ON DELETE CASCADE,
+    CONSTRAINT fk_to_camps FOREIGN KEY (to_camp_id) REFERENCES camps(id) ON DELETE CASCADE
+);
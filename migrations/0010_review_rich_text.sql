@@ -0,0 +1,8 @@
+ALTER TABLE reviews
+    ALTER COLUMN body TYPE text,
+    ADD COLUMN IF NOT EXISTS body_html text DEFAULT '' NOT NULL;
+
+-- Existing bodies are plain text, so they render as a single escaped paragraph.
+UPDATE reviews SET body_html = '<p>' || replace(replace(replace(replace(body,
+    '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;') || '</p>'
+WHERE body_html = '' AND body <> '';
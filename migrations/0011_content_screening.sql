@@ -0,0 +1,25 @@
+-- Existing accounts predate tracking and are treated as established.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS created_at timestamp with time zone;
+ALTER TABLE users ALTER COLUMN created_at SET DEFAULT now();
+
+ALTER TABLE reviews
+    ADD COLUMN IF NOT EXISTS moderation_status varchar(32) DEFAULT 'published' NOT NULL,
+    ADD CONSTRAINT reviews_moderation_status_check CHECK (moderation_status IN ('published', 'held'));
+
+ALTER TABLE camp_requests
+    ADD COLUMN IF NOT EXISTS moderation_status varchar(32) DEFAULT 'published' NOT NULL,
+    ADD CONSTRAINT camp_requests_moderation_status_check CHECK (moderation_status IN ('published', 'held'));
+
+CREATE TABLE IF NOT EXISTS content_screenings(
+    id bigserial primary key,
+    content_type varchar(32) NOT NULL,
+    content_id bigint NOT NULL,
+    user_id varchar(255) NOT NULL,
+    ctime timestamp with time zone DEFAULT now() NOT NULL,
+    score real NOT NULL,
+    held boolean NOT NULL,
+    -- Score of every check that fired, as `{"check": score}`.
+    checks jsonb DEFAULT '{}'::jsonb NOT NULL
+);
+CREATE INDEX IF NOT EXISTS content_screenings_user_ctime_idx ON content_screenings(user_id, ctime);
+CREATE INDEX IF NOT EXISTS content_screenings_content_idx ON content_screenings(content_type, content_id);
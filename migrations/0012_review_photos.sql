@@ -0,0 +1 @@
+ALTER TABLE reviews ADD COLUMN IF NOT EXISTS photo_urls text[] DEFAULT array[]::text[];
@@ -0,0 +1,24 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS avatar_url text;
+
+-- Usernames become unique regardless of case; later duplicates lose theirs
+UPDATE users SET username = '' WHERE supabase_id IN (
+    SELECT supabase_id FROM (
+        SELECT supabase_id, row_number() OVER (PARTITION BY lower(username) ORDER BY created_at, supabase_id) AS position
+        FROM users WHERE username <> ''
+    ) ranked WHERE position > 1
+);
+CREATE UNIQUE INDEX IF NOT EXISTS users_username_lower_key ON users (lower(username)) WHERE username <> '';
+
+CREATE TABLE IF NOT EXISTS username_reservations(
+    username varchar(255) primary key,
+    user_id varchar(255) NOT NULL REFERENCES users(supabase_id) ON DELETE CASCADE,
+    expires_at timestamp with time zone NOT NULL
+);
+CREATE INDEX IF NOT EXISTS username_reservations_user_id_idx ON username_reservations (user_id);
+
+CREATE TABLE IF NOT EXISTS review_helpful_votes(
+    review_id bigint NOT NULL REFERENCES reviews(id) ON DELETE CASCADE,
+    user_id varchar(255) NOT NULL REFERENCES users(supabase_id) ON DELETE CASCADE,
+    ctime timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT review_helpful_votes_pkey PRIMARY KEY (review_id, user_id)
+);
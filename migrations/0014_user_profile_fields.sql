@@ -0,0 +1,5 @@
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS bio text DEFAULT '' NOT NULL,
+    ADD COLUMN IF NOT EXISTS home_location varchar(255) DEFAULT '' NOT NULL,
+    -- Only the preferences a user changed are stored; missing keys mean "on".
+    ADD COLUMN IF NOT EXISTS notification_preferences jsonb DEFAULT '{}'::jsonb NOT NULL;
@@ -0,0 +1,4 @@
+-- Content of deleted accounts is reattributed to this placeholder author.
+INSERT INTO users (supabase_id, first_name, last_name, email, username)
+VALUES ('former-member', 'Former member', '', '', '')
+ON CONFLICT (supabase_id) DO NOTHING;
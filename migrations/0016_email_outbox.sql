@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS email_outbox(
+    id bigserial primary key,
+    user_id varchar(255) REFERENCES users(supabase_id) ON DELETE SET NULL,
+    recipient varchar(255) NOT NULL,
+    template varchar(64) NOT NULL,
+    subject text NOT NULL,
+    text_body text NOT NULL,
+    html_body text NOT NULL,
+    status varchar(16) DEFAULT 'pending' NOT NULL,
+    attempts int DEFAULT 0 NOT NULL,
+    last_error text,
+    ctime timestamp with time zone DEFAULT now() NOT NULL,
+    sent_at timestamp with time zone,
+
+    CONSTRAINT email_outbox_status_check CHECK (status IN ('pending', 'sent', 'failed'))
+);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS notifications(
+    id bigserial primary key,
+    user_id varchar(255) NOT NULL REFERENCES users(supabase_id) ON DELETE CASCADE,
+    kind varchar(64) NOT NULL,
+    -- The typed notification, tagged with its kind in `type`.
+    payload jsonb NOT NULL,
+    link text NOT NULL,
+    ctime timestamp with time zone DEFAULT now() NOT NULL,
+    read_at timestamp with time zone
+);
+CREATE INDEX IF NOT EXISTS notifications_user_idx ON notifications(user_id, id DESC);
+CREATE INDEX IF NOT EXISTS notifications_unread_idx ON notifications(user_id) WHERE read_at IS NULL;
@@ -0,0 +1,37 @@
+CREATE TABLE IF NOT EXISTS webhook_subscriptions(
+    id bigserial primary key,
+    -- NULL subscribes to every camp.
+    camp_id bigint REFERENCES camps(id) ON DELETE CASCADE,
+    url text NOT NULL,
+    secret varchar(255) NOT NULL,
+    -- Empty subscribes to every event type.
+    event_types varchar(64)[] NOT NULL DEFAULT '{}',
+    active boolean NOT NULL DEFAULT true,
+    created_by varchar(255) REFERENCES users(supabase_id) ON DELETE SET NULL,
+    ctime timestamp with time zone DEFAULT now() NOT NULL
+);
+CREATE INDEX IF NOT EXISTS webhook_subscriptions_camp_idx ON webhook_subscriptions(camp_id);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries(
+    id bigserial primary key,
+    subscription_id bigint NOT NULL REFERENCES webhook_subscriptions(id) ON DELETE CASCADE,
+    event_type varchar(64) NOT NULL,
+    payload jsonb NOT NULL,
+    status varchar(32) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts integer NOT NULL DEFAULT 0,
+    last_status_code integer,
+    last_error text,
+    ctime timestamp with time zone DEFAULT now() NOT NULL,
+    delivered_at timestamp with time zone
+);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_subscription_idx ON webhook_deliveries(subscription_id, id DESC);
+
+CREATE TABLE IF NOT EXISTS webhook_delivery_attempts(
+    id bigserial primary key,
+    delivery_id bigint NOT NULL REFERENCES webhook_deliveries(id) ON DELETE CASCADE,
+    status_code integer,
+    error text,
+    duration_ms integer NOT NULL,
+    ctime timestamp with time zone DEFAULT now() NOT NULL
+);
+CREATE INDEX IF NOT EXISTS webhook_delivery_attempts_delivery_idx ON webhook_delivery_attempts(delivery_id);
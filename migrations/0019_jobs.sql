@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS jobs(
+    id bigserial primary key,
+    kind varchar(64) NOT NULL,
+    payload jsonb NOT NULL DEFAULT '{}',
+    status varchar(32) NOT NULL DEFAULT 'queued' CHECK (status IN ('queued', 'running', 'succeeded', 'dead')),
+    attempts integer NOT NULL DEFAULT 0,
+    max_attempts integer NOT NULL DEFAULT 5,
+    run_at timestamp with time zone DEFAULT now() NOT NULL,
+    locked_at timestamp with time zone,
+    last_error text,
+    -- Recurring jobs are queued once per tick, whichever instance gets there first.
+    dedupe_key varchar(255) UNIQUE,
+    ctime timestamp with time zone DEFAULT now() NOT NULL,
+    finished_at timestamp with time zone
+);
+CREATE INDEX IF NOT EXISTS jobs_due_idx ON jobs(run_at) WHERE status = 'queued';
+CREATE INDEX IF NOT EXISTS jobs_running_idx ON jobs(locked_at) WHERE status = 'running';
+CREATE INDEX IF NOT EXISTS jobs_kind_status_idx ON jobs(kind, status);
@@ -0,0 +1,7 @@
+-- Optional ratings of single aspects of a camp, on the same 1-5 scale as the overall rating.
+ALTER TABLE reviews
+    ADD COLUMN IF NOT EXISTS staff_rating int CHECK (staff_rating BETWEEN 1 AND 5),
+    ADD COLUMN IF NOT EXISTS activities_rating int CHECK (activities_rating BETWEEN 1 AND 5),
+    ADD COLUMN IF NOT EXISTS facilities_rating int CHECK (facilities_rating BETWEEN 1 AND 5),
+    ADD COLUMN IF NOT EXISTS food_rating int CHECK (food_rating BETWEEN 1 AND 5),
+    ADD COLUMN IF NOT EXISTS safety_rating int CHECK (safety_rating BETWEEN 1 AND 5);
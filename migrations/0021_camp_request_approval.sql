@@ -0,0 +1,4 @@
+-- The camp a request was approved as, so the same request cannot be added twice.
+ALTER TABLE camp_requests
+    ADD COLUMN IF NOT EXISTS approved_at timestamp with time zone,
+    ADD COLUMN IF NOT EXISTS camp_id bigint REFERENCES camps(id) ON DELETE SET NULL;
@@ -0,0 +1,11 @@
+-- Hashes of the whitespace-collapsed, lowercased text that screening compares
+-- to find copy-pasted submissions, so the lookup is an index scan.
+ALTER TABLE reviews
+    ADD COLUMN IF NOT EXISTS text_hash text
+        GENERATED ALWAYS AS (md5(lower(btrim(regexp_replace(body, '\s+', ' ', 'g'))))) STORED;
+CREATE INDEX IF NOT EXISTS reviews_text_hash_idx ON reviews(text_hash);
+
+ALTER TABLE camp_requests
+    ADD COLUMN IF NOT EXISTS text_hash text
+        GENERATED ALWAYS AS (md5(lower(btrim(regexp_replace(name || ' ' || description || ' ' || coalesce(website, ''), '\s+', ' ', 'g'))))) STORED;
+CREATE INDEX IF NOT EXISTS camp_requests_text_hash_idx ON camp_requests(text_hash);
@@ -0,0 +1,303 @@
+use std::{env, sync::OnceLock};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::Sha256;
+
+use super::Error;
+
+/// Claims of a Supabase access token that matter to this API.
+#[derive(Debug, Default, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub exp: Option<i64>,
+    #[serde(default)]
+    pub user_metadata: UserMetadata,
+}
+
+/// Profile data the identity provider collected at sign up. Providers fill in
+/// different keys, so all of them are optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct UserMetadata {
+    #[serde(default)]
+    pub first_name: Option<String>,
+    #[serde(default)]
+    pub last_name: Option<String>,
+    #[serde(default)]
+    pub full_name: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+impl UserMetadata {
+    /// First and last name, split from the full name when only that is known.
+    pub fn names(&self) -> (String, String) {
+        if self.first_name.is_some() || self.last_name.is_some() {
+            return (
+                self.first_name.clone().unwrap_or_default(),
+                self.last_name.clone().unwrap_or_default(),
+            );
+        }
+
+        let full_name = self.full_name.as_ref().or(self.name.as_ref());
+        match full_name.map(|name| name.trim().split_once(' ')) {
+            Some(Some((first, last))) => (first.to_string(), last.trim().to_string()),
+            Some(None) => (full_name.cloned().unwrap_or_default(), String::new()),
+            None => (String::new(), String::new()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+}
+
+/// How access tokens are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verification<'a> {
+    /// Signatures are checked with the secret Supabase signs tokens with.
+    Secret(&'a [u8]),
+    /// Signatures are not checked, and a token that isn't a JWT at all is taken
+    /// as a bare user id. Only for local development without Supabase.
+    Unverified,
+    /// Nothing is configured, so no token is accepted.
+    Refuse,
+}
+
+/// `Secret` from `SUPABASE_JWT_SECRET`, or `Unverified` when that is unset and
+/// `ALLOW_UNVERIFIED_TOKENS=1` opts in to skipping the check.
+fn verification() -> Verification<'static> {
+    static SECRET: OnceLock<Option<String>> = OnceLock::new();
+    static ALLOW_UNVERIFIED: OnceLock<bool> = OnceLock::new();
+
+    let secret = SECRET.get_or_init(|| env::var("SUPABASE_JWT_SECRET").ok());
+    match secret.as_deref() {
+        Some(secret) => Verification::Secret(secret.as_bytes()),
+        None if *ALLOW_UNVERIFIED.get_or_init(|| {
+            env::var("ALLOW_UNVERIFIED_TOKENS").is_ok_and(|allow| allow == "1")
+        }) =>
+        {
+            Verification::Unverified
+        }
+        None => Verification::Refuse,
+    }
+}
+
+/// Reads the claims of an HS256 access token, checked against
+/// `SUPABASE_JWT_SECRET`. Tokens are refused when the secret is unset, unless
+/// `ALLOW_UNVERIFIED_TOKENS=1` is set for local development.
+pub fn decode_token(token: &str) -> Result<Claims, Error> {
+    decode_with(token, verification())
+}
+
+fn decode_with(token: &str, verification: Verification) -> Result<Claims, Error> {
+    let invalid = |reason: &str| Error::InvalidToken(reason.to_string());
+    if verification == Verification::Refuse {
+        return Err(invalid("SUPABASE_JWT_SECRET is not set"));
+    }
+
+    let Some((signed, signature)) = token.rsplit_once('.') else {
+        return match verification {
+            Verification::Unverified if !token.is_empty() => Ok(Claims {
+                sub: token.to_string(),
+                ..Claims::default()
+            }),
+            _ => Err(invalid("not a JWT")),
+        };
+    };
+
+    let (header, payload) = signed.split_once('.').ok_or_else(|| invalid("not a JWT"))?;
+    let header: Header = decode_part(header).ok_or_else(|| invalid("malformed header"))?;
+    if header.alg != "HS256" {
+        return Err(invalid("unsupported algorithm"));
+    }
+
+    if let Verification::Secret(secret) = verification {
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| invalid("malformed signature"))?;
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret).map_err(|_| invalid("unusable secret"))?;
+        mac.update(signed.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| invalid("bad signature"))?;
+    }
+
+    let claims: Claims = decode_part(payload).ok_or_else(|| invalid("malformed claims"))?;
+    if claims.exp.is_some_and(|exp| exp <= Utc::now().timestamp()) {
+        return Err(invalid("expired"));
+    }
+    if claims.sub.is_empty() {
+        return Err(invalid("missing subject"));
+    }
+
+    Ok(claims)
+}
+
+fn decode_part<T: DeserializeOwned>(part: &str) -> Option<T> {
+    let bytes = URL_SAFE_NO_PAD.decode(part).ok()?;
+
+    serde_json::from_slice(&bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const SECRET: &[u8] = b"super-secret-jwt-token-with-at-least-32-characters";
+
+    fn encode_part(value: &Value) -> String {
+        URL_SAFE_NO_PAD.encode(value.to_string())
+    }
+
+    fn sign(header: &Value, claims: &Value, secret: &[u8]) -> String {
+        let signed = format!("{}.{}", encode_part(header), encode_part(claims));
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("usable secret");
+        mac.update(signed.as_bytes());
+
+        format!(
+            "{}.{}",
+            signed,
+            URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+        )
+    }
+
+    fn token(claims: Value) -> String {
+        sign(&json!({"alg": "HS256", "typ": "JWT"}), &claims, SECRET)
+    }
+
+    fn reason(result: Result<Claims, Error>) -> String {
+        match result {
+            Err(Error::InvalidToken(reason)) => reason,
+            other => panic!("expected an invalid token, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accepts_a_good_signature() {
+        let token = token(json!({
+            "sub": "9a7c1d52",
+            "email": "amy@example.com",
+            "exp": Utc::now().timestamp() + 3600,
+            "user_metadata": {"full_name": "Amy  Lee Park"},
+        }));
+
+        let claims = decode_with(&token, Verification::Secret(SECRET)).expect("valid token");
+        assert_eq!(claims.sub, "9a7c1d52");
+        assert_eq!(claims.email.as_deref(), Some("amy@example.com"));
+        assert_eq!(
+            claims.user_metadata.names(),
+            ("Amy".to_string(), "Lee Park".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_a_bad_signature() {
+        let forged = sign(
+            &json!({"alg": "HS256"}),
+            &json!({"sub": "9a7c1d52"}),
+            b"another-secret",
+        );
+        assert_eq!(
+            reason(decode_with(&forged, Verification::Secret(SECRET))),
+            "bad signature"
+        );
+
+        // Claims swapped under a valid signature
+        let (signed, signature) = token(json!({"sub": "9a7c1d52"}))
+            .rsplit_once('.')
+            .map(|(signed, signature)| (signed.to_string(), signature.to_string()))
+            .expect("signed token");
+        let (header, _) = signed.split_once('.').expect("header");
+        let tampered = format!(
+            "{}.{}.{}",
+            header,
+            encode_part(&json!({"sub": "admin"})),
+            signature
+        );
+        assert_eq!(
+            reason(decode_with(&tampered, Verification::Secret(SECRET))),
+            "bad signature"
+        );
+
+        let unsigned = format!("{}.", signed);
+        assert_eq!(
+            reason(decode_with(&unsigned, Verification::Secret(SECRET))),
+            "bad signature"
+        );
+    }
+
+    #[test]
+    fn rejects_other_algorithms() {
+        for alg in ["none", "HS512", "RS256"] {
+            let token = sign(&json!({"alg": alg}), &json!({"sub": "9a7c1d52"}), SECRET);
+            assert_eq!(
+                reason(decode_with(&token, Verification::Secret(SECRET))),
+                "unsupported algorithm"
+            );
+            assert_eq!(
+                reason(decode_with(&token, Verification::Unverified)),
+                "unsupported algorithm"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let expired = token(json!({"sub": "9a7c1d52", "exp": Utc::now().timestamp() - 1}));
+
+        assert_eq!(
+            reason(decode_with(&expired, Verification::Secret(SECRET))),
+            "expired"
+        );
+    }
+
+    #[test]
+    fn rejects_an_empty_subject() {
+        for claims in [json!({"sub": ""}), json!({"email": "amy@example.com"})] {
+            let token = token(claims);
+            assert!(decode_with(&token, Verification::Secret(SECRET)).is_err());
+        }
+        assert_eq!(
+            reason(decode_with(
+                &token(json!({"sub": ""})),
+                Verification::Secret(SECRET)
+            )),
+            "missing subject"
+        );
+    }
+
+    #[test]
+    fn bare_user_ids_need_the_unverified_opt_in() {
+        let claims = decode_with("9a7c1d52", Verification::Unverified).expect("bare id");
+        assert_eq!(claims.sub, "9a7c1d52");
+
+        assert_eq!(
+            reason(decode_with("9a7c1d52", Verification::Secret(SECRET))),
+            "not a JWT"
+        );
+        assert_eq!(
+            reason(decode_with("", Verification::Unverified)),
+            "not a JWT"
+        );
+    }
+
+    #[test]
+    fn refuses_every_token_without_configuration() {
+        let token = token(json!({"sub": "9a7c1d52"}));
+
+        assert_eq!(
+            reason(decode_with(&token, Verification::Refuse)),
+            "SUPABASE_JWT_SECRET is not set"
+        );
+        assert!(decode_with("9a7c1d52", Verification::Refuse).is_err());
+    }
+}
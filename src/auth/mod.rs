@@ -1,18 +1,63 @@
 use sqlx::PgPool;
 use thiserror::Error as ThisError;
 
+use crate::models::FORMER_MEMBER_ID;
+
+pub use self::provisioning::forget_user;
+
+mod jwt;
+mod provisioning;
+
 #[derive(Debug, Clone)]
 pub struct UserCtx {
     pub user_id: String,
+    pub is_admin: bool,
 }
 
-pub async fn utx_from_token(_db: &PgPool, token: &str) -> Result<UserCtx, Error> {
-    match token.parse::<String>() {
-        Ok(user_id) => Ok(UserCtx { user_id }),
-        Err(_) => Err(Error::InvalidToken(token.to_string())),
+impl UserCtx {
+    pub fn require_admin(&self) -> Result<(), Error> {
+        if self.is_admin {
+            Ok(())
+        } else {
+            Err(Error::AdminRequired(self.user_id.clone()))
+        }
     }
 }
 
+/// Resolves the caller from their access token, creating their `users` row on
+/// first sight so no separate sign up call is needed.
+pub async fn utx_from_token(db: &PgPool, token: &str) -> Result<UserCtx, Error> {
+    let claims = jwt::decode_token(token)?;
+    if claims.sub == FORMER_MEMBER_ID {
+        return Err(Error::InvalidToken("reserved subject".to_string()));
+    }
+
+    let is_admin = provisioning::provision_user(db, &claims).await?;
+
+    Ok(UserCtx {
+        user_id: claims.sub,
+        is_admin,
+    })
+}
+
+/// An admin acting outside of a request, e.g. from a command line tool.
+pub async fn admin_ctx(db: &PgPool, user_id: &str) -> Result<UserCtx, Error> {
+    let is_admin = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM admins WHERE user_id = $1) AS \"is_admin!\"",
+        user_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    let utx = UserCtx {
+        user_id: user_id.to_string(),
+        is_admin,
+    };
+    utx.require_admin()?;
+
+    Ok(utx)
+}
+
 #[derive(ThisError, Debug)]
 pub enum Error {
     #[error("Invalid Token {0}")]
@@ -20,4 +65,10 @@ pub enum Error {
 
     #[error("Missing required token")]
     MissingToken,
+
+    #[error("User {0} is not an admin")]
+    AdminRequired(String),
+
+    #[error("Failed to load user context")]
+    DatabaseFailure(#[from] sqlx::Error),
 }
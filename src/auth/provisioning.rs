@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use sqlx::PgPool;
+
+use super::{jwt::Claims, Error};
+
+/// How long a provisioned user is trusted without looking at the database again.
+/// Admin grants and revocations take at most this long to apply.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+/// Stale entries are dropped once the cache holds this many users.
+const MAX_CACHED_USERS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+struct CachedUser {
+    email: Option<String>,
+    is_admin: bool,
+    checked: Instant,
+}
+
+fn cache() -> &'static Mutex<HashMap<String, CachedUser>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, CachedUser>>> = OnceLock::new();
+
+    CACHE.get_or_init(Default::default)
+}
+
+/// Makes sure the token's user has a `users` row with the email from its claims,
+/// and returns whether they are an admin. Hits the database only when the user
+/// wasn't seen recently or their email changed.
+pub async fn provision_user(db: &PgPool, claims: &Claims) -> Result<bool, Error> {
+    let email = claims
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|email| !email.is_empty());
+
+    if let Some(cached) = cache()
+        .lock()
+        .unwrap_or_else(|ex| ex.into_inner())
+        .get(&claims.sub)
+    {
+        let email_unchanged = email.is_none() || cached.email.as_deref() == email;
+        if email_unchanged && cached.checked.elapsed() < CACHE_TTL {
+            return Ok(cached.is_admin);
+        }
+    }
+
+    let (first_name, last_name) = claims.user_metadata.names();
+    // Names only seed a new row; afterwards they belong to the user's profile
+    sqlx::query!(
+        "INSERT INTO users (supabase_id, email, first_name, last_name) VALUES ($1, COALESCE($2, ''), $3, $4)
+         ON CONFLICT (supabase_id) DO UPDATE SET email = excluded.email
+         WHERE $2::varchar IS NOT NULL AND users.email <> excluded.email",
+        claims.sub,
+        email.map(|email| truncate(email, 255)),
+        truncate(&first_name, 255),
+        truncate(&last_name, 255)
+    )
+    .execute(db)
+    .await?;
+
+    let is_admin = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM admins WHERE user_id = $1) AS \"is_admin!\"",
+        claims.sub
+    )
+    .fetch_one(db)
+    .await?;
+
+    let mut cache = cache().lock().unwrap_or_else(|ex| ex.into_inner());
+    if cache.len() >= MAX_CACHED_USERS {
+        cache.retain(|_, cached| cached.checked.elapsed() < CACHE_TTL);
+    }
+    cache.insert(
+        claims.sub.clone(),
+        CachedUser {
+            email: email.map(str::to_string),
+            is_admin,
+            checked: Instant::now(),
+        },
+    );
+
+    Ok(is_admin)
+}
+
+/// Drops a user from the cache, so a deleted account is provisioned afresh.
+pub fn forget_user(user_id: &str) {
+    cache()
+        .lock()
+        .unwrap_or_else(|ex| ex.into_inner())
+        .remove(user_id);
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
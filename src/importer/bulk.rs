@@ -0,0 +1,841 @@
+use std::{collections::HashMap, env, fmt, fs, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use sqlx::PgPool;
+
+use super::{csv, Error};
+use crate::{
+    auth::{self, UserCtx},
+    models::{
+        self,
+        camp_duplicate::{
+            CampDuplicateManager, CampFingerprint, DuplicateCandidate, LIKELY_DUPLICATE_SCORE,
+        },
+        camp_revision::RevisionSource,
+        tag::TagManager,
+        validation::{FieldError, Validate, ValidationErrors},
+        CampManager, CampPatch,
+    },
+};
+
+/// Largest file the upload endpoint accepts.
+pub const MAX_IMPORT_BYTES: u64 = 20 * 1024 * 1024;
+const DEFAULT_CHUNK_SIZE: usize = 100;
+const MAX_CHUNK_SIZE: usize = 1000;
+/// Separates the entries of list fields such as `tags` within one CSV cell.
+const LIST_SEPARATOR: char = '|';
+
+/// The fields a column can fill: those of `CampPatch`, and `id` to update an
+/// existing camp instead of creating one.
+const FIELDS: [&str; 25] = [
+    "id",
+    "name",
+    "description",
+    "phone_number",
+    "street_address",
+    "city",
+    "state",
+    "country",
+    "zip_code",
+    "email",
+    "website",
+    "tags",
+    "apt_suite_other",
+    "image_urls",
+    "latitude",
+    "longitude",
+    "min_age",
+    "max_age",
+    "camp_type",
+    "gender_policy",
+    "price_min_cents",
+    "price_max_cents",
+    "currency",
+    "status",
+    "sessions",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    Csv,
+    /// JSON Lines, one camp object per line.
+    Jsonl,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(ImportFormat::Csv),
+            "jsonl" | "ndjson" => Some(ImportFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            ImportFormat::Csv => "csv",
+            ImportFormat::Jsonl => "jsonl",
+        };
+        write!(f, "{}", format)
+    }
+}
+
+/// What to do with a new camp that looks like one already in the catalog.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnDuplicate {
+    #[default]
+    Skip,
+    /// Update the best matching camp with the row instead.
+    Update,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportOptions {
+    pub format: ImportFormat,
+    /// Report what would happen without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub on_duplicate: OnDuplicate,
+    /// Name of a column mapping in `CAMP_IMPORT_MAPPINGS`.
+    pub mapping: Option<String>,
+    /// Rows committed per transaction.
+    pub chunk_size: Option<usize>,
+}
+
+/// Source column names to camp fields. Columns that are not mapped are used
+/// as is when they name a field and ignored otherwise.
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct ColumnMapping {
+    columns: HashMap<String, String>,
+}
+
+impl ColumnMapping {
+    /// Looks up `name` in the JSON file named by `CAMP_IMPORT_MAPPINGS`, e.g.
+    /// `{"directory_2024": {"Camp Name": "name", "Phone": "phone_number"}}`.
+    pub fn named(name: &str) -> Result<Self, Error> {
+        let path = env::var("CAMP_IMPORT_MAPPINGS")
+            .map_err(|_| Error::UnknownMapping(name.to_string()))?;
+        let json = fs::read_to_string(&path)
+            .map_err(|ex| Error::InvalidMapping(format!("cannot read {}: {}", path, ex)))?;
+        let mut mappings: HashMap<String, ColumnMapping> = serde_json::from_str(&json)
+            .map_err(|ex| Error::InvalidMapping(format!("{}: {}", path, ex)))?;
+
+        let mapping = mappings
+            .remove(name)
+            .ok_or_else(|| Error::UnknownMapping(name.to_string()))?;
+        if let Some(field) = mapping
+            .columns
+            .values()
+            .find(|field| !FIELDS.contains(&field.as_str()))
+        {
+            return Err(Error::InvalidMapping(format!(
+                "{} maps a column to unknown field {:?}",
+                name, field
+            )));
+        }
+
+        Ok(mapping)
+    }
+
+    fn field_for<'a>(&'a self, column: &'a str) -> Option<&'a str> {
+        match self.columns.get(column) {
+            Some(field) => Some(field),
+            None => FIELDS.contains(&column).then_some(column),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RowOutcome {
+    Created,
+    Updated,
+    Skipped,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RowReport {
+    /// Line the row starts on in the uploaded file.
+    pub line: usize,
+    pub outcome: RowOutcome,
+    pub camp_id: Option<i64>,
+    pub name: Option<String>,
+    pub errors: Vec<FieldError>,
+    /// Existing camps that look like the row; likely ones decide the outcome.
+    pub duplicates: Vec<DuplicateCandidate>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub format: ImportFormat,
+    pub dry_run: bool,
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    /// Source columns that fill no camp field.
+    pub ignored_columns: Vec<String>,
+    pub rows: Vec<RowReport>,
+}
+
+/// A row read from the file, keyed by camp field.
+struct SourceRow {
+    line: usize,
+    values: Result<Map<String, Value>, FieldError>,
+}
+
+enum Action {
+    Create,
+    Update(i64),
+}
+
+struct PlannedRow {
+    report: RowReport,
+    change: Option<(Action, CampPatch)>,
+}
+
+/// Imports the camps in `data` as `utx`. Every row is validated and checked
+/// for duplicates first; unless it is a dry run, the rows that pass are then
+/// written in transactions of `chunk_size` rows, and a chunk that fails is
+/// rolled back and reported as skipped.
+pub async fn import_camps(
+    db: &PgPool,
+    utx: &UserCtx,
+    data: &str,
+    options: &ImportOptions,
+) -> Result<ImportReport, Error> {
+    let mapping = match &options.mapping {
+        Some(name) => ColumnMapping::named(name)?,
+        None => ColumnMapping::default(),
+    };
+    let (rows, ignored_columns) = match options.format {
+        ImportFormat::Csv => read_csv(data, &mapping)?,
+        ImportFormat::Jsonl => read_jsonl(data, &mapping),
+    };
+
+    let mut seen: HashMap<(String, String), usize> = HashMap::new();
+    let mut planned = Vec::with_capacity(rows.len());
+    for row in rows {
+        planned.push(plan_row(db, utx, row, options.on_duplicate, &mut seen).await?);
+    }
+
+    if !options.dry_run {
+        let chunk_size = options
+            .chunk_size
+            .unwrap_or(DEFAULT_CHUNK_SIZE)
+            .clamp(1, MAX_CHUNK_SIZE);
+        for chunk in planned.chunks_mut(chunk_size) {
+            if let Err(ex) = commit_chunk(db, utx, chunk).await {
+                for row in chunk
+                    .iter_mut()
+                    .filter(|row| row.report.outcome != RowOutcome::Skipped)
+                {
+                    row.report.outcome = RowOutcome::Skipped;
+                    row.report.camp_id = None;
+                    row.report.message =
+                        Some(format!("Rolled back with its chunk. Cause: {:?}", ex));
+                }
+            }
+        }
+    }
+
+    let rows: Vec<RowReport> = planned.into_iter().map(|row| row.report).collect();
+    let count = |outcome: RowOutcome| rows.iter().filter(|row| row.outcome == outcome).count();
+
+    Ok(ImportReport {
+        format: options.format,
+        dry_run: options.dry_run,
+        total: rows.len(),
+        created: count(RowOutcome::Created),
+        updated: count(RowOutcome::Updated),
+        skipped: count(RowOutcome::Skipped),
+        ignored_columns,
+        rows,
+    })
+}
+
+async fn commit_chunk(
+    db: &PgPool,
+    utx: &UserCtx,
+    chunk: &mut [PlannedRow],
+) -> Result<(), models::Error> {
+    let mut tx = db.begin().await?;
+
+    for row in chunk.iter_mut() {
+        let Some((action, camp)) = row.change.take() else {
+            continue;
+        };
+        let camp = match action {
+            Action::Create => {
+                CampManager::insert_camp(&mut tx, utx, camp, RevisionSource::Import, None).await?
+            }
+            Action::Update(id) => {
+                CampManager::apply_patch(&mut tx, utx, id, camp, RevisionSource::Import).await?
+            }
+        };
+        row.report.camp_id = Some(camp.id);
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+const USAGE: &str = "usage: import-camps <file.csv|file.jsonl> --as <admin user id> [--format csv|jsonl] [--mapping <name>] [--on-duplicate skip|update] [--chunk-size <rows>] [--dry-run]";
+
+/// The `import-camps` command: imports a file as an admin and prints the report.
+pub async fn run_command(db: &PgPool, args: &[String]) -> Result<(), Error> {
+    let usage = || Error::Usage(USAGE.to_string());
+
+    let mut path = None;
+    let mut user_id = None;
+    let mut format = None;
+    let mut options = ImportOptions {
+        format: ImportFormat::Csv,
+        dry_run: false,
+        on_duplicate: OnDuplicate::Skip,
+        mapping: None,
+        chunk_size: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--as" => user_id = Some(args.next().ok_or_else(usage)?),
+            "--format" => {
+                let value = Value::String(args.next().ok_or_else(usage)?.clone());
+                format = Some(from_value(value).map_err(|_| usage())?);
+            }
+            "--mapping" => options.mapping = Some(args.next().ok_or_else(usage)?.clone()),
+            "--on-duplicate" => {
+                let value = Value::String(args.next().ok_or_else(usage)?.clone());
+                options.on_duplicate = from_value(value).map_err(|_| usage())?;
+            }
+            "--chunk-size" => {
+                let value = args.next().ok_or_else(usage)?;
+                options.chunk_size = Some(value.parse().map_err(|_| usage())?);
+            }
+            "--dry-run" => options.dry_run = true,
+            arg if path.is_none() && !arg.starts_with("--") => path = Some(Path::new(arg)),
+            _ => return Err(usage()),
+        }
+    }
+
+    let path = path.ok_or_else(usage)?;
+    options.format = format
+        .or_else(|| ImportFormat::from_path(path))
+        .ok_or_else(usage)?;
+    let utx = auth::admin_ctx(db, user_id.ok_or_else(usage)?).await?;
+    let data = fs::read_to_string(path)
+        .map_err(|ex| Error::InvalidFile(format!("{}: {}", path.display(), ex)))?;
+
+    let report = import_camps(db, &utx, &data, &options).await?;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).unwrap_or_default()
+    );
+
+    Ok(())
+}
+
+/// Decides what happens to a row, reporting it as it would be imported.
+async fn plan_row(
+    db: &PgPool,
+    utx: &UserCtx,
+    row: SourceRow,
+    on_duplicate: OnDuplicate,
+    seen: &mut HashMap<(String, String), usize>,
+) -> Result<PlannedRow, Error> {
+    let mut report = RowReport {
+        line: row.line,
+        outcome: RowOutcome::Skipped,
+        camp_id: None,
+        name: None,
+        errors: Vec::new(),
+        duplicates: Vec::new(),
+        message: None,
+    };
+    let skipped = |report| PlannedRow {
+        report,
+        change: None,
+    };
+
+    let values = match row.values {
+        Ok(values) => values,
+        Err(error) => {
+            report.errors.push(error);
+            return Ok(skipped(report));
+        }
+    };
+    report.name = values
+        .get("name")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+
+    let (id, mut camp) = match build_patch(values) {
+        Ok(patch) => patch,
+        Err(errors) => {
+            report.errors = errors.errors;
+            return Ok(skipped(report));
+        }
+    };
+
+    if let Some(tags) = camp.tags.take() {
+        match TagManager::normalize_tags(db, tags).await {
+            Ok(tags) => camp.tags = Some(tags),
+            Err(models::Error::UnknownTags(unknown)) => {
+                report.errors.push(FieldError {
+                    path: "$.tags".to_string(),
+                    message: format!("unknown tags: {}", unknown.join(", ")),
+                });
+                return Ok(skipped(report));
+            }
+            Err(ex) => return Err(ex.into()),
+        }
+    }
+
+    if let Some(id) = id {
+        return match CampManager::get_visible_camp(db, id, Some(utx)).await {
+            Ok(stored) => {
+                if let Err(invalid) = camp.validate_update(&stored) {
+                    report.errors = invalid.errors;
+                    return Ok(skipped(report));
+                }
+                report.outcome = RowOutcome::Updated;
+                report.camp_id = Some(id);
+                Ok(PlannedRow {
+                    report,
+                    change: Some((Action::Update(id), camp)),
+                })
+            }
+            Err(models::Error::DatabaseConnectionFailed(sqlx::Error::RowNotFound)) => {
+                report.message = Some(format!("No camp with id {}", id));
+                Ok(skipped(report))
+            }
+            Err(ex) => Err(ex.into()),
+        };
+    }
+
+    let fingerprint = CampFingerprint::from(&camp);
+    let key = (fingerprint.name.clone(), fingerprint.address.clone());
+    if let Some(first_line) = seen.get(&key) {
+        report.message = Some(format!("Repeats the camp on line {}", first_line));
+        return Ok(skipped(report));
+    }
+    seen.insert(key, row.line);
+
+    report.duplicates = CampDuplicateManager::find_duplicates(db, &fingerprint).await?;
+    let likely = report
+        .duplicates
+        .iter()
+        .find(|candidate| candidate.score >= LIKELY_DUPLICATE_SCORE)
+        .map(|candidate| candidate.camp_id);
+
+    match (likely, on_duplicate) {
+        (Some(camp_id), OnDuplicate::Skip) => {
+            report.message = Some(format!("Likely duplicate of camp {}", camp_id));
+            Ok(skipped(report))
+        }
+        (Some(camp_id), OnDuplicate::Update) => {
+            let stored = CampManager::get_visible_camp(db, camp_id, Some(utx)).await?;
+            if let Err(invalid) = camp.validate_update(&stored) {
+                report.errors = invalid.errors;
+                report.message = Some(format!("Matched camp {}", camp_id));
+                return Ok(skipped(report));
+            }
+            report.outcome = RowOutcome::Updated;
+            report.camp_id = Some(camp_id);
+            report.message = Some(format!("Matched camp {}", camp_id));
+            Ok(PlannedRow {
+                report,
+                change: Some((Action::Update(camp_id), camp)),
+            })
+        }
+        (None, _) => {
+            report.outcome = RowOutcome::Created;
+            Ok(PlannedRow {
+                report,
+                change: Some((Action::Create, camp)),
+            })
+        }
+    }
+}
+
+/// Reads a CSV file whose first record names the columns.
+fn read_csv(data: &str, mapping: &ColumnMapping) -> Result<(Vec<SourceRow>, Vec<String>), Error> {
+    let mut records = csv::parse(data).map_err(Error::InvalidFile)?.into_iter();
+    let header = records
+        .next()
+        .ok_or_else(|| Error::InvalidFile("the file has no header row".to_string()))?;
+
+    let columns: Vec<Option<&str>> = header
+        .fields
+        .iter()
+        .map(|column| mapping.field_for(column.trim()))
+        .collect();
+    let ignored_columns = header
+        .fields
+        .iter()
+        .zip(&columns)
+        .filter(|(_, field)| field.is_none())
+        .map(|(column, _)| column.trim().to_string())
+        .collect();
+
+    let rows = records
+        .map(|record| {
+            let values = if record.fields.len() != columns.len() {
+                Err(FieldError {
+                    path: "$".to_string(),
+                    message: format!(
+                        "has {} fields where the header has {}",
+                        record.fields.len(),
+                        columns.len()
+                    ),
+                })
+            } else {
+                Ok(columns
+                    .iter()
+                    .zip(record.fields)
+                    .filter_map(|(field, value)| {
+                        Some((field.as_ref()?.to_string(), Value::String(value)))
+                    })
+                    .collect())
+            };
+
+            SourceRow {
+                line: record.line,
+                values,
+            }
+        })
+        .collect();
+
+    Ok((rows, ignored_columns))
+}
+
+/// Reads JSON Lines, one camp object per line; blank lines are skipped.
+fn read_jsonl(data: &str, mapping: &ColumnMapping) -> (Vec<SourceRow>, Vec<String>) {
+    let mut ignored_columns: Vec<String> = Vec::new();
+
+    let rows = data
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let values = match serde_json::from_str::<Value>(line) {
+                Ok(Value::Object(object)) => {
+                    let mut values = Map::new();
+                    for (key, value) in object {
+                        match mapping.field_for(&key) {
+                            Some(field) => {
+                                values.insert(field.to_string(), value);
+                            }
+                            None if !ignored_columns.contains(&key) => ignored_columns.push(key),
+                            None => {}
+                        }
+                    }
+                    Ok(values)
+                }
+                Ok(_) => Err(FieldError {
+                    path: "$".to_string(),
+                    message: "must be a JSON object".to_string(),
+                }),
+                Err(ex) => Err(FieldError {
+                    path: "$".to_string(),
+                    message: format!("is not valid JSON: {}", ex),
+                }),
+            };
+
+            SourceRow {
+                line: index + 1,
+                values,
+            }
+        })
+        .collect();
+
+    (rows, ignored_columns)
+}
+
+/// Turns a row into the camp id it updates, if any, and a patch. New camps are
+/// validated here, updates once the stored camp is known.
+fn build_patch(values: Map<String, Value>) -> Result<(Option<i64>, CampPatch), ValidationErrors> {
+    let mut errors = ValidationErrors::default();
+    let mut id = None;
+    let mut camp = CampPatch::default();
+
+    for (field, value) in values {
+        let Some(value) = coerce(&field, value) else {
+            continue;
+        };
+        if let Err(message) = set_field(&mut camp, &mut id, &field, value) {
+            errors.add(format!("$.{}", field), message);
+        }
+    }
+    let has_name = camp
+        .name
+        .as_deref()
+        .is_some_and(|name| !name.trim().is_empty());
+    if id.is_none() && !has_name {
+        errors.add("$.name", "is required for new camps");
+    }
+    // Updates are validated once the camp they change is loaded
+    if id.is_none() {
+        if let Err(invalid) = camp.validate() {
+            errors.errors.extend(invalid.errors);
+        }
+    }
+    errors.into_result()?;
+
+    Ok((id, camp))
+}
+
+/// Converts text as CSV gives it to the JSON type of the field. Empty values
+/// and nulls leave the field unset.
+fn coerce(field: &str, value: Value) -> Option<Value> {
+    let text = match value {
+        Value::Null => return None,
+        Value::String(text) => text.trim().to_string(),
+        value => return Some(value),
+    };
+    if text.is_empty() {
+        return None;
+    }
+
+    let value = match field {
+        "id" | "latitude" | "longitude" | "min_age" | "max_age" | "price_min_cents"
+        | "price_max_cents" => text
+            .parse::<i64>()
+            .map(Number::from)
+            .ok()
+            .or_else(|| text.parse::<f64>().ok().and_then(Number::from_f64))
+            .map(Value::Number)
+            .unwrap_or(Value::String(text)),
+        "tags" | "image_urls" => Value::Array(
+            text.split(LIST_SEPARATOR)
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| Value::String(entry.to_string()))
+                .collect(),
+        ),
+        "camp_type" | "gender_policy" | "status" => {
+            Value::String(text.to_lowercase().replace([' ', '-'], "_"))
+        }
+        "sessions" => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        _ => Value::String(text),
+    };
+
+    Some(value)
+}
+
+fn set_field(
+    camp: &mut CampPatch,
+    id: &mut Option<i64>,
+    field: &str,
+    value: Value,
+) -> Result<(), String> {
+    match field {
+        "id" => *id = Some(from_value(value)?),
+        "name" => camp.name = Some(from_value(value)?),
+        "description" => camp.description = Some(from_value(value)?),
+        "phone_number" => camp.phone_number = Some(from_value(value)?),
+        "street_address" => camp.street_address = Some(from_value(value)?),
+        "city" => camp.city = Some(from_value(value)?),
+        "state" => camp.state = Some(from_value(value)?),
+        "country" => camp.country = Some(from_value(value)?),
+        "zip_code" => camp.zip_code = Some(from_value(value)?),
+        "email" => camp.email = Some(from_value(value)?),
+        "website" => camp.website = Some(from_value(value)?),
+        "tags" => camp.tags = Some(from_value(value)?),
+        "apt_suite_other" => camp.apt_suite_other = Some(from_value(value)?),
+        "image_urls" => camp.image_urls = Some(from_value(value)?),
+        "latitude" => camp.latitude = Some(from_value(value)?),
+        "longitude" => camp.longitude = Some(from_value(value)?),
+        "min_age" => camp.min_age = Some(from_value(value)?),
+        "max_age" => camp.max_age = Some(from_value(value)?),
+        "camp_type" => camp.camp_type = Some(from_value(value)?),
+        "gender_policy" => camp.gender_policy = Some(from_value(value)?),
+        "price_min_cents" => camp.price_min_cents = Some(from_value(value)?),
+        "price_max_cents" => camp.price_max_cents = Some(from_value(value)?),
+        "currency" => camp.currency = Some(from_value(value)?),
+        "status" => camp.status = Some(from_value(value)?),
+        "sessions" => camp.sessions = Some(from_value(value)?),
+        field => return Err(format!("{} is not a camp field", field)),
+    }
+
+    Ok(())
+}
+
+fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, String> {
+    serde_json::from_value(value).map_err(|ex| ex.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::CampStatus;
+
+    fn row(values: Value) -> Map<String, Value> {
+        match values {
+            Value::Object(values) => values,
+            _ => panic!("rows are objects"),
+        }
+    }
+
+    fn error_paths(errors: ValidationErrors) -> Vec<String> {
+        errors.errors.into_iter().map(|error| error.path).collect()
+    }
+
+    #[test]
+    fn leaves_empty_values_unset() {
+        assert_eq!(coerce("name", Value::Null), None);
+        assert_eq!(coerce("name", json!("")), None);
+        assert_eq!(coerce("min_age", json!("   ")), None);
+        assert_eq!(coerce("tags", json!("")), None);
+    }
+
+    #[test]
+    fn coerces_numbers() {
+        assert_eq!(coerce("min_age", json!(" 8 ")), Some(json!(8)));
+        assert_eq!(coerce("latitude", json!("39.09")), Some(json!(39.09)));
+        assert_eq!(coerce("price_min_cents", json!("-5")), Some(json!(-5)));
+        // Left as text for the field to report
+        assert_eq!(coerce("max_age", json!("ten")), Some(json!("ten")));
+        // Text that only looks like a number stays text elsewhere
+        assert_eq!(coerce("zip_code", json!("02134")), Some(json!("02134")));
+    }
+
+    #[test]
+    fn splits_lists() {
+        assert_eq!(
+            coerce("tags", json!("swimming| hiking ||arts ")),
+            Some(json!(["swimming", "hiking", "arts"]))
+        );
+        assert_eq!(
+            coerce("image_urls", json!("https://img.example/a.jpg")),
+            Some(json!(["https://img.example/a.jpg"]))
+        );
+        assert_eq!(coerce("tags", json!("|")), Some(json!([])));
+    }
+
+    #[test]
+    fn normalizes_enum_names() {
+        assert_eq!(
+            coerce("status", json!("Temporarily Closed")),
+            Some(json!("temporarily_closed"))
+        );
+        assert_eq!(
+            coerce("status", json!("permanently-closed")),
+            Some(json!("permanently_closed"))
+        );
+        assert_eq!(coerce("camp_type", json!("DAY")), Some(json!("day")));
+        assert_eq!(
+            coerce("gender_policy", json!(" Coed ")),
+            Some(json!("coed"))
+        );
+    }
+
+    #[test]
+    fn parses_sessions_as_json() {
+        let sessions = r#"[{"start_date": "2025-07-01", "end_date": "2025-07-14"}]"#;
+
+        assert_eq!(
+            coerce("sessions", json!(sessions)),
+            Some(json!([{"start_date": "2025-07-01", "end_date": "2025-07-14"}]))
+        );
+        assert_eq!(coerce("sessions", json!("[{")), Some(json!("[{")));
+    }
+
+    #[test]
+    fn keeps_json_values_and_trims_text() {
+        assert_eq!(coerce("min_age", json!(8)), Some(json!(8)));
+        assert_eq!(coerce("tags", json!(["a", "b"])), Some(json!(["a", "b"])));
+        assert_eq!(
+            coerce("name", json!("  Pine Lake ")),
+            Some(json!("Pine Lake"))
+        );
+    }
+
+    #[test]
+    fn builds_new_camps() {
+        let (id, camp) = build_patch(row(json!({
+            "name": "Pine Lake",
+            "country": "us",
+            "zip_code": "96150",
+            "tags": "swimming|hiking",
+            "min_age": "8",
+            "max_age": "14",
+            "camp_type": "Overnight",
+            "gender_policy": "coed",
+            "status": "Temporarily Closed",
+            "apt_suite_other": "",
+        })))
+        .expect("a valid row");
+
+        assert_eq!(id, None);
+        assert_eq!(camp.name.as_deref(), Some("Pine Lake"));
+        assert_eq!(camp.country.as_deref(), Some("US"));
+        assert_eq!(
+            camp.tags,
+            Some(vec!["swimming".to_string(), "hiking".to_string()])
+        );
+        assert_eq!((camp.min_age, camp.max_age), (Some(8), Some(14)));
+        assert_eq!(
+            camp.camp_type
+                .map(|camp_type| camp_type.to_string())
+                .as_deref(),
+            Some("overnight")
+        );
+        assert_eq!(
+            camp.gender_policy
+                .map(|policy| policy.to_string())
+                .as_deref(),
+            Some("coed")
+        );
+        assert_eq!(camp.status, Some(CampStatus::TemporarilyClosed));
+        assert_eq!(camp.apt_suite_other, None);
+    }
+
+    #[test]
+    fn requires_a_name_for_new_camps() {
+        let missing = build_patch(row(json!({"city": "Tahoe"}))).expect_err("no name");
+        assert_eq!(error_paths(missing), ["$.name"]);
+
+        let blank = build_patch(row(json!({"name": "   "}))).expect_err("blank name");
+        assert_eq!(error_paths(blank), ["$.name"]);
+    }
+
+    #[test]
+    fn leaves_updates_to_be_validated_against_the_stored_camp() {
+        let (id, camp) = build_patch(row(json!({"id": "12", "max_age": "6", "email": "nope"})))
+            .expect("an update");
+
+        assert_eq!(id, Some(12));
+        assert_eq!(camp.max_age, Some(6));
+        assert_eq!(camp.name, None);
+    }
+
+    #[test]
+    fn reports_every_invalid_field() {
+        let errors = build_patch(row(json!({
+            "name": "Pine Lake",
+            "min_age": "ten",
+            "camp_type": "sleepaway",
+            "email": "not-an-email",
+            "colour": "green",
+        })))
+        .expect_err("invalid fields");
+
+        let mut paths = error_paths(errors);
+        paths.sort();
+        assert_eq!(paths, ["$.camp_type", "$.colour", "$.email", "$.min_age"]);
+    }
+}
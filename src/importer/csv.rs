@@ -0,0 +1,166 @@
+/// One CSV record and the line it starts on.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CsvRecord {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+/// Splits RFC 4180 CSV into records: comma separated, fields optionally in
+/// double quotes with `""` for a quote, line breaks allowed inside quotes.
+/// Blank lines are skipped.
+pub fn parse(text: &str) -> Result<Vec<CsvRecord>, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            '\n' if in_quotes => {
+                line += 1;
+                field.push(ch);
+            }
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes && chars.peek() == Some(&'\n') => {}
+            '\n' if !in_quotes => {
+                fields.push(std::mem::take(&mut field));
+                push_record(&mut records, record_line, std::mem::take(&mut fields));
+                line += 1;
+                record_line = line;
+            }
+            ch => field.push(ch),
+        }
+    }
+
+    if in_quotes {
+        return Err(format!(
+            "unterminated quoted field starting on line {}",
+            record_line
+        ));
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        push_record(&mut records, record_line, fields);
+    }
+
+    Ok(records)
+}
+
+fn push_record(records: &mut Vec<CsvRecord>, line: usize, fields: Vec<String>) {
+    if !(fields.len() == 1 && fields[0].trim().is_empty()) {
+        records.push(CsvRecord { line, fields });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(line: usize, fields: &[&str]) -> CsvRecord {
+        CsvRecord {
+            line,
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn splits_records_and_fields() {
+        assert_eq!(
+            parse("name,city\nPine Lake,Tahoe\nMaple,,\n"),
+            Ok(vec![
+                record(1, &["name", "city"]),
+                record(2, &["Pine Lake", "Tahoe"]),
+                record(3, &["Maple", "", ""]),
+            ])
+        );
+    }
+
+    #[test]
+    fn reads_quoted_fields() {
+        assert_eq!(
+            parse("\"Pine, Lake\",\"Say \"\"hi\"\"\",\"\",\"\"\"\"\n"),
+            Ok(vec![record(1, &["Pine, Lake", "Say \"hi\"", "", "\""])])
+        );
+        // Quotes only open a field at its start
+        assert_eq!(
+            parse("5\" tent,a\"b\n"),
+            Ok(vec![record(1, &["5\" tent", "a\"b"])])
+        );
+    }
+
+    #[test]
+    fn keeps_line_breaks_inside_quotes() {
+        let records = parse("name,description\nPine,\"Lake\nand\r\nwoods\"\nMaple,Hills\n");
+
+        assert_eq!(
+            records,
+            Ok(vec![
+                record(1, &["name", "description"]),
+                record(2, &["Pine", "Lake\nand\r\nwoods"]),
+                record(5, &["Maple", "Hills"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn accepts_crlf_and_a_missing_final_newline() {
+        assert_eq!(
+            parse("name,city\r\nPine,Tahoe\r\nMaple,Reno"),
+            Ok(vec![
+                record(1, &["name", "city"]),
+                record(2, &["Pine", "Tahoe"]),
+                record(3, &["Maple", "Reno"]),
+            ])
+        );
+        // A lone carriage return is data
+        assert_eq!(parse("a\rb\n"), Ok(vec![record(1, &["a\rb"])]));
+    }
+
+    #[test]
+    fn strips_a_byte_order_mark() {
+        assert_eq!(
+            parse("\u{feff}name,city\nPine,Tahoe\n"),
+            Ok(vec![
+                record(1, &["name", "city"]),
+                record(2, &["Pine", "Tahoe"])
+            ])
+        );
+    }
+
+    #[test]
+    fn skips_blank_lines_but_counts_them() {
+        assert_eq!(
+            parse("name\n\nPine\n   \r\nMaple\n"),
+            Ok(vec![
+                record(1, &["name"]),
+                record(3, &["Pine"]),
+                record(5, &["Maple"]),
+            ])
+        );
+        assert_eq!(parse(""), Ok(vec![]));
+        // A row of empty fields is kept, so its columns can be reported
+        assert_eq!(parse(",\n"), Ok(vec![record(1, &["", ""])]));
+    }
+
+    #[test]
+    fn reports_where_an_unterminated_quote_starts() {
+        assert_eq!(
+            parse("name\nPine\n\"Maple\nHills\n"),
+            Err("unterminated quoted field starting on line 3".to_string())
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{
+    json_ld::LinkedOrganization,
+    rules::{FieldSelector, SiteRules},
+};
+use crate::models::{
+    validation::{self, FieldError, MAX_VARCHAR_LEN},
+    CampPatch,
+};
+
+/// Images beyond this many are left for the moderator to add by hand.
+const MAX_IMPORTED_IMAGES: usize = 10;
+
+/// A camp read from a web page, with the values that had to be left out or
+/// shortened to pass validation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtractedCamp {
+    pub camp: CampPatch,
+    pub warnings: Vec<FieldError>,
+}
+
+/// Reads a camp from `html`, the page at `page_url`. Each field comes from the
+/// site's rules, then the schema.org JSON-LD, then the common markup in
+/// `SiteRules::fallback`.
+pub fn extract(html: &str, page_url: &Url, rules: &SiteRules) -> ExtractedCamp {
+    let document = Html::parse_document(html);
+    let organization = LinkedOrganization::find(&document).unwrap_or_default();
+    let fallback = SiteRules::fallback();
+
+    let pick = |field: fn(&SiteRules) -> &Vec<FieldSelector>, linked: Option<String>| {
+        first_value(&document, field(rules))
+            .or(linked)
+            .or_else(|| first_value(&document, field(fallback)))
+    };
+
+    let mut image_urls: Vec<String> = all_values(&document, &rules.image_urls);
+    image_urls.extend(organization.image_urls);
+    image_urls.extend(all_values(&document, &fallback.image_urls));
+
+    let camp = CampPatch {
+        name: pick(|rules| &rules.name, organization.name),
+        description: pick(|rules| &rules.description, organization.description),
+        phone_number: pick(|rules| &rules.phone_number, organization.telephone)
+            .map(|phone| link_target(&phone, "tel:")),
+        email: pick(|rules| &rules.email, organization.email)
+            .map(|email| link_target(&email, "mailto:")),
+        street_address: pick(|rules| &rules.street_address, organization.street_address),
+        city: pick(|rules| &rules.city, organization.city),
+        state: pick(|rules| &rules.state, organization.state),
+        zip_code: pick(|rules| &rules.zip_code, organization.zip_code),
+        country: pick(|rules| &rules.country, organization.country),
+        website: Some(page_url.to_string()),
+        image_urls: Some(image_urls),
+        latitude: organization.latitude,
+        longitude: organization.longitude,
+        ..CampPatch::default()
+    };
+
+    sanitize(camp, page_url)
+}
+
+fn first_value(document: &Html, selectors: &[FieldSelector]) -> Option<String> {
+    selectors
+        .iter()
+        .find_map(|selector| selector.values(document).next())
+}
+
+fn all_values(document: &Html, selectors: &[FieldSelector]) -> Vec<String> {
+    selectors
+        .iter()
+        .flat_map(|selector| selector.values(document))
+        .collect()
+}
+
+/// `tel:+1-555-0100` or `mailto:hi@camp.example?subject=Hi` to the bare value.
+fn link_target(value: &str, scheme: &str) -> String {
+    let value = value.strip_prefix(scheme).unwrap_or(value);
+    let value = value.split('?').next().unwrap_or(value);
+
+    urlencoding::decode(value)
+        .map(|value| value.trim().to_string())
+        .unwrap_or_else(|_| value.trim().to_string())
+}
+
+/// Drops or shortens whatever `CampPatch::validate` would refuse, so a page
+/// with one odd value still yields a request the moderator can fix up.
+fn sanitize(mut camp: CampPatch, page_url: &Url) -> ExtractedCamp {
+    let mut warnings = Vec::new();
+    let mut warn = |path: &str, message: String| {
+        warnings.push(FieldError {
+            path: path.to_string(),
+            message,
+        })
+    };
+
+    for (path, value) in [
+        ("$.name", &mut camp.name),
+        ("$.description", &mut camp.description),
+        ("$.street_address", &mut camp.street_address),
+        ("$.city", &mut camp.city),
+        ("$.state", &mut camp.state),
+    ] {
+        if let Some(text) = value.as_mut() {
+            if text.chars().count() > MAX_VARCHAR_LEN {
+                *text = text.chars().take(MAX_VARCHAR_LEN - 1).collect::<String>() + "…";
+                warn(
+                    path,
+                    format!("was shortened to {} characters", MAX_VARCHAR_LEN),
+                );
+            }
+        }
+    }
+
+    if let Some(country) = camp.country.take() {
+        let code = country.trim().to_uppercase();
+        if validation::is_valid_country(&code) {
+            camp.country = Some(code);
+        } else {
+            warn(
+                "$.country",
+                format!("{:?} is not an ISO 3166-1 alpha-2 country code", country),
+            );
+        }
+    }
+    if let (Some(zip_code), Some(country)) = (camp.zip_code.as_deref(), camp.country.as_deref()) {
+        if !validation::is_valid_postal_code(country, zip_code) {
+            warn(
+                "$.zip_code",
+                format!("{:?} is not a valid postal code for {}", zip_code, country),
+            );
+            camp.zip_code = None;
+        }
+    }
+    if let Some(phone_number) = camp.phone_number.take() {
+        match validation::normalize_phone(&phone_number, camp.country.as_deref()) {
+            Ok(normalized) => camp.phone_number = Some(normalized),
+            Err(message) => warn("$.phone_number", format!("{:?} {}", phone_number, message)),
+        }
+    }
+    if let Some(email) = camp.email.take() {
+        if validation::is_valid_email(&email) {
+            camp.email = Some(email);
+        } else {
+            warn(
+                "$.email",
+                format!("{:?} is not a valid email address", email),
+            );
+        }
+    }
+
+    if let Some(url) = camp.website.as_deref() {
+        if url.chars().count() > MAX_VARCHAR_LEN {
+            warn(
+                "$.website",
+                format!("is longer than {} characters", MAX_VARCHAR_LEN),
+            );
+            camp.website = None;
+        }
+    }
+
+    let mut image_urls: Vec<String> = Vec::new();
+    for url in camp.image_urls.take().unwrap_or_default() {
+        // Relative paths and protocol-relative URLs are common in image tags
+        match page_url.join(&url).map(String::from) {
+            Ok(url) if validation::is_valid_url(&url) => {
+                if !image_urls.contains(&url) {
+                    image_urls.push(url);
+                }
+            }
+            _ => warn(
+                "$.image_urls",
+                format!("{:?} is not an http or https URL", url),
+            ),
+        }
+    }
+    if image_urls.len() > MAX_IMPORTED_IMAGES {
+        warn(
+            "$.image_urls",
+            format!(
+                "only the first {} of {} images were kept",
+                MAX_IMPORTED_IMAGES,
+                image_urls.len()
+            ),
+        );
+        image_urls.truncate(MAX_IMPORTED_IMAGES);
+    }
+    camp.image_urls = Some(image_urls);
+
+    if camp
+        .latitude
+        .is_some_and(|latitude| !(-90.0..=90.0).contains(&latitude))
+        || camp
+            .longitude
+            .is_some_and(|longitude| !(-180.0..=180.0).contains(&longitude))
+    {
+        warn("$.latitude", "the coordinates are out of range".to_string());
+        camp.latitude = None;
+        camp.longitude = None;
+    }
+
+    ExtractedCamp { camp, warnings }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::importer::rules::ImportRules;
+
+    fn fixture(name: &str) -> String {
+        let path = format!(
+            "{}/src/importer/fixtures/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        fs::read_to_string(&path).unwrap_or_else(|ex| panic!("cannot read {}: {}", path, ex))
+    }
+
+    fn extract_fixture(name: &str, page_url: &str, rules: &SiteRules) -> ExtractedCamp {
+        let page_url = Url::parse(page_url).expect("valid page URL");
+
+        extract(&fixture(name), &page_url, rules)
+    }
+
+    fn warned_paths(extracted: &ExtractedCamp) -> Vec<&str> {
+        extracted
+            .warnings
+            .iter()
+            .map(|warning| warning.path.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn reads_local_business_json_ld() {
+        let extracted = extract_fixture(
+            "pine_lake_json_ld.html",
+            "https://www.pinelake.example/",
+            &SiteRules::default(),
+        );
+        let camp = &extracted.camp;
+
+        assert_eq!(camp.name.as_deref(), Some("Pine Lake Camp"));
+        assert_eq!(
+            camp.description.as_deref(),
+            Some("An overnight camp on the shores of Pine Lake, with canoeing, archery and campfires.")
+        );
+        assert_eq!(camp.phone_number.as_deref(), Some("+15550100199"));
+        assert_eq!(camp.email.as_deref(), Some("hello@pinelake.example"));
+        assert_eq!(camp.street_address.as_deref(), Some("12 Shore Road"));
+        assert_eq!(camp.city.as_deref(), Some("Lakeville"));
+        assert_eq!(camp.state.as_deref(), Some("MN"));
+        assert_eq!(camp.zip_code.as_deref(), Some("55044"));
+        assert_eq!(camp.country.as_deref(), Some("US"));
+        assert_eq!(camp.latitude, Some(44.65));
+        assert_eq!(camp.longitude, Some(-93.24));
+        assert_eq!(
+            camp.image_urls.as_deref(),
+            Some(
+                &[
+                    "https://www.pinelake.example/img/dock.jpg".to_string(),
+                    "https://www.pinelake.example/img/cabins.jpg".to_string(),
+                ][..]
+            )
+        );
+        assert_eq!(
+            camp.website.as_deref(),
+            Some("https://www.pinelake.example/")
+        );
+        assert!(extracted.warnings.is_empty(), "{:?}", extracted.warnings);
+    }
+
+    #[test]
+    fn falls_back_to_meta_tags_links_and_microdata() {
+        let extracted = extract_fixture(
+            "maple_grove_meta.html",
+            "https://maplegrove.example/",
+            &SiteRules::default(),
+        );
+        let camp = &extracted.camp;
+
+        assert_eq!(camp.name.as_deref(), Some("Maple Grove Day Camp"));
+        assert_eq!(
+            camp.description.as_deref(),
+            Some("Day camp for ages 5 to 12 in North London.")
+        );
+        assert_eq!(camp.phone_number.as_deref(), Some("+442079460958"));
+        assert_eq!(camp.email.as_deref(), Some("office@maplegrove.example"));
+        assert_eq!(camp.street_address.as_deref(), Some("4 Grove Lane"));
+        assert_eq!(camp.city.as_deref(), Some("London"));
+        assert_eq!(camp.zip_code.as_deref(), Some("N1 9GU"));
+        assert_eq!(camp.country, None);
+        assert_eq!(
+            camp.image_urls.as_deref(),
+            Some(&["https://cdn.maplegrove.example/hero.jpg".to_string()][..])
+        );
+        assert_eq!(warned_paths(&extracted), ["$.country", "$.image_urls"]);
+    }
+
+    #[test]
+    fn site_rules_take_precedence_over_json_ld() {
+        let rules = ImportRules::parse(&fixture("sunrise_rules.json")).expect("valid rules");
+        let site_rules = rules
+            .for_host("www.sunrise.example")
+            .expect("rules for the site");
+
+        let extracted = extract_fixture(
+            "sunrise_site_rules.html",
+            "https://www.sunrise.example/camps/adventure",
+            site_rules,
+        );
+        let camp = &extracted.camp;
+
+        assert_eq!(camp.name.as_deref(), Some("Sunrise Adventure Camp"));
+        assert_eq!(
+            camp.description.as_deref(),
+            Some("Desert hiking, climbing and star gazing for teens.")
+        );
+        assert_eq!(camp.phone_number.as_deref(), Some("+19285550142"));
+        assert_eq!(camp.country.as_deref(), Some("US"));
+        // Fields without a site rule still come from the JSON-LD
+        assert_eq!(
+            camp.street_address.as_deref(),
+            Some("77 Canyon Drive, Sedona, AZ 86336")
+        );
+        assert_eq!(camp.email, None);
+        assert_eq!(
+            camp.image_urls.as_deref(),
+            Some(
+                &[
+                    "https://www.sunrise.example/photos/canyon.jpg".to_string(),
+                    "https://img.sunrise.example/stars.jpg".to_string(),
+                ][..]
+            )
+        );
+        assert_eq!(warned_paths(&extracted), ["$.email"]);
+    }
+
+    #[test]
+    fn other_sites_get_no_site_rules() {
+        let rules = ImportRules::parse(&fixture("sunrise_rules.json")).expect("valid rules");
+
+        assert!(rules.for_host("pinelake.example").is_none());
+    }
+
+    #[test]
+    fn finds_no_name_on_a_page_without_a_camp() {
+        let page_url = Url::parse("https://empty.example/").expect("valid page URL");
+        let extracted = extract(
+            "<html><body></body></html>",
+            &page_url,
+            &SiteRules::default(),
+        );
+
+        assert_eq!(extracted.camp.name, None);
+        assert_eq!(extracted.camp.image_urls, Some(Vec::new()));
+    }
+}
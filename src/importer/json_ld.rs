@@ -0,0 +1,169 @@
+use scraper::{Html, Selector};
+use serde_json::Value;
+
+/// schema.org types describing a place camps are held at, preferred over a
+/// plain organization when a page has both.
+const LOCAL_BUSINESS_TYPES: [&str; 6] = [
+    "LocalBusiness",
+    "Campground",
+    "ChildCare",
+    "EntertainmentBusiness",
+    "LodgingBusiness",
+    "SportsActivityLocation",
+];
+const ORGANIZATION_TYPES: [&str; 4] = [
+    "Organization",
+    "EducationalOrganization",
+    "NGO",
+    "SportsOrganization",
+];
+
+/// The camp fields found in a page's schema.org `LocalBusiness` or
+/// `Organization` JSON-LD.
+#[derive(Debug, Default)]
+pub struct LinkedOrganization {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub telephone: Option<String>,
+    pub email: Option<String>,
+    pub street_address: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub zip_code: Option<String>,
+    pub country: Option<String>,
+    pub image_urls: Vec<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl LinkedOrganization {
+    /// Reads the best matching node from the page's JSON-LD scripts. Scripts
+    /// that are not valid JSON are skipped.
+    pub fn find(document: &Html) -> Option<Self> {
+        let scripts = Selector::parse("script[type='application/ld+json']").ok()?;
+
+        let values: Vec<Value> = document
+            .select(&scripts)
+            .filter_map(|script| serde_json::from_str(&script.text().collect::<String>()).ok())
+            .collect();
+
+        let mut nodes = Vec::new();
+        values
+            .iter()
+            .for_each(|value| collect_nodes(value, &mut nodes));
+
+        let node = nodes
+            .iter()
+            .find(|node| has_type(node, &LOCAL_BUSINESS_TYPES))
+            .or_else(|| {
+                nodes
+                    .iter()
+                    .find(|node| has_type(node, &ORGANIZATION_TYPES))
+            })?;
+
+        Some(LinkedOrganization::from_node(node))
+    }
+
+    fn from_node(node: &Value) -> Self {
+        let address = first(&node["address"]);
+        let (street_address, city, state, zip_code, country) = match address {
+            Value::String(address) => (Some(address.clone()), None, None, None, None),
+            address => (
+                text(&address["streetAddress"]),
+                text(&address["addressLocality"]),
+                text(&address["addressRegion"]),
+                text(&address["postalCode"]),
+                text(&address["addressCountry"])
+                    .or_else(|| text(&address["addressCountry"]["name"])),
+            ),
+        };
+        let geo = first(&node["geo"]);
+
+        LinkedOrganization {
+            name: text(&node["name"]),
+            description: text(&node["description"]),
+            telephone: text(&node["telephone"]),
+            email: text(&node["email"]),
+            street_address,
+            city,
+            state,
+            zip_code,
+            country,
+            image_urls: image_urls(&node["image"]),
+            latitude: number(&geo["latitude"]),
+            longitude: number(&geo["longitude"]),
+        }
+    }
+}
+
+/// Every object in `value`, including those nested in `@graph` and in other
+/// properties, e.g. the `publisher` of a `WebPage`.
+fn collect_nodes<'a>(value: &'a Value, nodes: &mut Vec<&'a Value>) {
+    match value {
+        Value::Array(values) => values.iter().for_each(|value| collect_nodes(value, nodes)),
+        Value::Object(properties) => {
+            nodes.push(value);
+            properties
+                .values()
+                .for_each(|value| collect_nodes(value, nodes));
+        }
+        _ => {}
+    }
+}
+
+/// Matches `@type` given as one name or a list, with or without a
+/// `schema:` or `https://schema.org/` prefix.
+fn has_type(node: &Value, types: &[&str]) -> bool {
+    let names = match &node["@type"] {
+        Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+        Value::String(name) => vec![name.as_str()],
+        _ => Vec::new(),
+    };
+
+    names.into_iter().any(|name| {
+        let name = name.rsplit(['/', ':']).next().unwrap_or(name);
+        types.contains(&name)
+    })
+}
+
+/// The value itself, or the first entry of a list.
+fn first(value: &Value) -> &Value {
+    match value {
+        Value::Array(values) => values.first().unwrap_or(&Value::Null),
+        value => value,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    let text = match first(value) {
+        Value::String(text) => text.split_whitespace().collect::<Vec<_>>().join(" "),
+        Value::Number(number) => number.to_string(),
+        _ => return None,
+    };
+
+    (!text.is_empty()).then_some(text)
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match first(value) {
+        Value::Number(number) => number.as_f64(),
+        Value::String(number) => number.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// `image` may be a URL, an `ImageObject` or a list of either.
+fn image_urls(value: &Value) -> Vec<String> {
+    let images = match value {
+        Value::Array(images) => images.iter().collect(),
+        image => vec![image],
+    };
+
+    images
+        .into_iter()
+        .filter_map(|image| match image {
+            Value::String(url) => Some(url.clone()),
+            image => text(&image["url"]).or_else(|| text(&image["contentUrl"])),
+        })
+        .collect()
+}
@@ -0,0 +1,180 @@
+use std::{env, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use thiserror::Error as ThisError;
+use url::Url;
+
+use crate::{
+    auth::{self, UserCtx},
+    models::{
+        self,
+        camp_request::{CampRequestManager, CampRequestSubmission},
+        validation::{self, FieldError},
+    },
+};
+
+use self::{
+    extract::{extract, ExtractedCamp},
+    rules::{ImportRules, SiteRules},
+};
+
+pub mod bulk;
+pub(crate) mod csv;
+mod extract;
+mod json_ld;
+mod rules;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 15;
+/// Pages larger than this are refused rather than parsed.
+const MAX_PAGE_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampImportRequest {
+    pub website: String,
+}
+
+/// A camp request pre-filled from a camp's website, waiting for moderator review.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampImport {
+    #[serde(flatten)]
+    pub submission: CampRequestSubmission,
+    /// Values found on the page that were left out or shortened.
+    pub warnings: Vec<FieldError>,
+}
+
+/// Fetches camp websites and turns them into camp requests.
+pub struct CampImporter {
+    client: reqwest::Client,
+    rules: ImportRules,
+}
+
+impl CampImporter {
+    pub fn new(rules: ImportRules, timeout: Duration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .user_agent("CampReviews-Importer/1.0")
+            .build()
+            .unwrap_or_default();
+
+        CampImporter { client, rules }
+    }
+
+    /// Site rules from `IMPORT_RULES`; each page fetch may take up to
+    /// `IMPORT_TIMEOUT_SECS`.
+    pub fn from_env() -> Self {
+        let timeout_secs = env::var("IMPORT_TIMEOUT_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+
+        CampImporter::new(ImportRules::from_env(), Duration::from_secs(timeout_secs))
+    }
+
+    /// Fetches `website` and files what it says about the camp as a camp request
+    /// by `utx`, for a moderator to check and approve.
+    pub async fn import(
+        &self,
+        db: &PgPool,
+        utx: &UserCtx,
+        website: &str,
+    ) -> Result<CampImport, Error> {
+        let website = website.trim();
+        if !validation::is_valid_url(website) {
+            return Err(Error::InvalidWebsite(website.to_string()));
+        }
+
+        let (page_url, html) = self.fetch(website).await?;
+        let no_rules = SiteRules::default();
+        let rules = page_url
+            .host_str()
+            .and_then(|host| self.rules.for_host(host))
+            .unwrap_or(&no_rules);
+
+        let ExtractedCamp { camp, warnings } = extract(&html, &page_url, rules);
+        if camp.name.is_none() {
+            return Err(Error::NoCampFound(page_url.to_string()));
+        }
+
+        let submission = CampRequestManager::new_request(db, utx, camp).await?;
+
+        Ok(CampImport {
+            submission,
+            warnings,
+        })
+    }
+
+    /// The page's final URL after redirects, and its HTML.
+    async fn fetch(&self, website: &str) -> Result<(Url, String), Error> {
+        let mut response = self
+            .client
+            .get(website)
+            .header(reqwest::header::ACCEPT, "text/html")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if !content_type.is_empty() && !content_type.contains("html") {
+            return Err(Error::NotHtml(content_type));
+        }
+        if response
+            .content_length()
+            .is_some_and(|length| length as usize > MAX_PAGE_BYTES)
+        {
+            return Err(Error::PageTooLarge);
+        }
+
+        let page_url = response.url().clone();
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > MAX_PAGE_BYTES {
+                return Err(Error::PageTooLarge);
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok((page_url, String::from_utf8_lossy(&body).into_owned()))
+    }
+}
+
+#[derive(ThisError, Debug)]
+pub enum Error {
+    #[error("{0:?}")]
+    Model(#[from] models::Error),
+
+    #[error("Not an absolute http or https URL: {0}")]
+    InvalidWebsite(String),
+
+    #[error("Fetching the website failed: {0}")]
+    FetchFailed(#[from] reqwest::Error),
+
+    #[error("The website answered with {0} rather than HTML")]
+    NotHtml(String),
+
+    #[error("The page is larger than {} bytes", MAX_PAGE_BYTES)]
+    PageTooLarge,
+
+    #[error("No camp name found at {0}")]
+    NoCampFound(String),
+
+    #[error("Cannot read the file: {0}")]
+    InvalidFile(String),
+
+    #[error("No column mapping named {0}")]
+    UnknownMapping(String),
+
+    #[error("Invalid column mapping: {0}")]
+    InvalidMapping(String),
+
+    #[error("{0}")]
+    Usage(String),
+
+    #[error("{0:?}")]
+    Auth(#[from] auth::Error),
+}
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, env, fs, sync::OnceLock};
+
+use scraper::{ElementRef, Html, Selector};
+use serde::Deserialize;
+
+/// A CSS selector, optionally followed by `@attribute` to read that attribute
+/// instead of the element's text, e.g. `meta[name='description']@content`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct FieldSelector {
+    selector: Selector,
+    attribute: Option<String>,
+}
+
+impl FieldSelector {
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        // `a[href*='@']` has an `@` too, so only a trailing plain name counts
+        let (css, attribute) = match rule.rsplit_once('@') {
+            Some((css, attribute))
+                if !attribute.is_empty()
+                    && attribute
+                        .chars()
+                        .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_') =>
+            {
+                (css, Some(attribute.to_string()))
+            }
+            _ => (rule, None),
+        };
+        let selector = Selector::parse(css.trim())
+            .map_err(|ex| format!("invalid selector {:?}: {}", rule, ex))?;
+
+        Ok(FieldSelector {
+            selector,
+            attribute,
+        })
+    }
+
+    /// The values of every element the selector matches, whitespace collapsed
+    /// and empty ones left out.
+    pub fn values<'a>(&'a self, document: &'a Html) -> impl Iterator<Item = String> + 'a {
+        document
+            .select(&self.selector)
+            .filter_map(|element| self.value(element))
+    }
+
+    fn value(&self, element: ElementRef) -> Option<String> {
+        let value = match &self.attribute {
+            Some(attribute) => element.value().attr(attribute)?.to_string(),
+            None => element.text().collect::<Vec<_>>().join(" "),
+        };
+        let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        (!value.is_empty()).then_some(value)
+    }
+}
+
+impl TryFrom<String> for FieldSelector {
+    type Error = String;
+
+    fn try_from(rule: String) -> Result<Self, Self::Error> {
+        FieldSelector::parse(&rule)
+    }
+}
+
+/// Where a site keeps each camp field. Selectors are tried in order and take
+/// precedence over the page's JSON-LD; fields without any fall back to it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SiteRules {
+    pub name: Vec<FieldSelector>,
+    pub description: Vec<FieldSelector>,
+    pub phone_number: Vec<FieldSelector>,
+    pub email: Vec<FieldSelector>,
+    pub street_address: Vec<FieldSelector>,
+    pub city: Vec<FieldSelector>,
+    pub state: Vec<FieldSelector>,
+    pub zip_code: Vec<FieldSelector>,
+    pub country: Vec<FieldSelector>,
+    /// Every match is kept, not just the first.
+    pub image_urls: Vec<FieldSelector>,
+}
+
+impl SiteRules {
+    /// Common markup most sites use: meta tags, `tel:` and `mailto:` links and
+    /// schema.org microdata. Used after the site's rules and the JSON-LD.
+    pub fn fallback() -> &'static SiteRules {
+        static FALLBACK: OnceLock<SiteRules> = OnceLock::new();
+
+        FALLBACK.get_or_init(|| {
+            let selectors = |rules: &[&str]| {
+                rules
+                    .iter()
+                    .filter_map(|rule| FieldSelector::parse(rule).ok())
+                    .collect()
+            };
+
+            SiteRules {
+                name: selectors(&[
+                    "[itemscope] > [itemprop='name']",
+                    "meta[property='og:site_name']@content",
+                    "meta[property='og:title']@content",
+                    "h1",
+                    "title",
+                ]),
+                description: selectors(&[
+                    "meta[name='description']@content",
+                    "meta[property='og:description']@content",
+                ]),
+                phone_number: selectors(&["[itemprop='telephone']", "a[href^='tel:']@href"]),
+                email: selectors(&["[itemprop='email']", "a[href^='mailto:']@href"]),
+                street_address: selectors(&["[itemprop='streetAddress']"]),
+                city: selectors(&["[itemprop='addressLocality']"]),
+                state: selectors(&["[itemprop='addressRegion']"]),
+                zip_code: selectors(&["[itemprop='postalCode']"]),
+                country: selectors(&["[itemprop='addressCountry']"]),
+                image_urls: selectors(&[
+                    "meta[property='og:image']@content",
+                    "img[itemprop='image']@src",
+                ]),
+            }
+        })
+    }
+}
+
+/// Extraction rules per site, keyed by host name without a leading `www.`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct ImportRules {
+    sites: HashMap<String, SiteRules>,
+}
+
+impl ImportRules {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let rules: ImportRules = serde_json::from_str(json).map_err(|ex| ex.to_string())?;
+
+        Ok(ImportRules {
+            sites: rules
+                .sites
+                .into_iter()
+                .map(|(host, rules)| (normalize_host(&host).to_string(), rules))
+                .collect(),
+        })
+    }
+
+    /// Reads the JSON file named by `IMPORT_RULES`, e.g.
+    /// `{"pinelake.example": {"name": ["h1.camp-title"], "image_urls": [".gallery img@src"]}}`.
+    /// Without one, every site gets only the JSON-LD and the fallback rules.
+    pub fn from_env() -> Self {
+        let Ok(path) = env::var("IMPORT_RULES") else {
+            return ImportRules::default();
+        };
+
+        match fs::read_to_string(&path)
+            .map_err(|ex| ex.to_string())
+            .and_then(|json| ImportRules::parse(&json))
+        {
+            Ok(rules) => rules,
+            Err(ex) => {
+                println!(
+                    "Failed to read IMPORT_RULES from {}, using no site rules. Cause: {}",
+                    path, ex
+                );
+                ImportRules::default()
+            }
+        }
+    }
+
+    pub fn for_host(&self, host: &str) -> Option<&SiteRules> {
+        self.sites.get(normalize_host(host))
+    }
+}
+
+fn normalize_host(host: &str) -> &str {
+    host.strip_prefix("www.").unwrap_or(host)
+}
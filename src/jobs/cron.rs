@@ -0,0 +1,285 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Timelike, Utc};
+
+/// How far ahead `next_after` looks before deciding a schedule never fires,
+/// e.g. for the 30th of February.
+const SEARCH_LIMIT_DAYS: i64 = 5 * 366;
+
+/// A five-field cron expression, `minute hour day-of-month month day-of-week`,
+/// evaluated in UTC. Fields take `*`, numbers, ranges (`1-5`), lists (`1,15`)
+/// and steps (`*/10`, `8-18/2`). Sunday is 0 or 7.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// As in classic cron, when both day fields are restricted either may match.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("expected 5 fields in {:?}", expression));
+        };
+
+        let mut weekday_bits = parse_field(weekdays, 0, 7)?;
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits & !(1 << 7)) | 1;
+        }
+
+        Ok(CronSchedule {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+
+    /// The first minute strictly after `after` that the schedule fires at.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after
+            .with_second(0)
+            .and_then(|time| time.with_nanosecond(0))?
+            + Duration::minutes(1);
+        let limit = time + Duration::days(SEARCH_LIMIT_DAYS);
+
+        while time < limit {
+            let date = time.date_naive();
+            if !has(self.months, date.month()) {
+                let (year, month) = match date.month() {
+                    12 => (date.year() + 1, 1),
+                    month => (date.year(), month + 1),
+                };
+                time = start_of(NaiveDate::from_ymd_opt(year, month, 1)?);
+            } else if !self.day_matches(date) {
+                time = start_of(date.succ_opt()?);
+            } else if !has(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn start_of(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
+}
+
+/// Parses one field into a bit set of the values it matches.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step in {:?}", part))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start, part)?, parse_value(end, part)?),
+                // `5/15` means from 5 to the end in steps of 15
+                None if step > 1 => (parse_value(range, part)?, max),
+                None => {
+                    let value = parse_value(range, part)?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("{:?} is outside {}-{}", part, min, max));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn parse_value(value: &str, part: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value in {:?}", part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn next(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        CronSchedule::parse(expression).unwrap().next_after(after)
+    }
+
+    #[test]
+    fn parses_steps_ranges_and_lists() {
+        assert_eq!(
+            parse_field("*/15", 0, 59),
+            Ok(1 | 1 << 15 | 1 << 30 | 1 << 45)
+        );
+        assert_eq!(parse_field("8-18/4", 0, 23), Ok(1 << 8 | 1 << 12 | 1 << 16));
+        assert_eq!(parse_field("5/20", 0, 59), Ok(1 << 5 | 1 << 25 | 1 << 45));
+        assert_eq!(
+            parse_field("1,15,20-21", 1, 31),
+            Ok(1 << 1 | 1 << 15 | 1 << 20 | 1 << 21)
+        );
+        assert_eq!(parse_field("*", 1, 12), Ok(0b1_1111_1111_1110));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for expression in [
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "10-5 * * * *",
+            "a * * * *",
+            "1,,2 * * * *",
+        ] {
+            assert!(CronSchedule::parse(expression).is_err(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn fires_strictly_after_the_given_minute() {
+        let schedule = CronSchedule::parse("*/10 * * * *").unwrap();
+
+        assert_eq!(
+            schedule.next_after(at(2024, 5, 1, 9, 0)),
+            Some(at(2024, 5, 1, 9, 10))
+        );
+        assert_eq!(
+            schedule.next_after(at(2024, 5, 1, 9, 0) + Duration::seconds(59)),
+            Some(at(2024, 5, 1, 9, 10))
+        );
+        assert_eq!(
+            schedule.next_after(at(2024, 5, 1, 9, 55)),
+            Some(at(2024, 5, 1, 10, 0))
+        );
+    }
+
+    #[test]
+    fn walks_hour_ranges_into_the_next_day() {
+        assert_eq!(
+            next("30 8-18/2 * * *", at(2024, 5, 1, 18, 30)),
+            Some(at(2024, 5, 2, 8, 30))
+        );
+        assert_eq!(
+            next("30 8-18/2 * * *", at(2024, 5, 1, 9, 0)),
+            Some(at(2024, 5, 1, 10, 30))
+        );
+    }
+
+    #[test]
+    fn treats_seven_as_sunday() {
+        // 2024-05-05 is a Sunday
+        let sunday = Some(at(2024, 5, 5, 3, 0));
+
+        assert_eq!(next("0 3 * * 7", at(2024, 5, 1, 0, 0)), sunday);
+        assert_eq!(next("0 3 * * 0", at(2024, 5, 1, 0, 0)), sunday);
+        assert_eq!(next("0 3 * * 6-7", at(2024, 5, 5, 0, 0)), sunday);
+    }
+
+    #[test]
+    fn matches_either_day_field_when_both_are_restricted() {
+        // The 13th of May 2024 is a Monday, Friday the 3rd comes first
+        let schedule = CronSchedule::parse("0 0 13 * 5").unwrap();
+
+        assert_eq!(
+            schedule.next_after(at(2024, 5, 1, 0, 0)),
+            Some(at(2024, 5, 3, 0, 0))
+        );
+        assert_eq!(
+            schedule.next_after(at(2024, 5, 10, 0, 0)),
+            Some(at(2024, 5, 13, 0, 0))
+        );
+        assert_eq!(
+            schedule.next_after(at(2024, 5, 13, 0, 0)),
+            Some(at(2024, 5, 17, 0, 0))
+        );
+    }
+
+    #[test]
+    fn matches_only_the_restricted_day_field() {
+        assert_eq!(
+            next("0 0 13 * *", at(2024, 5, 1, 0, 0)),
+            Some(at(2024, 5, 13, 0, 0))
+        );
+        assert_eq!(
+            next("0 0 * * 5", at(2024, 5, 4, 0, 0)),
+            Some(at(2024, 5, 10, 0, 0))
+        );
+    }
+
+    #[test]
+    fn rolls_over_months_and_years() {
+        assert_eq!(
+            next("0 0 31 * *", at(2024, 4, 1, 0, 0)),
+            Some(at(2024, 5, 31, 0, 0))
+        );
+        assert_eq!(
+            next("0 0 1 * *", at(2024, 12, 15, 0, 0)),
+            Some(at(2025, 1, 1, 0, 0))
+        );
+        assert_eq!(
+            next("15 6 * 3 *", at(2024, 4, 1, 0, 0)),
+            Some(at(2025, 3, 1, 6, 15))
+        );
+        assert_eq!(
+            next("0 0 29 2 *", at(2024, 3, 1, 0, 0)),
+            Some(at(2028, 2, 29, 0, 0))
+        );
+    }
+
+    #[test]
+    fn never_fires_on_dates_that_do_not_exist() {
+        assert_eq!(next("0 0 30 2 *", at(2024, 1, 1, 0, 0)), None);
+        assert_eq!(next("0 0 31 4,6,9,11 *", at(2024, 1, 1, 0, 0)), None);
+    }
+}
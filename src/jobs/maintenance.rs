@@ -0,0 +1,96 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use super::{Error, Job};
+use crate::models::{
+    email_outbox::EmailOutboxManager, image::ImageManager, job::JobManager, ReviewManager,
+    UserManager,
+};
+
+/// How long succeeded jobs are kept for the stats.
+const SUCCEEDED_JOB_RETENTION_DAYS: i64 = 7;
+/// How long sent and failed emails are kept for troubleshooting.
+const EMAIL_RETENTION_DAYS: i64 = 30;
+
+/// Deletes a stored image once nothing refers to it any more.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoveImage {
+    pub url: String,
+}
+
+#[async_trait]
+impl Job for RemoveImage {
+    const KIND: &'static str = "remove_image";
+
+    async fn run(&self, db: &PgPool) -> Result<(), Error> {
+        // Images are stored by content, so someone may have uploaded the same one since
+        if !ImageManager::is_referenced(db, &self.url).await? {
+            ImageManager::remove(&self.url).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Recalculates every camp's rating, correcting any drift from the incremental updates.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecomputeRatings;
+
+#[async_trait]
+impl Job for RecomputeRatings {
+    const KIND: &'static str = "recompute_ratings";
+
+    async fn run(&self, db: &PgPool) -> Result<(), Error> {
+        ReviewManager::recompute_ratings(db).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurgeUsernameReservations;
+
+#[async_trait]
+impl Job for PurgeUsernameReservations {
+    const KIND: &'static str = "purge_username_reservations";
+
+    async fn run(&self, db: &PgPool) -> Result<(), Error> {
+        UserManager::purge_expired_reservations(db).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PruneJobs;
+
+#[async_trait]
+impl Job for PruneJobs {
+    const KIND: &'static str = "prune_jobs";
+
+    async fn run(&self, db: &PgPool) -> Result<(), Error> {
+        JobManager::prune_succeeded(
+            db,
+            Utc::now() - Duration::days(SUCCEEDED_JOB_RETENTION_DAYS),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PruneEmailOutbox;
+
+#[async_trait]
+impl Job for PruneEmailOutbox {
+    const KIND: &'static str = "prune_email_outbox";
+
+    async fn run(&self, db: &PgPool) -> Result<(), Error> {
+        EmailOutboxManager::prune(db, Utc::now() - Duration::days(EMAIL_RETENTION_DAYS)).await?;
+
+        Ok(())
+    }
+}
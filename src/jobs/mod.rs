@@ -0,0 +1,353 @@
+use std::{collections::HashMap, env, future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use thiserror::Error as ThisError;
+
+use crate::{
+    mailer::{self, SendEmail},
+    models::{
+        self,
+        job::{JobManager, JobRecord, JobStatus, NewJob},
+    },
+    webhooks::DeliverWebhook,
+};
+
+pub use self::{
+    cron::CronSchedule,
+    maintenance::{
+        PruneEmailOutbox, PruneJobs, PurgeUsernameReservations, RecomputeRatings, RemoveImage,
+    },
+};
+
+mod cron;
+mod maintenance;
+
+const DEFAULT_WORKERS: usize = 4;
+const DEFAULT_POLL_SECS: u64 = 2;
+/// A job running longer than this is stopped and counted as a failed attempt.
+const JOB_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// How long a claimed job belongs to its worker before another may take over.
+const LEASE: Duration = Duration::from_secs(15 * 60);
+/// How often recurring schedules are checked.
+const SCHEDULER_TICK: Duration = Duration::from_secs(30);
+
+/// Work that runs in the background, stored as its JSON form under `KIND`.
+#[async_trait]
+pub trait Job: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Names the job in the queue; keep it stable once jobs are queued.
+    const KIND: &'static str;
+    const MAX_ATTEMPTS: i32 = 5;
+
+    async fn run(&self, db: &PgPool) -> Result<(), Error>;
+
+    /// Called once the job is out of attempts, with the last error, e.g. to
+    /// record that what it was doing was given up on.
+    async fn buried(&self, _db: &PgPool, _error: &str) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Queues `job` to run at `run_at` as part of the caller's transaction.
+pub async fn enqueue<J: Job>(
+    tx: &mut Transaction<'_, Postgres>,
+    job: &J,
+    run_at: DateTime<Utc>,
+) -> Result<(), models::Error> {
+    let payload = serde_json::to_value(job)
+        .map_err(|ex| models::Error::InvalidData(format!("invalid {} job: {}", J::KIND, ex)))?;
+
+    JobManager::enqueue(
+        tx,
+        NewJob {
+            kind: J::KIND,
+            payload,
+            run_at,
+            max_attempts: J::MAX_ATTEMPTS,
+            dedupe_key: None,
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<(), Error>> + Send>>;
+type JobFn = Box<dyn Fn(Arc<PgPool>, serde_json::Value) -> JobFuture + Send + Sync>;
+type BuryFn = Box<dyn Fn(Arc<PgPool>, serde_json::Value, String) -> JobFuture + Send + Sync>;
+
+/// Runs one kind of job from its payload.
+struct Handler {
+    run: JobFn,
+    bury: BuryFn,
+}
+
+/// A job queued on a cron schedule.
+struct Recurring {
+    kind: &'static str,
+    schedule: CronSchedule,
+    payload: serde_json::Value,
+    max_attempts: i32,
+}
+
+/// Runs queued jobs on a pool of in-process workers and queues recurring ones.
+pub struct JobRunner {
+    handlers: HashMap<&'static str, Handler>,
+    recurring: Vec<Recurring>,
+    workers: usize,
+    poll_interval: Duration,
+}
+
+impl JobRunner {
+    pub fn new(workers: usize, poll_interval: Duration) -> Self {
+        JobRunner {
+            handlers: HashMap::new(),
+            recurring: Vec::new(),
+            workers: workers.max(1),
+            poll_interval,
+        }
+    }
+
+    /// `JOB_WORKERS` jobs run at once, polling every `JOB_POLL_SECS` when idle.
+    pub fn from_env() -> Self {
+        let workers = env::var("JOB_WORKERS")
+            .ok()
+            .and_then(|workers| workers.parse().ok())
+            .unwrap_or(DEFAULT_WORKERS);
+        let poll_secs = env::var("JOB_POLL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(DEFAULT_POLL_SECS);
+
+        JobRunner::new(workers, Duration::from_secs(poll_secs))
+            .register::<RemoveImage>()
+            .register::<RecomputeRatings>()
+            .register::<PurgeUsernameReservations>()
+            .register::<PruneJobs>()
+            .register::<PruneEmailOutbox>()
+            .register::<SendEmail>()
+            .register::<DeliverWebhook>()
+            .every("30 3 * * *", RecomputeRatings)
+            .every("15 * * * *", PurgeUsernameReservations)
+            .every("45 4 * * *", PruneJobs)
+            .every("50 4 * * *", PruneEmailOutbox)
+    }
+
+    pub fn register<J: Job>(mut self) -> Self {
+        self.handlers.insert(
+            J::KIND,
+            Handler {
+                run: Box::new(|db, payload| {
+                    Box::pin(async move {
+                        let job: J = serde_json::from_value(payload)?;
+                        job.run(&db).await
+                    })
+                }),
+                bury: Box::new(|db, payload, error| {
+                    Box::pin(async move {
+                        let job: J = serde_json::from_value(payload)?;
+                        job.buried(&db, &error).await
+                    })
+                }),
+            },
+        );
+        self
+    }
+
+    /// Queues `job` at every tick of the cron `expression`. Ticks missed while no
+    /// instance was running are skipped.
+    pub fn every<J: Job>(mut self, expression: &str, job: J) -> Self {
+        let recurring = CronSchedule::parse(expression).and_then(|schedule| {
+            let payload = serde_json::to_value(&job).map_err(|ex| ex.to_string())?;
+            Ok(Recurring {
+                kind: J::KIND,
+                schedule,
+                payload,
+                max_attempts: J::MAX_ATTEMPTS,
+            })
+        });
+        match recurring {
+            Ok(recurring) => self.recurring.push(recurring),
+            Err(ex) => println!(
+                "ERROR - invalid schedule {:?} for {} jobs. Cause: {}",
+                expression,
+                J::KIND,
+                ex
+            ),
+        }
+        self
+    }
+
+    /// Starts the workers and the scheduler; they run until the process ends.
+    pub fn start(self, db: Arc<PgPool>) {
+        let runner = Arc::new(self);
+
+        for _ in 0..runner.workers {
+            tokio::spawn(runner.clone().work(db.clone()));
+        }
+        tokio::spawn(runner.schedule(db));
+    }
+
+    async fn work(self: Arc<Self>, db: Arc<PgPool>) {
+        // Only kinds this build knows are claimed, so older instances leave newer jobs alone
+        let kinds: Vec<String> = self.handlers.keys().map(|kind| kind.to_string()).collect();
+        let lease = chrono::Duration::from_std(LEASE).unwrap_or_default();
+
+        loop {
+            match JobManager::claim(&db, &kinds, lease).await {
+                Ok(Some(job)) => {
+                    if let Err(ex) = self.execute(&db, &job).await {
+                        println!(
+                            "ERROR - could not record the outcome of job {}. Cause: {:?}",
+                            job.id, ex
+                        );
+                    }
+                }
+                Ok(None) => tokio::time::sleep(self.poll_interval).await,
+                Err(ex) => {
+                    println!("ERROR - claiming a job failed. Cause: {:?}", ex);
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+            }
+        }
+    }
+
+    /// Runs one claimed job in its own task, so a panic fails the job rather
+    /// than the worker.
+    async fn execute(&self, db: &Arc<PgPool>, job: &JobRecord) -> Result<(), models::Error> {
+        let Some(handler) = self.handlers.get(job.kind.as_str()) else {
+            let error = Error::UnknownKind(job.kind.clone()).to_string();
+            JobManager::fail(db, job, &error).await?;
+            return Ok(());
+        };
+
+        let task = tokio::spawn(tokio::time::timeout(
+            JOB_TIMEOUT,
+            (handler.run)(db.clone(), job.payload.clone()),
+        ));
+        let result = match task.await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(Error::Timeout),
+            Err(ex) => Err(Error::Panicked(ex.to_string())),
+        };
+
+        match result {
+            Ok(()) => JobManager::complete(db, job.id).await,
+            Err(ex) => {
+                println!(
+                    "ERROR - {} job {} failed (attempt {} of {}). Cause: {}",
+                    job.kind, job.id, job.attempts, job.max_attempts, ex
+                );
+                let error = ex.to_string();
+                if JobManager::fail(db, job, &error).await? == JobStatus::Dead {
+                    self.bury(db, job, &error).await;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Lets a job that is out of attempts clean up after itself.
+    async fn bury(&self, db: &Arc<PgPool>, job: &JobRecord, error: &str) {
+        let Some(handler) = self.handlers.get(job.kind.as_str()) else {
+            return;
+        };
+
+        if let Err(ex) = (handler.bury)(db.clone(), job.payload.clone(), error.to_string()).await {
+            println!(
+                "ERROR - cleaning up after dead {} job {} failed. Cause: {}",
+                job.kind, job.id, ex
+            );
+        }
+    }
+
+    async fn schedule(self: Arc<Self>, db: Arc<PgPool>) {
+        let lease = chrono::Duration::from_std(LEASE).unwrap_or_default();
+        let mut checked_until = Utc::now();
+
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+            let now = Utc::now();
+
+            for recurring in &self.recurring {
+                if let Err(ex) = queue_ticks(&db, recurring, checked_until, now).await {
+                    println!(
+                        "ERROR - queueing recurring {} jobs failed. Cause: {:?}",
+                        recurring.kind, ex
+                    );
+                }
+            }
+            checked_until = now;
+
+            match JobManager::bury_abandoned(&db, lease).await {
+                Ok(jobs) => {
+                    for job in &jobs {
+                        self.bury(&db, job, job.last_error.as_deref().unwrap_or_default())
+                            .await;
+                    }
+                }
+                Err(ex) => println!("ERROR - burying abandoned jobs failed. Cause: {:?}", ex),
+            }
+        }
+    }
+}
+
+/// Queues a job for every tick in `(from, until]`, keyed by the tick so that
+/// every instance's scheduler agrees on a single job.
+async fn queue_ticks(
+    db: &PgPool,
+    recurring: &Recurring,
+    from: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<(), models::Error> {
+    let mut tick = from;
+    while let Some(next) = recurring
+        .schedule
+        .next_after(tick)
+        .filter(|next| *next <= until)
+    {
+        let mut tx = db.begin().await?;
+        JobManager::enqueue(
+            &mut tx,
+            NewJob {
+                kind: recurring.kind,
+                payload: recurring.payload.clone(),
+                run_at: next,
+                max_attempts: recurring.max_attempts,
+                dedupe_key: Some(format!("{}@{}", recurring.kind, next.to_rfc3339())),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        tick = next;
+    }
+
+    Ok(())
+}
+
+#[derive(ThisError, Debug)]
+pub enum Error {
+    #[error("{0:?}")]
+    Model(#[from] models::Error),
+
+    #[error("Invalid job payload: {0}")]
+    InvalidPayload(#[from] serde_json::Error),
+
+    #[error("No handler for {0} jobs")]
+    UnknownKind(String),
+
+    #[error("Sending the email failed: {0}")]
+    Mail(#[from] mailer::Error),
+
+    #[error("Webhook delivery failed: {0}")]
+    Webhook(String),
+
+    #[error("Job timed out")]
+    Timeout,
+
+    #[error("Job panicked: {0}")]
+    Panicked(String),
+}
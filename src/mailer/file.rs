@@ -0,0 +1,37 @@
+use std::{env, path::PathBuf};
+
+use async_trait::async_trait;
+
+use super::{EmailTransport, Envelope, Error};
+
+const DEFAULT_OUTBOX_DIR: &str = "mail-outbox";
+
+/// Development transport that writes every message to `<dir>/<id>.eml`, where
+/// any mail client can open it.
+pub struct FileTransport {
+    dir: PathBuf,
+}
+
+impl FileTransport {
+    pub fn new(dir: PathBuf) -> Self {
+        FileTransport { dir }
+    }
+
+    pub fn from_env() -> Self {
+        FileTransport::new(
+            env::var("MAIL_OUTBOX_DIR")
+                .unwrap_or_else(|_| DEFAULT_OUTBOX_DIR.to_string())
+                .into(),
+        )
+    }
+}
+
+#[async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, envelope: &Envelope<'_>, message: &str) -> Result<(), Error> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(self.dir.join(format!("{}.eml", envelope.id)), message).await?;
+
+        Ok(())
+    }
+}
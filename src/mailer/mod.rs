@@ -0,0 +1,332 @@
+use std::{env, sync::OnceLock};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use thiserror::Error as ThisError;
+
+use crate::{
+    jobs::{self, Job},
+    models::email_outbox::{EmailOutboxManager, OutboxEmail},
+};
+
+pub use self::{file::FileTransport, smtp::SmtpTransport};
+
+mod file;
+mod smtp;
+
+const DEFAULT_FROM: &str = "Camp Reviews <no-reply@localhost>";
+
+/// Who an email goes to and comes from, as told to the transport rather than
+/// read from the message headers.
+#[derive(Debug)]
+pub struct Envelope<'a> {
+    pub id: i64,
+    pub from: &'a str,
+    pub to: &'a str,
+}
+
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    /// Delivers one complete RFC 5322 message.
+    async fn send(&self, envelope: &Envelope<'_>, message: &str) -> Result<(), Error>;
+}
+
+/// Sends outbox emails through a transport chosen by `MAIL_TRANSPORT`.
+pub struct Mailer {
+    transport: Box<dyn EmailTransport>,
+    /// The `From` header, e.g. `Camp Reviews <no-reply@example.com>`.
+    from: String,
+}
+
+impl Mailer {
+    pub fn new(transport: Box<dyn EmailTransport>, from: String) -> Self {
+        Mailer { transport, from }
+    }
+
+    /// `MAIL_TRANSPORT=smtp` sends through `SMTP_HOST`:`SMTP_PORT` (a local mail
+    /// catcher by default); anything else writes `.eml` files to `MAIL_OUTBOX_DIR`.
+    pub fn from_env() -> Self {
+        let transport: Box<dyn EmailTransport> = match env::var("MAIL_TRANSPORT").as_deref() {
+            Ok("smtp") => Box::new(SmtpTransport::from_env()),
+            _ => Box::new(FileTransport::from_env()),
+        };
+        let from = env::var("MAIL_FROM").unwrap_or_else(|_| DEFAULT_FROM.to_string());
+
+        Mailer::new(transport, from)
+    }
+
+    /// The mailer `from_env` configures, built on first use.
+    pub fn shared() -> &'static Mailer {
+        static MAILER: OnceLock<Mailer> = OnceLock::new();
+
+        MAILER.get_or_init(Mailer::from_env)
+    }
+
+    async fn send(&self, email: &OutboxEmail) -> Result<(), Error> {
+        let from = address_of(&self.from);
+        check_address(from)?;
+        check_address(&email.recipient)?;
+
+        let envelope = Envelope {
+            id: email.id,
+            from,
+            to: &email.recipient,
+        };
+        let message = build_message(&self.from, email);
+
+        self.transport.send(&envelope, &message).await
+    }
+}
+
+/// Sends one outbox email. The job runner retries failed attempts, so the
+/// outbox only records how the latest one went.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendEmail {
+    pub email_id: i64,
+}
+
+#[async_trait]
+impl Job for SendEmail {
+    const KIND: &'static str = "send_email";
+    /// About four hours of retries with the runner's backoff.
+    const MAX_ATTEMPTS: i32 = 12;
+
+    async fn run(&self, db: &PgPool) -> Result<(), jobs::Error> {
+        // Emails of deleted users are removed with them
+        let Some(email) = EmailOutboxManager::get_unsent(db, self.email_id).await? else {
+            return Ok(());
+        };
+
+        match Mailer::shared().send(&email).await {
+            Ok(()) => EmailOutboxManager::mark_sent(db, email.id).await?,
+            Err(ex) => {
+                EmailOutboxManager::record_failure(db, email.id, &ex.to_string()).await?;
+                return Err(ex.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn buried(&self, db: &PgPool, error: &str) -> Result<(), jobs::Error> {
+        EmailOutboxManager::mark_failed(db, self.email_id, error).await?;
+
+        Ok(())
+    }
+}
+
+/// Renders a queued email as a `multipart/alternative` message with a plain text
+/// and an HTML part.
+fn build_message(from: &str, email: &OutboxEmail) -> String {
+    let boundary = format!("=_camp_reviews_{}", email.id);
+    let domain = address_of(from).rsplit('@').next().unwrap_or("localhost");
+
+    let mut message = String::new();
+    for (name, value) in [
+        ("From", from.to_string()),
+        ("To", format!("<{}>", email.recipient)),
+        ("Subject", encode_header(&email.subject)),
+        ("Date", Utc::now().to_rfc2822()),
+        ("Message-ID", format!("<outbox-{}@{}>", email.id, domain)),
+        ("MIME-Version", "1.0".to_string()),
+        (
+            "Content-Type",
+            format!("multipart/alternative; boundary=\"{}\"", boundary),
+        ),
+    ] {
+        message.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    message.push_str("\r\n");
+
+    for (content_type, body) in [
+        ("text/plain", &email.text_body),
+        ("text/html", &email.html_body),
+    ] {
+        message.push_str(&format!(
+            "--{}\r\nContent-Type: {}; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n",
+            boundary, content_type
+        ));
+        let encoded = STANDARD.encode(body);
+        for line in encoded.as_bytes().chunks(76) {
+            message.push_str(std::str::from_utf8(line).unwrap_or_default());
+            message.push_str("\r\n");
+        }
+    }
+    message.push_str(&format!("--{}--\r\n", boundary));
+
+    message
+}
+
+/// Header values are kept on one line; anything beyond printable ASCII is sent
+/// as an RFC 2047 encoded word.
+fn encode_header(value: &str) -> String {
+    let value = value.replace(['\r', '\n'], " ");
+    if value
+        .chars()
+        .all(|ch| ch.is_ascii() && !ch.is_ascii_control())
+    {
+        value
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+/// The bare address of `Name <address>`, or the value itself.
+fn address_of(mailbox: &str) -> &str {
+    match (mailbox.rfind('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+/// Refuses addresses that could break out of an SMTP command or header.
+fn check_address(address: &str) -> Result<(), Error> {
+    let well_formed = address.contains('@')
+        && !address
+            .chars()
+            .any(|ch| ch.is_whitespace() || ch.is_control() || matches!(ch, '<' | '>'));
+    if well_formed {
+        Ok(())
+    } else {
+        Err(Error::InvalidAddress(address.to_string()))
+    }
+}
+
+#[derive(ThisError, Debug)]
+pub enum Error {
+    #[error("Failed to reach the mail transport: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("SMTP server refused: {0}")]
+    Smtp(String),
+
+    #[error("Mail transport timed out")]
+    Timeout,
+
+    #[error("Invalid email address {0:?}")]
+    InvalidAddress(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbox_email(subject: &str) -> OutboxEmail {
+        OutboxEmail {
+            id: 42,
+            user_id: Some("amy".to_string()),
+            recipient: "amy@example.com".to_string(),
+            template: "new_review_on_favorite".to_string(),
+            subject: subject.to_string(),
+            text_body: "A new review of Pine Lake".to_string(),
+            html_body: "<p>A new review of <b>Pine Lake</b></p>".to_string(),
+            status: "pending".to_string(),
+            attempts: 1,
+            last_error: None,
+            ctime: Utc::now(),
+            sent_at: None,
+        }
+    }
+
+    fn header<'a>(message: &'a str, name: &str) -> Option<&'a str> {
+        let (headers, _) = message.split_once("\r\n\r\n")?;
+        headers
+            .split("\r\n")
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+    }
+
+    #[test]
+    fn builds_a_multipart_message() {
+        let message = build_message(
+            "Camp Reviews <no-reply@camps.example>",
+            &outbox_email("New review"),
+        );
+
+        assert_eq!(
+            header(&message, "From"),
+            Some("Camp Reviews <no-reply@camps.example>")
+        );
+        assert_eq!(header(&message, "To"), Some("<amy@example.com>"));
+        assert_eq!(header(&message, "Subject"), Some("New review"));
+        assert_eq!(
+            header(&message, "Message-ID"),
+            Some("<outbox-42@camps.example>")
+        );
+        assert_eq!(
+            header(&message, "Content-Type"),
+            Some("multipart/alternative; boundary=\"=_camp_reviews_42\"")
+        );
+        assert!(header(&message, "Date").is_some());
+        assert!(message.ends_with("--=_camp_reviews_42--\r\n"));
+        assert!(message.split('\n').all(|line| line.len() <= 78));
+        assert!(!message.replace("\r\n", "").contains(['\r', '\n']));
+
+        let parts: Vec<&str> = message.split("--=_camp_reviews_42\r\n").skip(1).collect();
+        assert_eq!(parts.len(), 2);
+        for (part, content_type, body) in [
+            (parts[0], "text/plain", "A new review of Pine Lake"),
+            (
+                parts[1],
+                "text/html",
+                "<p>A new review of <b>Pine Lake</b></p>",
+            ),
+        ] {
+            let (headers, encoded) = part.split_once("\r\n\r\n").expect("part body");
+            assert!(headers.contains(&format!("Content-Type: {}; charset=utf-8", content_type)));
+            let encoded: String = encoded
+                .trim_end_matches("--=_camp_reviews_42--\r\n")
+                .split("\r\n")
+                .collect();
+            let decoded = STANDARD.decode(encoded).expect("base64 body");
+            assert_eq!(String::from_utf8(decoded).as_deref(), Ok(body));
+        }
+    }
+
+    #[test]
+    fn headers_stay_on_one_line() {
+        let message = build_message(
+            "no-reply@camps.example",
+            &outbox_email("Hi\r\nBcc: everyone@example.com"),
+        );
+
+        assert_eq!(
+            header(&message, "Subject"),
+            Some("Hi  Bcc: everyone@example.com")
+        );
+        assert_eq!(header(&message, "Bcc"), None);
+    }
+
+    #[test]
+    fn encodes_non_ascii_headers() {
+        assert_eq!(encode_header("New review"), "New review");
+        assert_eq!(encode_header("Café Lac"), "=?UTF-8?B?Q2Fmw6kgTGFj?=");
+        assert_eq!(encode_header("Tab\there"), "=?UTF-8?B?VGFiCWhlcmU=?=");
+        assert_eq!(encode_header("two\nlines"), "two lines");
+    }
+
+    #[test]
+    fn reads_bare_addresses() {
+        assert_eq!(
+            address_of("Camp Reviews <no-reply@camps.example>"),
+            "no-reply@camps.example"
+        );
+        assert_eq!(
+            address_of(" no-reply@camps.example "),
+            "no-reply@camps.example"
+        );
+        assert_eq!(address_of("broken> <"), "broken> <");
+    }
+
+    #[test]
+    fn refuses_addresses_that_break_smtp_commands() {
+        assert!(check_address("amy@example.com").is_ok());
+        assert!(check_address("amy.example.com").is_err());
+        assert!(check_address("amy@example.com>\r\nRCPT TO:<eve@example.com").is_err());
+        assert!(check_address("amy @example.com").is_err());
+        assert!(check_address("<amy@example.com>").is_err());
+        assert!(check_address("").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use std::{env, time::Duration};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use super::{EmailTransport, Envelope, Error};
+
+const DEFAULT_HOST: &str = "localhost";
+/// The port local mail catchers such as MailHog and Mailpit listen on.
+const DEFAULT_PORT: u16 = 1025;
+/// Limit for a whole conversation with the server.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Plain SMTP with optional `AUTH PLAIN`. There is no STARTTLS, so it is meant
+/// for a mail catcher or a relay on the local network.
+pub struct SmtpTransport {
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+}
+
+impl SmtpTransport {
+    pub fn new(host: String, port: u16, credentials: Option<(String, String)>) -> Self {
+        SmtpTransport {
+            host,
+            port,
+            credentials,
+        }
+    }
+
+    /// Configured by `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME` and `SMTP_PASSWORD`.
+    pub fn from_env() -> Self {
+        let host = env::var("SMTP_HOST").unwrap_or_else(|_| DEFAULT_HOST.to_string());
+        let port = env::var("SMTP_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok())
+            .unwrap_or(DEFAULT_PORT);
+        let credentials = env::var("SMTP_USERNAME")
+            .ok()
+            .map(|username| (username, env::var("SMTP_PASSWORD").unwrap_or_default()));
+
+        SmtpTransport::new(host, port, credentials)
+    }
+
+    async fn converse(&self, envelope: &Envelope<'_>, message: &str) -> Result<(), Error> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        expect_reply(&mut reader, '2').await?;
+        command(&mut writer, &mut reader, "EHLO localhost", '2').await?;
+        if let Some((username, password)) = &self.credentials {
+            let token = STANDARD.encode(format!("\0{}\0{}", username, password));
+            command(
+                &mut writer,
+                &mut reader,
+                &format!("AUTH PLAIN {}", token),
+                '2',
+            )
+            .await?;
+        }
+        command(
+            &mut writer,
+            &mut reader,
+            &format!("MAIL FROM:<{}>", envelope.from),
+            '2',
+        )
+        .await?;
+        command(
+            &mut writer,
+            &mut reader,
+            &format!("RCPT TO:<{}>", envelope.to),
+            '2',
+        )
+        .await?;
+        command(&mut writer, &mut reader, "DATA", '3').await?;
+
+        writer.write_all(data_section(message).as_bytes()).await?;
+        expect_reply(&mut reader, '2').await?;
+
+        // The message is accepted at this point, so a failed goodbye doesn't matter
+        let _ = command(&mut writer, &mut reader, "QUIT", '2').await;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, envelope: &Envelope<'_>, message: &str) -> Result<(), Error> {
+        tokio::time::timeout(SEND_TIMEOUT, self.converse(envelope, message))
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+}
+
+/// The message as sent after `DATA`, ending with the lone dot that closes it.
+/// Lines starting with a dot are escaped so they can't end the data early.
+fn data_section(message: &str) -> String {
+    let mut data = String::with_capacity(message.len() + 8);
+    for line in message.split("\r\n") {
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.truncate(data.trim_end_matches("\r\n").len());
+    data.push_str("\r\n.\r\n");
+
+    data
+}
+
+async fn command(
+    writer: &mut (impl AsyncWriteExt + Unpin),
+    reader: &mut (impl AsyncBufReadExt + Unpin),
+    line: &str,
+    expected: char,
+) -> Result<(), Error> {
+    writer.write_all(format!("{}\r\n", line).as_bytes()).await?;
+
+    expect_reply(reader, expected).await
+}
+
+/// Reads a possibly multi-line reply and checks the first digit of its code.
+async fn expect_reply(
+    reader: &mut (impl AsyncBufReadExt + Unpin),
+    expected: char,
+) -> Result<(), Error> {
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(Error::Smtp("connection closed".to_string()));
+        }
+        reply.push_str(&line);
+        // `250-...` continues the reply, `250 ...` ends it
+        if line.as_bytes().get(3) != Some(&b'-') {
+            break;
+        }
+    }
+
+    if reply.starts_with(expected) {
+        Ok(())
+    } else {
+        Err(Error::Smtp(reply.trim_end().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_lines_starting_with_a_dot() {
+        assert_eq!(
+            data_section("Subject: hi\r\n\r\n.\r\n..two\r\nend. here"),
+            "Subject: hi\r\n\r\n..\r\n...two\r\nend. here\r\n.\r\n"
+        );
+    }
+
+    #[test]
+    fn ends_the_data_exactly_once() {
+        assert_eq!(data_section("body\r\n"), "body\r\n.\r\n");
+        assert_eq!(data_section("body\r\n\r\n\r\n"), "body\r\n.\r\n");
+        assert_eq!(data_section("body"), "body\r\n.\r\n");
+    }
+
+    #[tokio::test]
+    async fn reads_multi_line_replies() {
+        let mut reply = "250-mail.example\r\n250-SIZE 1000\r\n250 OK\r\n354 go\r\n".as_bytes();
+
+        expect_reply(&mut reply, '2').await.expect("2xx reply");
+        expect_reply(&mut reply, '3').await.expect("3xx reply");
+        assert!(matches!(
+            expect_reply(&mut reply, '2').await,
+            Err(Error::Smtp(reason)) if reason == "connection closed"
+        ));
+
+        let mut refused = "550 5.1.1 no such user\r\n".as_bytes();
+        assert!(matches!(
+            expect_reply(&mut refused, '2').await,
+            Err(Error::Smtp(reason)) if reason == "550 5.1.1 no such user"
+        ));
+    }
+}
@@ -1,10 +1,15 @@
-use models::connect_to_db;
+use jobs::JobRunner;
+use models::{connect_to_db, review_activity::ReviewActivityManager};
 use routes::start_web;
 use std::{env, sync::Arc};
 
 mod auth;
+mod importer;
+mod jobs;
+mod mailer;
 mod models;
 mod routes;
+mod webhooks;
 
 const DEFAULT_WEB_PORT: u16 = 8080;
 
@@ -23,6 +28,20 @@ async fn main() {
     // Connect to database
     let db = Arc::new(connect_to_db().await.expect("Cannot connect to db"));
 
+    // One-off commands run instead of the server
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("import-camps") {
+        if let Err(ex) = importer::bulk::run_command(&db, &args[1..]).await {
+            println!("ERROR - camp import failed. Cause: {}", ex);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    tokio::spawn(ReviewActivityManager::listen(db.clone()));
+    // Background work, including sending emails and webhooks, for as long as the server runs
+    JobRunner::from_env().start(db.clone());
+
     match start_web(web_port, db).await {
         Ok(_) => println!("Server ended safely"),
         Err(ex) => println!("ERROR - server failed to start. Cause: {:?}", ex),
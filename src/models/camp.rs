@@ -1,12 +1,29 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use chrono::{serde::ts_seconds_option, DateTime, NaiveDate, Utc};
+
 use super::{
-    camp_request::{CampRequest, CampRequestManager},
+    camp_duplicate::{CampDuplicateManager, CampFingerprint, LIKELY_DUPLICATE_SCORE},
+    camp_request::CampRequestManager,
+    camp_revision::{CampRevisionManager, RevisionSource},
+    camp_session::{CampSessionManager, CampSessionPatch},
+    events::{DomainEvent, EventBus},
+    screening::ModerationStatus,
+    tag::TagManager,
+    validation::{self, Validate, ValidationErrors, MAX_VARCHAR_LEN},
     Error, Review,
 };
 use crate::auth::UserCtx;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Transaction};
 
-#[derive(Debug, FromRow, Serialize, Deserialize, Default)]
+/// Oldest camper age a camp can advertise.
+const MAX_CAMPER_AGE: i32 = 100;
+
+#[derive(Debug, FromRow, Serialize, Deserialize, Default, Clone)]
 pub struct Camp {
     pub id: i64,
     pub name: String,
@@ -23,9 +40,82 @@ pub struct Camp {
     pub website: Option<String>,
     pub apt_suite_other: Option<String>,
     pub rating: Option<f32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub min_age: Option<i32>,
+    pub max_age: Option<i32>,
+    pub camp_type: Option<String>,
+    pub gender_policy: Option<String>,
+    pub price_min_cents: Option<i64>,
+    pub price_max_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub status: String,
+    #[serde(with = "ts_seconds_option")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CampStatus {
+    Active,
+    TemporarilyClosed,
+    PermanentlyClosed,
+    Hidden,
+}
+
+impl fmt::Display for CampStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            CampStatus::Active => "active",
+            CampStatus::TemporarilyClosed => "temporarily_closed",
+            CampStatus::PermanentlyClosed => "permanently_closed",
+            CampStatus::Hidden => "hidden",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CampType {
+    Day,
+    Overnight,
+    Virtual,
+    Travel,
+}
+
+impl fmt::Display for CampType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let camp_type = match self {
+            CampType::Day => "day",
+            CampType::Overnight => "overnight",
+            CampType::Virtual => "virtual",
+            CampType::Travel => "travel",
+        };
+        write!(f, "{}", camp_type)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GenderPolicy {
+    Coed,
+    Boys,
+    Girls,
 }
 
-#[derive(Debug, FromRow, Serialize, Deserialize)]
+impl fmt::Display for GenderPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gender_policy = match self {
+            GenderPolicy::Coed => "coed",
+            GenderPolicy::Boys => "boys",
+            GenderPolicy::Girls => "girls",
+        };
+        write!(f, "{}", gender_policy)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct CampPatch {
     pub name: Option<String>,
     pub description: Option<String>,
@@ -40,71 +130,469 @@ pub struct CampPatch {
     pub tags: Option<Vec<String>>,
     pub apt_suite_other: Option<String>,
     pub image_urls: Option<Vec<String>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub min_age: Option<i32>,
+    pub max_age: Option<i32>,
+    pub camp_type: Option<CampType>,
+    pub gender_policy: Option<GenderPolicy>,
+    pub price_min_cents: Option<i64>,
+    pub price_max_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub status: Option<CampStatus>,
+    /// When present, replaces all of the camp's sessions.
+    pub sessions: Option<Vec<CampSessionPatch>>,
+}
+
+impl Validate for CampPatch {
+    fn validate(&mut self) -> Result<(), ValidationErrors> {
+        self.validate_over(None)
+    }
+}
+
+impl CampPatch {
+    /// Validates the patch as an edit of `stored`. Fields the patch leaves out
+    /// keep their stored values, so a national phone number is read with the
+    /// stored country and a new minimum age is checked against the stored maximum.
+    pub fn validate_update(&mut self, stored: &Camp) -> Result<(), ValidationErrors> {
+        self.validate_over(Some(stored))
+    }
+
+    fn validate_over(&mut self, stored: Option<&Camp>) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+
+        for (path, value) in [
+            ("$.name", &self.name),
+            ("$.description", &self.description),
+            ("$.street_address", &self.street_address),
+            ("$.city", &self.city),
+            ("$.state", &self.state),
+            ("$.apt_suite_other", &self.apt_suite_other),
+            ("$.website", &self.website),
+        ] {
+            if let Some(value) = value {
+                errors.check_length(path, value, MAX_VARCHAR_LEN);
+            }
+        }
+
+        if let Some(country) = self.country.as_mut() {
+            *country = country.trim().to_uppercase();
+            if !country.is_empty() && !validation::is_valid_country(country) {
+                errors.add("$.country", "must be an ISO 3166-1 alpha-2 country code");
+            }
+        }
+        let country = match &self.country {
+            Some(country) => Some(country.clone()),
+            None => stored.map(|camp| camp.country.clone()),
+        }
+        .filter(|country| !country.is_empty());
+
+        if let Some(zip_code) = self.zip_code.as_mut() {
+            *zip_code = zip_code.trim().to_uppercase();
+        }
+        let zip_code = match &self.zip_code {
+            Some(zip_code) => Some(zip_code.clone()),
+            // A new country must still fit the stored postal code
+            None if self.country.is_some() => stored.map(|camp| camp.zip_code.clone()),
+            None => None,
+        };
+        if let (Some(zip_code), Some(country)) = (zip_code, country.as_deref()) {
+            if !zip_code.is_empty() && !validation::is_valid_postal_code(country, &zip_code) {
+                errors.add(
+                    "$.zip_code",
+                    format!("is not a valid postal code for {}", country),
+                );
+            }
+        }
+
+        if let Some(phone_number) = self.phone_number.as_mut() {
+            if !phone_number.trim().is_empty() {
+                match validation::normalize_phone(phone_number, country.as_deref()) {
+                    Ok(normalized) => *phone_number = normalized,
+                    Err(message) => errors.add("$.phone_number", message),
+                }
+            }
+        }
+
+        if let Some(email) = self.email.as_mut() {
+            *email = email.trim().to_string();
+            errors.check_email("$.email", email);
+        }
+        if let Some(website) = self.website.as_mut() {
+            *website = website.trim().to_string();
+            errors.check_url("$.website", website);
+        }
+        for (index, url) in self.image_urls.iter().flatten().enumerate() {
+            errors.check_url(&format!("$.image_urls[{}]", index), url);
+        }
+        for (index, tag) in self.tags.iter().flatten().enumerate() {
+            errors.check_length(&format!("$.tags[{}]", index), tag, MAX_VARCHAR_LEN);
+        }
+
+        if let Some(latitude) = self.latitude {
+            errors.check_range("$.latitude", latitude, -90.0, 90.0);
+        }
+        if let Some(longitude) = self.longitude {
+            errors.check_range("$.longitude", longitude, -180.0, 180.0);
+        }
+
+        if let Some(min_age) = self.min_age {
+            errors.check_range("$.min_age", min_age, 0, MAX_CAMPER_AGE);
+        }
+        if let Some(max_age) = self.max_age {
+            errors.check_range("$.max_age", max_age, 0, MAX_CAMPER_AGE);
+        }
+        let min_age = self.min_age.or(stored.and_then(|camp| camp.min_age));
+        let max_age = self.max_age.or(stored.and_then(|camp| camp.max_age));
+        if min_age.zip(max_age).is_some_and(|(min, max)| min > max) {
+            // Blame the field the patch changed, as the other one may be stored
+            match self.max_age {
+                Some(_) => errors.add("$.max_age", "must not be less than min_age"),
+                None => errors.add("$.min_age", "must not be greater than max_age"),
+            }
+        }
+
+        errors.check_non_negative("$.price_min_cents", self.price_min_cents);
+        errors.check_non_negative("$.price_max_cents", self.price_max_cents);
+        let price_min_cents = self
+            .price_min_cents
+            .or(stored.and_then(|camp| camp.price_min_cents));
+        let price_max_cents = self
+            .price_max_cents
+            .or(stored.and_then(|camp| camp.price_max_cents));
+        if price_min_cents
+            .zip(price_max_cents)
+            .is_some_and(|(min, max)| min > max)
+        {
+            match self.price_max_cents {
+                Some(_) => errors.add("$.price_max_cents", "must not be less than price_min_cents"),
+                None => errors.add(
+                    "$.price_min_cents",
+                    "must not be greater than price_max_cents",
+                ),
+            }
+        }
+
+        if let Some(currency) = self.currency.as_mut() {
+            *currency = currency.trim().to_uppercase();
+            if !validation::is_valid_currency(currency) {
+                errors.add(
+                    "$.currency",
+                    "must be a three letter ISO 4217 currency code",
+                );
+            }
+        }
+
+        for (index, session) in self.sessions.iter_mut().flatten().enumerate() {
+            session.validate_at(&format!("$.sessions[{}]", index), &mut errors);
+        }
+
+        errors.into_result()
+    }
+}
+
+/// A camp as shown to a reader, with fields personalized for signed-in users.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampView {
+    #[serde(flatten)]
+    pub camp: Camp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_favorite: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub my_review: Option<Review>,
+}
+
+/// Query string filters accepted by the camp listing.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct CampFilter {
+    pub camp_type: Option<CampType>,
+    pub gender_policy: Option<GenderPolicy>,
+    pub age: Option<i32>,
+    pub max_price_cents: Option<i64>,
+    pub currency: Option<String>,
+    /// Together with `available_to`, keeps camps with a session overlapping the window.
+    pub available_from: Option<NaiveDate>,
+    pub available_to: Option<NaiveDate>,
+    /// Hidden camps are only listed when explicitly asked for by an admin.
+    pub status: Option<CampStatus>,
+    pub include_deleted: Option<bool>,
+}
+
+impl CampFilter {
+    /// Drops the admin-only options for callers who are not admins.
+    pub fn restrict_to(mut self, is_admin: bool) -> Self {
+        if !is_admin {
+            self.include_deleted = None;
+            if self.status == Some(CampStatus::Hidden) {
+                self.status = None;
+            }
+        }
+        self
+    }
+
+    pub fn push_conditions(&self, query: &mut QueryBuilder<Postgres>) {
+        query.push(" WHERE TRUE");
+
+        match self.status {
+            Some(status) => {
+                query.push(" AND status = ").push_bind(status.to_string());
+            }
+            None => {
+                query.push(" AND status <> 'hidden'");
+            }
+        }
+        if !self.include_deleted.unwrap_or(false) {
+            query.push(" AND deleted_at IS NULL");
+        }
+
+        if let Some(camp_type) = self.camp_type {
+            query
+                .push(" AND camp_type = ")
+                .push_bind(camp_type.to_string());
+        }
+        if let Some(gender_policy) = self.gender_policy {
+            query
+                .push(" AND gender_policy = ")
+                .push_bind(gender_policy.to_string());
+        }
+        if let Some(age) = self.age {
+            query
+                .push(" AND COALESCE(min_age, 0) <= ")
+                .push_bind(age)
+                .push(" AND COALESCE(max_age, 1000) >= ")
+                .push_bind(age);
+        }
+        if let Some(max_price_cents) = self.max_price_cents {
+            query
+                .push(" AND price_min_cents <= ")
+                .push_bind(max_price_cents);
+        }
+        if let Some(currency) = &self.currency {
+            query
+                .push(" AND currency = ")
+                .push_bind(currency.to_uppercase());
+        }
+        if self.available_from.is_some() || self.available_to.is_some() {
+            query.push(
+                " AND EXISTS (SELECT 1 FROM camp_sessions WHERE camp_sessions.camp_id = camps.id",
+            );
+            self.push_session_window(query);
+            query.push(")");
+        }
+    }
+
+    /// Restricts a query over `camp_sessions` to sessions overlapping the availability window.
+    pub fn push_session_window(&self, query: &mut QueryBuilder<Postgres>) {
+        if let Some(available_from) = self.available_from {
+            query
+                .push(" AND camp_sessions.end_date >= ")
+                .push_bind(available_from);
+        }
+        if let Some(available_to) = self.available_to {
+            query
+                .push(" AND camp_sessions.start_date <= ")
+                .push_bind(available_to);
+        }
+    }
 }
 
 pub struct CampManager;
 
 impl CampManager {
-    pub async fn get_all_camps(db: &PgPool, _utx: UserCtx) -> Result<Vec<Camp>, Error> {
-        let query = "SELECT * FROM camps";
-        let all_camps = sqlx::query_as::<_, Camp>(query).fetch_all(db).await?;
+    pub async fn get_all_camps(
+        db: &PgPool,
+        utx: Option<UserCtx>,
+        filter: CampFilter,
+    ) -> Result<Vec<CampView>, Error> {
+        let is_admin = utx.as_ref().is_some_and(|utx| utx.is_admin);
+        let filter = filter.restrict_to(is_admin);
+        let mut query = QueryBuilder::new("SELECT * FROM camps");
+        filter.push_conditions(&mut query);
+        let all_camps = query.build_query_as::<Camp>().fetch_all(db).await?;
 
-        Ok(all_camps)
+        Self::personalize(db, utx.as_ref(), all_camps).await
     }
 
-    pub async fn add_camp(db: &PgPool, _utx: UserCtx, camp_request_id: i64) -> Result<Camp, Error> {
-        let data = CampRequestManager::get_camp_request(db, camp_request_id).await?;
+    /// Adds the reader's favorite flag and own review; anonymous readers get plain camps.
+    pub async fn personalize(
+        db: &PgPool,
+        utx: Option<&UserCtx>,
+        camps: Vec<Camp>,
+    ) -> Result<Vec<CampView>, Error> {
+        let Some(utx) = utx else {
+            return Ok(camps
+                .into_iter()
+                .map(|camp| CampView {
+                    camp,
+                    is_favorite: None,
+                    my_review: None,
+                })
+                .collect());
+        };
 
-        let camp: Camp = sqlx::query_as!(
-            Camp,
-            "insert into camps (name, description, phone_number, street_address, city, state, country, email, zip_code, website, tags, image_urls) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) returning *",
-            data.name,
-            data.description,
-            data.phone_number,
-            data.street_address,
-            data.city,
-            data.state,
-            data.country,
-            data.email,
-            data.zip_code,
-            data.website.unwrap_or_default(),
-            &data.tags.unwrap_or_default(),
-            &data.image_urls.unwrap_or_default(),
-        ).fetch_one(db).await?;
+        let camp_ids: Vec<i64> = camps.iter().map(|camp| camp.id).collect();
+        let favorite_ids: HashSet<i64> = sqlx::query_scalar!(
+            "SELECT camp_id FROM users_camps WHERE user_id = $1 AND camp_id = ANY($2)",
+            utx.user_id,
+            &camp_ids
+        )
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .collect();
+        let mut reviews_by_camp: HashMap<i64, Review> = sqlx::query_as!(
+            Review,
+            "SELECT * FROM reviews WHERE author_id = $1 AND camp_id = ANY($2)",
+            utx.user_id,
+            &camp_ids
+        )
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|review| (review.camp_id, review))
+        .collect();
+
+        Ok(camps
+            .into_iter()
+            .map(|camp| CampView {
+                is_favorite: Some(favorite_ids.contains(&camp.id)),
+                my_review: reviews_by_camp.remove(&camp.id),
+                camp,
+            })
+            .collect())
+    }
+
+    /// Approves a camp request, refusing likely duplicates unless `force` is set.
+    pub async fn add_camp(
+        db: &PgPool,
+        utx: UserCtx,
+        camp_request_id: i64,
+        force: bool,
+    ) -> Result<Camp, Error> {
+        let mut tx = db.begin().await?;
+
+        // Locked so that two admins approving at once cannot both add the camp
+        let data = CampRequestManager::lock_camp_request(&mut tx, camp_request_id).await?;
+        if data.approved_at.is_some() {
+            return Err(Error::InvalidQuery(format!(
+                "Camp request {} was already approved",
+                camp_request_id
+            )));
+        }
+        if data.moderation_status != ModerationStatus::Published.to_string() {
+            return Err(Error::InvalidQuery(format!(
+                "Camp request {} is held for moderation and must be published first",
+                camp_request_id
+            )));
+        }
+
+        if !force {
+            let likely_duplicates: Vec<_> =
+                CampDuplicateManager::find_duplicates(db, &CampFingerprint::from(&data))
+                    .await?
+                    .into_iter()
+                    .filter(|candidate| candidate.score >= LIKELY_DUPLICATE_SCORE)
+                    .collect();
+            if !likely_duplicates.is_empty() {
+                return Err(Error::LikelyDuplicate(likely_duplicates));
+            }
+        }
+
+        let requested_by = data.user_id.clone();
+        let camp = CampPatch::try_from(data)?;
+
+        let camp = Self::insert_camp(
+            &mut tx,
+            &utx,
+            camp,
+            RevisionSource::CampRequest,
+            Some(camp_request_id),
+        )
+        .await?;
+        CampRequestManager::mark_approved(&mut tx, camp_request_id, camp.id).await?;
+        EventBus::publish(
+            &mut tx,
+            DomainEvent::CampRequestApproved {
+                camp_request_id,
+                camp_id: camp.id,
+                requested_by,
+            },
+        )
+        .await?;
+
+        tx.commit().await?;
 
         Ok(camp)
     }
 
-    pub async fn get_camp(db: &PgPool, id: i64, _utx: UserCtx) -> Result<Camp, Error> {
-        let camp = sqlx::query_as!(Camp, "SELECT * FROM camps WHERE id = $1", id)
-            .fetch_one(db)
-            .await?;
+    /// Hidden and deleted camps are only visible to admins.
+    pub async fn get_visible_camp(
+        db: &PgPool,
+        id: i64,
+        utx: Option<&UserCtx>,
+    ) -> Result<Camp, Error> {
+        let camp = sqlx::query_as!(
+            Camp,
+            "SELECT * FROM camps WHERE id = $1 AND ($2 OR (deleted_at IS NULL AND status <> 'hidden'))",
+            id,
+            utx.is_some_and(|utx| utx.is_admin)
+        )
+        .fetch_one(db)
+        .await?;
 
         Ok(camp)
     }
 
-    pub async fn get_featured_camps(db: &PgPool) -> Result<Vec<Camp>, Error> {
+    pub async fn get_featured_camps(
+        db: &PgPool,
+        utx: Option<UserCtx>,
+    ) -> Result<Vec<CampView>, Error> {
         let featured_camps = sqlx::query_as!(
             Camp,
-            "SELECT * FROM camps ORDER BY rating DESC NULLS LAST LIMIT 10"
+            "SELECT * FROM camps WHERE deleted_at IS NULL AND status = 'active' ORDER BY rating DESC NULLS LAST LIMIT 10"
         )
         .fetch_all(db)
         .await?;
 
-        Ok(featured_camps)
+        Self::personalize(db, utx.as_ref(), featured_camps).await
     }
 
     pub async fn update_camp(
         db: &PgPool,
         id: i64,
-        data: CampPatch,
-        _utx: UserCtx,
+        mut data: CampPatch,
+        utx: UserCtx,
     ) -> Result<Camp, Error> {
-        let original_camp = sqlx::query_as!(Camp, "SELECT * FROM camps WHERE id = $1", id)
-            .fetch_one(db)
-            .await?;
+        // Only admins see hidden and deleted camps, so only they can edit, and unhide, them
+        Self::get_visible_camp(db, id, Some(&utx)).await?;
+        if let Some(tags) = data.tags.take() {
+            data.tags = Some(TagManager::normalize_tags(db, tags).await?);
+        }
+
+        let mut tx = db.begin().await?;
+        let camp = Self::apply_patch(&mut tx, &utx, id, data, RevisionSource::Edit).await?;
+        tx.commit().await?;
 
-        let camp = sqlx::query_as!(Camp, "UPDATE camps SET name=$1, description=$2, phone_number=$3, street_address=$4, city=$5, state=$6, country=$7, email=$8, zip_code=$9, website=$10, tags=$11, image_urls=$12 WHERE id = $13 returning *",
+        Ok(camp)
+    }
+
+    /// Validates a patch with normalized tags against the camp as stored and
+    /// writes it, recording the change as a revision from `source`.
+    pub async fn apply_patch(
+        tx: &mut Transaction<'_, Postgres>,
+        utx: &UserCtx,
+        id: i64,
+        mut data: CampPatch,
+        source: RevisionSource,
+    ) -> Result<Camp, Error> {
+        let original_camp =
+            sqlx::query_as!(Camp, "SELECT * FROM camps WHERE id = $1 FOR UPDATE", id)
+                .fetch_one(&mut **tx)
+                .await?;
+        data.validate_update(&original_camp)?;
+        let before = original_camp.clone();
+
+        let camp = sqlx::query_as!(Camp, "UPDATE camps SET name=$1, description=$2, phone_number=$3, street_address=$4, city=$5, state=$6, country=$7, email=$8, zip_code=$9, website=$10, tags=$11, image_urls=$12, latitude=$13, longitude=$14, min_age=$15, max_age=$16, camp_type=$17, gender_policy=$18, price_min_cents=$19, price_max_cents=$20, currency=$21, status=$22 WHERE id = $23 returning *",
             data.name.unwrap_or(original_camp.name),
             data.description.unwrap_or(original_camp.description),
             data.phone_number.unwrap_or(original_camp.phone_number),
@@ -117,16 +605,176 @@ impl CampManager {
             Some(data.website.unwrap_or(original_camp.website.unwrap_or_default())),
             &data.tags.unwrap_or(original_camp.tags.unwrap_or_default()),
             &data.image_urls.unwrap_or(original_camp.image_urls.unwrap_or_default()),
-            id).fetch_one(db).await?;
+            data.latitude.or(original_camp.latitude),
+            data.longitude.or(original_camp.longitude),
+            data.min_age.or(original_camp.min_age),
+            data.max_age.or(original_camp.max_age),
+            data.camp_type.map(|camp_type| camp_type.to_string()).or(original_camp.camp_type),
+            data.gender_policy.map(|gender_policy| gender_policy.to_string()).or(original_camp.gender_policy),
+            data.price_min_cents.or(original_camp.price_min_cents),
+            data.price_max_cents.or(original_camp.price_max_cents),
+            data.currency.map(|currency| currency.to_uppercase()).or(original_camp.currency),
+            data.status.map(|status| status.to_string()).unwrap_or(original_camp.status),
+            id).fetch_one(&mut **tx).await?;
+
+        if let Some(sessions) = data.sessions {
+            CampSessionManager::replace_camp_sessions(tx, id, sessions).await?;
+        }
+        CampRevisionManager::record(tx, utx, source, None, Some(&before), &camp).await?;
 
         Ok(camp)
     }
 
-    pub async fn delete_camp(db: &PgPool, id: i64, _utx: UserCtx) -> Result<Camp, Error> {
-        let camp = sqlx::query_as!(Camp, "DELETE FROM camps WHERE id = $1 returning *", id)
-            .fetch_one(db)
+    /// Creates a camp from an already validated patch with normalized tags,
+    /// recording it as a revision from `source`, e.g. the approved camp request
+    /// `source_id`.
+    pub async fn insert_camp(
+        tx: &mut Transaction<'_, Postgres>,
+        utx: &UserCtx,
+        data: CampPatch,
+        source: RevisionSource,
+        source_id: Option<i64>,
+    ) -> Result<Camp, Error> {
+        let camp = sqlx::query_as!(
+            Camp,
+            "insert into camps (name, description, phone_number, street_address, city, state, country, email, zip_code, website, tags, image_urls, apt_suite_other, latitude, longitude, min_age, max_age, camp_type, gender_policy, price_min_cents, price_max_cents, currency, status) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, COALESCE($23, 'active')) returning *",
+            data.name.unwrap_or_default(),
+            data.description.unwrap_or_default(),
+            data.phone_number.unwrap_or_default(),
+            data.street_address.unwrap_or_default(),
+            data.city.unwrap_or_default(),
+            data.state.unwrap_or_default(),
+            data.country.unwrap_or_default(),
+            data.email.unwrap_or_default(),
+            data.zip_code.unwrap_or_default(),
+            data.website.unwrap_or_default(),
+            &data.tags.unwrap_or_default(),
+            &data.image_urls.unwrap_or_default(),
+            data.apt_suite_other,
+            data.latitude,
+            data.longitude,
+            data.min_age,
+            data.max_age,
+            data.camp_type.map(|camp_type| camp_type.to_string()),
+            data.gender_policy.map(|gender_policy| gender_policy.to_string()),
+            data.price_min_cents,
+            data.price_max_cents,
+            data.currency.map(|currency| currency.to_uppercase()),
+            data.status.map(|status| status.to_string()),
+        ).fetch_one(&mut **tx).await?;
+
+        if let Some(sessions) = data.sessions {
+            CampSessionManager::replace_camp_sessions(tx, camp.id, sessions).await?;
+        }
+        CampRevisionManager::record(tx, utx, source, source_id, None, &camp).await?;
+
+        Ok(camp)
+    }
+
+    /// Restores a camp's columns to the snapshot stored in one of its revisions.
+    /// The snapshot is validated and its tags normalized as for an edit, since
+    /// the rules may have changed since it was taken. Sessions are not part of
+    /// revisions, so they are left as they are.
+    pub async fn rollback_camp(
+        db: &PgPool,
+        id: i64,
+        revision_id: i64,
+        utx: UserCtx,
+    ) -> Result<Camp, Error> {
+        let revision = CampRevisionManager::get_camp_revision(db, id, revision_id).await?;
+        let mut snapshot: CampPatch = serde_json::from_value(revision.snapshot)
+            .map_err(|ex| Error::InvalidData(format!("invalid camp revision: {}", ex)))?;
+        // The snapshot holds every column, so it is validated on its own rather than over the stored camp
+        snapshot.validate()?;
+        snapshot.tags =
+            Some(TagManager::normalize_tags(db, snapshot.tags.unwrap_or_default()).await?);
+
+        let mut tx = db.begin().await?;
+
+        let before = sqlx::query_as!(Camp, "SELECT * FROM camps WHERE id = $1 FOR UPDATE", id)
+            .fetch_one(&mut *tx)
             .await?;
 
+        let camp = sqlx::query_as!(Camp, "UPDATE camps SET name=$1, description=$2, phone_number=$3, street_address=$4, city=$5, state=$6, country=$7, email=$8, zip_code=$9, website=$10, tags=$11, image_urls=$12, latitude=$13, longitude=$14, min_age=$15, max_age=$16, camp_type=$17, gender_policy=$18, price_min_cents=$19, price_max_cents=$20, currency=$21, status=$22, apt_suite_other=$23 WHERE id = $24 returning *",
+            snapshot.name.unwrap_or_default(),
+            snapshot.description.unwrap_or_default(),
+            snapshot.phone_number.unwrap_or_default(),
+            snapshot.street_address.unwrap_or_default(),
+            snapshot.city.unwrap_or_default(),
+            snapshot.state.unwrap_or_default(),
+            snapshot.country.unwrap_or_default(),
+            snapshot.email.unwrap_or_default(),
+            snapshot.zip_code.unwrap_or_default(),
+            snapshot.website,
+            &snapshot.tags.unwrap_or_default(),
+            &snapshot.image_urls.unwrap_or_default(),
+            snapshot.latitude,
+            snapshot.longitude,
+            snapshot.min_age,
+            snapshot.max_age,
+            snapshot.camp_type.map(|camp_type| camp_type.to_string()),
+            snapshot.gender_policy.map(|gender_policy| gender_policy.to_string()),
+            snapshot.price_min_cents,
+            snapshot.price_max_cents,
+            snapshot.currency,
+            snapshot.status.map(|status| status.to_string()).unwrap_or(before.status.clone()),
+            snapshot.apt_suite_other,
+            id).fetch_one(&mut *tx).await?;
+
+        CampRevisionManager::record(
+            &mut tx,
+            &utx,
+            RevisionSource::Rollback,
+            Some(revision_id),
+            Some(&before),
+            &camp,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(camp)
+    }
+
+    /// Soft-deletes a camp, keeping its reviews and favorites so it can be restored.
+    pub async fn delete_camp(db: &PgPool, id: i64, utx: UserCtx) -> Result<Camp, Error> {
+        Self::set_deleted(db, id, true, utx).await
+    }
+
+    pub async fn restore_camp(db: &PgPool, id: i64, utx: UserCtx) -> Result<Camp, Error> {
+        Self::set_deleted(db, id, false, utx).await
+    }
+
+    async fn set_deleted(db: &PgPool, id: i64, deleted: bool, utx: UserCtx) -> Result<Camp, Error> {
+        let mut tx = db.begin().await?;
+
+        let before = sqlx::query_as!(
+            Camp,
+            "SELECT * FROM camps WHERE id = $1 AND (deleted_at IS NULL) = $2 FOR UPDATE",
+            id,
+            deleted
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let camp = sqlx::query_as!(
+            Camp,
+            "UPDATE camps SET deleted_at = CASE WHEN $2 THEN now() END WHERE id = $1 returning *",
+            id,
+            deleted
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let source = if deleted {
+            RevisionSource::Delete
+        } else {
+            RevisionSource::Restore
+        };
+        CampRevisionManager::record(&mut tx, &utx, source, None, Some(&before), &camp).await?;
+
+        tx.commit().await?;
+
         Ok(camp)
     }
 
@@ -135,9 +783,116 @@ impl CampManager {
         camp_id: i64,
         _utx: UserCtx,
     ) -> Result<Vec<Review>, Error> {
-        let reviews = sqlx::query_as!(Review, "SELECT * FROM reviews WHERE camp_id = $1", camp_id)
-            .fetch_all(db)
-            .await?;
+        let reviews = sqlx::query_as!(
+            Review,
+            "SELECT * FROM reviews WHERE camp_id = $1 AND moderation_status = 'published'",
+            camp_id
+        )
+        .fetch_all(db)
+        .await?;
         Ok(reviews)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored_camp() -> Camp {
+        Camp {
+            id: 1,
+            name: "Pine Lake".to_string(),
+            country: "US".to_string(),
+            zip_code: "55044".to_string(),
+            min_age: Some(8),
+            max_age: Some(14),
+            price_min_cents: Some(50_000),
+            price_max_cents: Some(90_000),
+            ..Camp::default()
+        }
+    }
+
+    fn error_paths(result: Result<(), ValidationErrors>) -> Vec<String> {
+        match result {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.errors.into_iter().map(|error| error.path).collect(),
+        }
+    }
+
+    #[test]
+    fn national_phone_uses_the_stored_country() {
+        let mut patch = CampPatch {
+            phone_number: Some("(555) 010-0199".to_string()),
+            ..CampPatch::default()
+        };
+        assert!(patch.validate().is_err());
+
+        let mut patch = CampPatch {
+            phone_number: Some("(555) 010-0199".to_string()),
+            ..CampPatch::default()
+        };
+        patch.validate_update(&stored_camp()).expect("valid phone");
+        assert_eq!(patch.phone_number.as_deref(), Some("+15550100199"));
+    }
+
+    #[test]
+    fn postal_codes_are_checked_against_the_merged_country() {
+        let mut patch = CampPatch {
+            zip_code: Some("SW1A 1AA".to_string()),
+            ..CampPatch::default()
+        };
+        assert_eq!(
+            error_paths(patch.validate_update(&stored_camp())),
+            ["$.zip_code"]
+        );
+
+        // Moving the camp abroad must still fit the stored postal code
+        let mut patch = CampPatch {
+            country: Some("gb".to_string()),
+            ..CampPatch::default()
+        };
+        assert_eq!(
+            error_paths(patch.validate_update(&stored_camp())),
+            ["$.zip_code"]
+        );
+
+        let mut patch = CampPatch {
+            country: Some("gb".to_string()),
+            zip_code: Some("sw1a 1aa".to_string()),
+            ..CampPatch::default()
+        };
+        patch
+            .validate_update(&stored_camp())
+            .expect("valid address");
+        assert_eq!(patch.country.as_deref(), Some("GB"));
+        assert_eq!(patch.zip_code.as_deref(), Some("SW1A 1AA"));
+    }
+
+    #[test]
+    fn ranges_are_checked_against_stored_bounds() {
+        let mut patch = CampPatch {
+            min_age: Some(16),
+            ..CampPatch::default()
+        };
+        assert_eq!(
+            error_paths(patch.validate_update(&stored_camp())),
+            ["$.min_age"]
+        );
+
+        let mut patch = CampPatch {
+            price_max_cents: Some(10_000),
+            ..CampPatch::default()
+        };
+        assert_eq!(
+            error_paths(patch.validate_update(&stored_camp())),
+            ["$.price_max_cents"]
+        );
+
+        let mut patch = CampPatch {
+            min_age: Some(16),
+            max_age: Some(17),
+            ..CampPatch::default()
+        };
+        assert!(patch.validate_update(&stored_camp()).is_ok());
+    }
+}
@@ -0,0 +1,297 @@
+use chrono::{Days, Utc};
+use sqlx::{PgPool, QueryBuilder};
+
+use super::{camp_session::CampSession, Camp, CampFilter, Error};
+
+const PRODID: &str = "-//Camp Reviews//Camp Sessions//EN";
+const MAX_LINE_OCTETS: usize = 75;
+
+pub struct CampCalendarManager;
+
+impl CampCalendarManager {
+    pub async fn camp_calendar(db: &PgPool, camp_id: i64) -> Result<String, Error> {
+        let camp = sqlx::query_as!(
+            Camp,
+            "SELECT * FROM camps WHERE id = $1 AND deleted_at IS NULL AND status <> 'hidden'",
+            camp_id
+        )
+        .fetch_one(db)
+        .await?;
+
+        let sessions = sqlx::query_as!(
+            CampSession,
+            "SELECT * FROM camp_sessions WHERE camp_id = $1 ORDER BY start_date, id",
+            camp_id
+        )
+        .fetch_all(db)
+        .await?;
+
+        let name = camp.name.clone();
+
+        Ok(render_calendar(&name, &[camp], &sessions))
+    }
+
+    /// Calendar of every session of the camps matching `filter`.
+    pub async fn catalog_calendar(db: &PgPool, filter: CampFilter) -> Result<String, Error> {
+        let filter = filter.restrict_to(false);
+        let mut query = QueryBuilder::new("SELECT * FROM camps");
+        filter.push_conditions(&mut query);
+        let camps = query.build_query_as::<Camp>().fetch_all(db).await?;
+
+        let camp_ids: Vec<i64> = camps.iter().map(|camp| camp.id).collect();
+        let mut query = QueryBuilder::new("SELECT * FROM camp_sessions WHERE camp_id = ANY(");
+        query.push_bind(camp_ids).push(")");
+        filter.push_session_window(&mut query);
+        query.push(" ORDER BY start_date, id");
+        let sessions = query.build_query_as::<CampSession>().fetch_all(db).await?;
+
+        Ok(render_calendar("Camp sessions", &camps, &sessions))
+    }
+
+    /// Calendar of every session of the visible camps `user_id` marked as favorite.
+    pub async fn favorites_calendar(db: &PgPool, user_id: &str) -> Result<String, Error> {
+        let camps = sqlx::query_as!(
+            Camp,
+            "SELECT camps.* FROM camps JOIN users_camps ON camp_id = camps.id
+            WHERE user_id = $1 AND deleted_at IS NULL AND status <> 'hidden'
+            ORDER BY camps.id",
+            user_id
+        )
+        .fetch_all(db)
+        .await?;
+
+        let camp_ids: Vec<i64> = camps.iter().map(|camp| camp.id).collect();
+        let sessions = sqlx::query_as!(
+            CampSession,
+            "SELECT * FROM camp_sessions WHERE camp_id = ANY($1) ORDER BY start_date, id",
+            &camp_ids
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(render_calendar("Favorite camp sessions", &camps, &sessions))
+    }
+}
+
+/// Renders sessions as an RFC 5545 VCALENDAR with one all-day VEVENT per session.
+fn render_calendar(name: &str, camps: &[Camp], sessions: &[CampSession]) -> String {
+    let dtstamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+
+    for session in sessions {
+        let Some(camp) = camps.iter().find(|camp| camp.id == session.camp_id) else {
+            continue;
+        };
+
+        let summary = if session.name.is_empty() {
+            camp.name.clone()
+        } else {
+            format!("{} - {}", camp.name, session.name)
+        };
+        // DTEND is exclusive for all-day events, while end_date is the last camp day
+        let end_date = session
+            .end_date
+            .checked_add_days(Days::new(1))
+            .unwrap_or(session.end_date);
+        let location = [
+            camp.street_address.as_str(),
+            camp.city.as_str(),
+            camp.state.as_str(),
+            camp.zip_code.as_str(),
+            camp.country.as_str(),
+        ]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join(", ");
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:camp-session-{}@camp-reviews", session.id));
+        lines.push(format!("DTSTAMP:{}", dtstamp));
+        lines.push(format!(
+            "DTSTART;VALUE=DATE:{}",
+            session.start_date.format("%Y%m%d")
+        ));
+        lines.push(format!("DTEND;VALUE=DATE:{}", end_date.format("%Y%m%d")));
+        lines.push(format!("SUMMARY:{}", escape_text(&summary)));
+        if !location.is_empty() {
+            lines.push(format!("LOCATION:{}", escape_text(&location)));
+        }
+        if !camp.description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape_text(&camp.description)));
+        }
+        if let Some(website) = camp
+            .website
+            .as_deref()
+            .filter(|website| !website.is_empty())
+        {
+            lines.push(format!("URL:{}", website));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line at 75 octets without splitting UTF-8 sequences, terminated by CRLF.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut octets = 0;
+
+    for ch in line.chars() {
+        if octets + ch.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(ch);
+        octets += ch.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn session(
+        id: i64,
+        camp_id: i64,
+        name: &str,
+        start: (u32, u32),
+        end: (u32, u32),
+    ) -> CampSession {
+        CampSession {
+            id,
+            camp_id,
+            name: name.to_string(),
+            start_date: NaiveDate::from_ymd_opt(2026, start.0, start.1).expect("valid date"),
+            end_date: NaiveDate::from_ymd_opt(2026, end.0, end.1).expect("valid date"),
+            capacity: None,
+            price_cents: None,
+            currency: None,
+        }
+    }
+
+    /// Undoes line folding, so assertions can look at whole content lines.
+    fn unfold(calendar: &str) -> Vec<String> {
+        calendar
+            .replace("\r\n ", "")
+            .split("\r\n")
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn escapes_separators_backslashes_and_newlines() {
+        assert_eq!(escape_text("plain text"), "plain text");
+        assert_eq!(escape_text("Canoe; hike, swim"), r"Canoe\; hike\, swim");
+        assert_eq!(escape_text(r"C:\camp"), r"C:\\camp");
+        assert_eq!(
+            escape_text("line 1\r\nline 2\nline 3"),
+            r"line 1\nline 2\nline 3"
+        );
+        // The backslash is escaped first, so later escapes are not doubled
+        assert_eq!(escape_text(r"a\,b"), r"a\\\,b");
+    }
+
+    #[test]
+    fn short_lines_are_only_terminated() {
+        assert_eq!(fold_line("BEGIN:VCALENDAR"), "BEGIN:VCALENDAR\r\n");
+        assert_eq!(fold_line(""), "\r\n");
+
+        let exactly_max = "x".repeat(MAX_LINE_OCTETS);
+        assert_eq!(fold_line(&exactly_max), format!("{}\r\n", exactly_max));
+    }
+
+    #[test]
+    fn long_lines_fold_at_75_octets_with_a_leading_space() {
+        let line = format!("DESCRIPTION:{}", "a".repeat(200));
+        let folded = fold_line(&line);
+
+        let parts: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].len(), MAX_LINE_OCTETS);
+        assert!(parts[1..].iter().all(|part| part.starts_with(' ')));
+        assert!(parts.iter().all(|part| part.len() <= MAX_LINE_OCTETS));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn folding_never_splits_a_multibyte_character() {
+        // 'é' takes two octets, so 74 ASCII octets leave no room for it on the first line
+        let line = format!("{}é{}", "a".repeat(74), "ü".repeat(60));
+        let folded = fold_line(&line);
+
+        for part in folded.trim_end_matches("\r\n").split("\r\n") {
+            assert!(part.len() <= MAX_LINE_OCTETS, "{} octets", part.len());
+        }
+        assert!(folded.starts_with(&format!("{}\r\n é", "a".repeat(74))));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn renders_one_all_day_event_per_session() {
+        let camp = Camp {
+            id: 7,
+            name: "Pine Lake".to_string(),
+            description: "Canoeing, archery".to_string(),
+            city: "Lakeville".to_string(),
+            state: "MN".to_string(),
+            website: Some("https://pinelake.example/".to_string()),
+            ..Camp::default()
+        };
+        let sessions = [
+            session(1, 7, "Week 1", (7, 6), (7, 10)),
+            session(2, 7, "", (8, 31), (8, 31)),
+            // Sessions of camps not being rendered are left out
+            session(3, 8, "Elsewhere", (7, 6), (7, 10)),
+        ];
+
+        let calendar = render_calendar("Pine Lake", &[camp], &sessions);
+        let lines = unfold(&calendar);
+
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(lines.first().map(String::as_str), Some("BEGIN:VCALENDAR"));
+        assert_eq!(
+            lines.iter().filter(|line| *line == "BEGIN:VEVENT").count(),
+            2
+        );
+        assert!(lines.contains(&"UID:camp-session-1@camp-reviews".to_string()));
+        assert!(lines.contains(&"SUMMARY:Pine Lake - Week 1".to_string()));
+        assert!(lines.contains(&"SUMMARY:Pine Lake".to_string()));
+        // DTEND is the day after the last camp day, also across a month end
+        assert!(lines.contains(&"DTSTART;VALUE=DATE:20260706".to_string()));
+        assert!(lines.contains(&"DTEND;VALUE=DATE:20260711".to_string()));
+        assert!(lines.contains(&"DTEND;VALUE=DATE:20260901".to_string()));
+        assert!(lines.contains(&r"LOCATION:Lakeville\, MN".to_string()));
+        assert!(lines.contains(&r"DESCRIPTION:Canoeing\, archery".to_string()));
+        assert!(lines.contains(&"URL:https://pinelake.example/".to_string()));
+        assert!(!calendar.contains("Elsewhere"));
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+
+use super::{Camp, Error};
+
+pub const MIN_COMPARED_CAMPS: usize = 2;
+pub const MAX_COMPARED_CAMPS: usize = 5;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ComparedCamp {
+    #[serde(flatten)]
+    pub camp: Camp,
+    pub review_count: i64,
+    pub sub_ratings: SubRatingAverages,
+    pub unique_tags: Vec<String>,
+    pub distance_km: Option<f64>,
+}
+
+/// Average of each optional aspect rating over a camp's published reviews;
+/// `None` when no review rated that aspect.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct SubRatingAverages {
+    pub staff: Option<f64>,
+    pub activities: Option<f64>,
+    pub facilities: Option<f64>,
+    pub food: Option<f64>,
+    pub safety: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampComparison {
+    pub camps: Vec<ComparedCamp>,
+    /// Every compared field, with one value per camp in request order.
+    pub fields: BTreeMap<String, Vec<Value>>,
+    pub differing_fields: Vec<String>,
+    pub common_tags: Vec<String>,
+}
+
+pub struct CampComparisonManager;
+
+impl CampComparisonManager {
+    pub async fn compare(
+        db: &PgPool,
+        ids: &[i64],
+        origin: Option<(f64, f64)>,
+    ) -> Result<CampComparison, Error> {
+        let unique_ids: BTreeSet<i64> = ids.iter().copied().collect();
+        if unique_ids.len() != ids.len() {
+            return Err(Error::InvalidQuery("camp ids must be unique".to_string()));
+        }
+        if ids.len() < MIN_COMPARED_CAMPS || ids.len() > MAX_COMPARED_CAMPS {
+            return Err(Error::InvalidQuery(format!(
+                "between {} and {} camps can be compared",
+                MIN_COMPARED_CAMPS, MAX_COMPARED_CAMPS
+            )));
+        }
+
+        let mut camps_by_id: HashMap<i64, Camp> = sqlx::query_as::<_, Camp>(
+            "SELECT * FROM camps WHERE id = ANY($1) AND deleted_at IS NULL AND status <> 'hidden'",
+        )
+        .bind(ids)
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|camp| (camp.id, camp))
+        .collect();
+
+        let mut review_stats: HashMap<i64, (i64, SubRatingAverages)> = sqlx::query!(
+            "SELECT camp_id, COUNT(*) AS \"count!\",
+                AVG(staff_rating)::float8 AS staff, AVG(activities_rating)::float8 AS activities,
+                AVG(facilities_rating)::float8 AS facilities, AVG(food_rating)::float8 AS food,
+                AVG(safety_rating)::float8 AS safety
+            FROM reviews WHERE camp_id = ANY($1) AND moderation_status = 'published' GROUP BY camp_id",
+            ids
+        )
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let averages = SubRatingAverages {
+                staff: row.staff,
+                activities: row.activities,
+                facilities: row.facilities,
+                food: row.food,
+                safety: row.safety,
+            };
+            (row.camp_id, (row.count, averages))
+        })
+        .collect();
+
+        let mut camps = Vec::with_capacity(ids.len());
+        for id in ids {
+            let camp = camps_by_id
+                .remove(id)
+                .ok_or_else(|| Error::InvalidQuery(format!("camp {} does not exist", id)))?;
+
+            let (review_count, sub_ratings) = review_stats.remove(id).unwrap_or_default();
+            camps.push(ComparedCamp {
+                review_count,
+                sub_ratings,
+                distance_km: origin
+                    .zip(camp.latitude.zip(camp.longitude))
+                    .map(|(origin, location)| distance_km(origin, location)),
+                unique_tags: Vec::new(),
+                camp,
+            });
+        }
+
+        let common_tags = fill_tag_sets(&mut camps);
+        let fields = align_fields(&camps);
+        let differing_fields = fields
+            .iter()
+            .filter(|(_, values)| values.windows(2).any(|pair| pair[0] != pair[1]))
+            .map(|(field, _)| field.clone())
+            .collect();
+
+        Ok(CampComparison {
+            camps,
+            fields,
+            differing_fields,
+            common_tags,
+        })
+    }
+}
+
+/// Stores each camp's unique tags and returns the tags shared by all camps.
+fn fill_tag_sets(camps: &mut [ComparedCamp]) -> Vec<String> {
+    let tag_sets: Vec<BTreeSet<String>> = camps
+        .iter()
+        .map(|compared| {
+            compared
+                .camp
+                .tags
+                .iter()
+                .flatten()
+                .map(|tag| tag.trim().to_lowercase())
+                .collect()
+        })
+        .collect();
+
+    let common: BTreeSet<String> = tag_sets
+        .iter()
+        .skip(1)
+        .fold(tag_sets[0].clone(), |acc, tags| {
+            acc.intersection(tags).cloned().collect()
+        });
+
+    for (index, compared) in camps.iter_mut().enumerate() {
+        compared.unique_tags = tag_sets[index]
+            .iter()
+            .filter(|tag| {
+                tag_sets
+                    .iter()
+                    .enumerate()
+                    .all(|(other, tags)| other == index || !tags.contains(*tag))
+            })
+            .cloned()
+            .collect();
+    }
+
+    common.into_iter().collect()
+}
+
+/// Sub-ratings are aligned one aspect per row, e.g. `sub_ratings.staff`.
+fn align_fields(camps: &[ComparedCamp]) -> BTreeMap<String, Vec<Value>> {
+    let mut fields: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+
+    for compared in camps {
+        if let Ok(Value::Object(map)) = serde_json::to_value(compared) {
+            for (field, value) in map {
+                match (field.as_str(), value) {
+                    ("id" | "unique_tags", _) => {}
+                    ("sub_ratings", Value::Object(aspects)) => {
+                        for (aspect, average) in aspects {
+                            let row = format!("sub_ratings.{}", aspect);
+                            fields.entry(row).or_default().push(average);
+                        }
+                    }
+                    (_, value) => fields.entry(field).or_default().push(value),
+                }
+            }
+        }
+    }
+
+    fields
+}
+
+/// Great-circle distance between two `(latitude, longitude)` points.
+pub fn distance_km((lat, lng): (f64, f64), (other_lat, other_lng): (f64, f64)) -> f64 {
+    let d_lat = (other_lat - lat).to_radians();
+    let d_lng = (other_lng - lng).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat.to_radians().cos() * other_lat.to_radians().cos() * (d_lng / 2.0).sin().powi(2);
+
+    EARTH_RADIUS_KM * 2.0 * a.sqrt().asin()
+}
@@ -0,0 +1,215 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use super::{
+    favorite_camps::UserCampJunctionManager,
+    review::{ReviewManager, ReviewWithUser},
+    Camp, CampManager, Error, Review,
+};
+use crate::auth::UserCtx;
+
+/// Optional parts of the camp detail document, requested with `?include=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CampInclude {
+    Reviews,
+    MyReview,
+    IsFavorite,
+    RatingHistogram,
+    Photos,
+}
+
+impl CampInclude {
+    const ALL: [CampInclude; 5] = [
+        CampInclude::Reviews,
+        CampInclude::MyReview,
+        CampInclude::IsFavorite,
+        CampInclude::RatingHistogram,
+        CampInclude::Photos,
+    ];
+
+    /// What `/camps/{id}` returns without `?include=`.
+    pub const DEFAULT: [CampInclude; 2] = [CampInclude::IsFavorite, CampInclude::MyReview];
+
+    /// Parses a comma separated include list, rejecting unknown names.
+    pub fn parse_list(includes: &str) -> Result<Vec<CampInclude>, Error> {
+        let mut parsed = Vec::new();
+        for name in includes
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let include = name.parse::<CampInclude>()?;
+            if !parsed.contains(&include) {
+                parsed.push(include);
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl fmt::Display for CampInclude {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let include = match self {
+            CampInclude::Reviews => "reviews",
+            CampInclude::MyReview => "my_review",
+            CampInclude::IsFavorite => "is_favorite",
+            CampInclude::RatingHistogram => "rating_histogram",
+            CampInclude::Photos => "photos",
+        };
+        write!(f, "{}", include)
+    }
+}
+
+impl FromStr for CampInclude {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        CampInclude::ALL
+            .into_iter()
+            .find(|include| include.to_string() == name)
+            .ok_or_else(|| {
+                let known: Vec<String> = CampInclude::ALL.iter().map(|i| i.to_string()).collect();
+                Error::InvalidQuery(format!(
+                    "unknown include '{}', expected one of {}",
+                    name,
+                    known.join(", ")
+                ))
+            })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampPhoto {
+    pub url: String,
+    /// The review the photo was posted with, if it isn't one of the camp's own images.
+    pub review_id: Option<i64>,
+}
+
+/// A camp together with the parts the caller asked for. Parts that were not
+/// requested, or need a signed-in caller, are left out of the JSON.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampDetail {
+    #[serde(flatten)]
+    pub camp: Camp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_favorite: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub my_review: Option<Review>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reviews: Option<Vec<ReviewWithUser>>,
+    /// Published review count per star rating, 1 to 5.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating_histogram: Option<BTreeMap<i32, i64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub photos: Option<Vec<CampPhoto>>,
+}
+
+pub struct CampDetailManager;
+
+impl CampDetailManager {
+    /// Fetches the camp and every requested part concurrently.
+    pub async fn get_camp_detail(
+        db: &PgPool,
+        camp_id: i64,
+        utx: Option<UserCtx>,
+        includes: &[CampInclude],
+    ) -> Result<CampDetail, Error> {
+        let wants = |include: CampInclude| includes.contains(&include);
+
+        let (camp, is_favorite, my_review, reviews, rating_histogram, review_photos) = tokio::try_join!(
+            CampManager::get_visible_camp(db, camp_id, utx.as_ref()),
+            async {
+                match utx.as_ref().filter(|_| wants(CampInclude::IsFavorite)) {
+                    Some(utx) => UserCampJunctionManager::is_favorite(db, utx, camp_id)
+                        .await
+                        .map(Some),
+                    None => Ok(None),
+                }
+            },
+            async {
+                match utx.as_ref().filter(|_| wants(CampInclude::MyReview)) {
+                    Some(utx) => ReviewManager::get_review(db, utx, camp_id).await,
+                    None => Ok(None),
+                }
+            },
+            async {
+                if wants(CampInclude::Reviews) {
+                    ReviewManager::get_camp_reviews(db, camp_id).await.map(Some)
+                } else {
+                    Ok(None)
+                }
+            },
+            async {
+                if wants(CampInclude::RatingHistogram) {
+                    Self::get_rating_histogram(db, camp_id).await.map(Some)
+                } else {
+                    Ok(None)
+                }
+            },
+            async {
+                if wants(CampInclude::Photos) {
+                    Self::get_review_photos(db, camp_id).await.map(Some)
+                } else {
+                    Ok(None)
+                }
+            },
+        )?;
+
+        let photos = review_photos.map(|review_photos| {
+            camp.image_urls
+                .iter()
+                .flatten()
+                .map(|url| CampPhoto {
+                    url: url.clone(),
+                    review_id: None,
+                })
+                .chain(review_photos)
+                .collect()
+        });
+
+        Ok(CampDetail {
+            camp,
+            is_favorite,
+            my_review,
+            reviews,
+            rating_histogram,
+            photos,
+        })
+    }
+
+    async fn get_rating_histogram(db: &PgPool, camp_id: i64) -> Result<BTreeMap<i32, i64>, Error> {
+        let mut histogram: BTreeMap<i32, i64> = (1..=5).map(|rating| (rating, 0)).collect();
+
+        let counts = sqlx::query!(
+            "SELECT rating, COUNT(*) AS \"count!\" FROM reviews WHERE camp_id = $1 AND moderation_status = 'published' GROUP BY rating",
+            camp_id
+        )
+        .fetch_all(db)
+        .await?;
+        for row in counts {
+            histogram.insert(row.rating, row.count);
+        }
+
+        Ok(histogram)
+    }
+
+    async fn get_review_photos(db: &PgPool, camp_id: i64) -> Result<Vec<CampPhoto>, Error> {
+        let photos = sqlx::query!(
+            "SELECT id, unnest(photo_urls) AS \"url!\" FROM reviews WHERE camp_id = $1 AND moderation_status = 'published' ORDER BY ctime DESC, id DESC",
+            camp_id
+        )
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|row| CampPhoto {
+            url: row.url,
+            review_id: Some(row.id),
+        })
+        .collect();
+
+        Ok(photos)
+    }
+}
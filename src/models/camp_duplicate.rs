@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use super::{
+    camp_comparison::distance_km,
+    camp_request::CampRequest,
+    camp_revision::{CampRevisionManager, RevisionSource},
+    review::update_calc_review_average,
+    Camp, CampPatch, Error, FORMER_MEMBER_ID,
+};
+use crate::auth::UserCtx;
+
+/// Candidates scoring below this are not reported at all.
+const MIN_DUPLICATE_SCORE: f64 = 0.35;
+/// Candidates at or above this block camp request approval unless forced.
+pub const LIKELY_DUPLICATE_SCORE: f64 = 0.6;
+const NEARBY_KM: f64 = 0.5;
+const MAX_CANDIDATES: i64 = 10;
+
+const NAME_WEIGHT: f64 = 0.45;
+const ADDRESS_WEIGHT: f64 = 0.2;
+const PHONE_WEIGHT: f64 = 0.15;
+const WEBSITE_WEIGHT: f64 = 0.1;
+const LOCATION_WEIGHT: f64 = 0.1;
+
+/// The fields of a camp or camp request that duplicates are matched on.
+#[derive(Debug, Default)]
+pub struct CampFingerprint {
+    pub name: String,
+    pub address: String,
+    pub phone: String,
+    pub website_domain: String,
+    pub coordinates: Option<(f64, f64)>,
+}
+
+impl From<&CampRequest> for CampFingerprint {
+    fn from(request: &CampRequest) -> Self {
+        CampFingerprint {
+            name: normalize_text(&request.name),
+            address: normalize_text(&format!("{} {}", request.street_address, request.city)),
+            phone: normalize_phone(&request.phone_number),
+            website_domain: website_domain(request.website.as_deref().unwrap_or_default()),
+            coordinates: request.latitude.zip(request.longitude),
+        }
+    }
+}
+
+impl From<&CampPatch> for CampFingerprint {
+    fn from(patch: &CampPatch) -> Self {
+        let field = |value: &Option<String>| value.clone().unwrap_or_default();
+
+        CampFingerprint {
+            name: normalize_text(&field(&patch.name)),
+            address: normalize_text(&format!(
+                "{} {}",
+                field(&patch.street_address),
+                field(&patch.city)
+            )),
+            phone: normalize_phone(&field(&patch.phone_number)),
+            website_domain: website_domain(&field(&patch.website)),
+            coordinates: patch.latitude.zip(patch.longitude),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateCandidate {
+    pub camp_id: i64,
+    pub name: String,
+    pub score: f64,
+    pub name_similarity: f64,
+    pub address_similarity: f64,
+    pub phone_match: bool,
+    pub website_match: bool,
+    pub distance_km: Option<f64>,
+}
+
+pub struct CampDuplicateManager;
+
+impl CampDuplicateManager {
+    /// Returns existing camps that look like the fingerprint, best match first.
+    pub async fn find_duplicates(
+        db: &PgPool,
+        fingerprint: &CampFingerprint,
+    ) -> Result<Vec<DuplicateCandidate>, Error> {
+        let rows = sqlx::query!(
+            r#"SELECT id, name, latitude, longitude,
+                similarity(lower(name), $1)::float8 AS "name_similarity!",
+                similarity(lower(street_address || ' ' || city), $2)::float8 AS "address_similarity!",
+                ($3 <> '' AND regexp_replace(phone_number, '\D', '', 'g') = $3) AS "phone_match!",
+                ($4 <> '' AND lower(substring(website from '^(?:[a-zA-Z]+://)?(?:www\.)?([^/:?#]+)')) = $4) AS "website_match!"
+            FROM camps
+            WHERE deleted_at IS NULL
+                AND (lower(name) % $1
+                    OR ($3 <> '' AND regexp_replace(phone_number, '\D', '', 'g') = $3)
+                    OR ($4 <> '' AND lower(substring(website from '^(?:[a-zA-Z]+://)?(?:www\.)?([^/:?#]+)')) = $4)
+                    OR ($5::float8 IS NOT NULL AND abs(latitude - $5) < 0.01 AND abs(longitude - $6) < 0.01))
+            ORDER BY similarity(lower(name), $1) DESC
+            LIMIT $7"#,
+            fingerprint.name,
+            fingerprint.address,
+            fingerprint.phone,
+            fingerprint.website_domain,
+            fingerprint.coordinates.map(|(lat, _)| lat),
+            fingerprint.coordinates.map(|(_, lng)| lng),
+            MAX_CANDIDATES
+        )
+        .fetch_all(db)
+        .await?;
+
+        let mut candidates: Vec<DuplicateCandidate> = rows
+            .into_iter()
+            .map(|row| {
+                let distance_km = fingerprint
+                    .coordinates
+                    .zip(row.latitude.zip(row.longitude))
+                    .map(|(origin, camp)| distance_km(origin, camp));
+                let nearby = distance_km.is_some_and(|distance| distance <= NEARBY_KM);
+
+                let score = NAME_WEIGHT * row.name_similarity
+                    + ADDRESS_WEIGHT * row.address_similarity
+                    + PHONE_WEIGHT * f64::from(u8::from(row.phone_match))
+                    + WEBSITE_WEIGHT * f64::from(u8::from(row.website_match))
+                    + LOCATION_WEIGHT * f64::from(u8::from(nearby));
+
+                DuplicateCandidate {
+                    camp_id: row.id,
+                    name: row.name,
+                    score: (score * 100.0).round() / 100.0,
+                    name_similarity: row.name_similarity,
+                    address_similarity: row.address_similarity,
+                    phone_match: row.phone_match,
+                    website_match: row.website_match,
+                    distance_km,
+                }
+            })
+            .filter(|candidate| candidate.score >= MIN_DUPLICATE_SCORE)
+            .collect();
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        Ok(candidates)
+    }
+
+    /// Folds `source_id` into `target_id`: reviews (one per author, newest wins,
+    /// except for former members), favorites, sessions and images move over, and
+    /// the source is soft-deleted behind a redirect.
+    pub async fn merge(
+        db: &PgPool,
+        source_id: i64,
+        target_id: i64,
+        utx: UserCtx,
+    ) -> Result<Camp, Error> {
+        if source_id == target_id {
+            return Err(Error::InvalidData(
+                "a camp cannot be merged into itself".to_string(),
+            ));
+        }
+
+        let mut tx = db.begin().await?;
+
+        let source = sqlx::query_as!(
+            Camp,
+            "SELECT * FROM camps WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+            source_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let target_before = sqlx::query_as!(
+            Camp,
+            "SELECT * FROM camps WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+            target_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // Reviews of deleted accounts all share the former member's id but come
+        // from different people, so they are all kept
+        sqlx::query!(
+            "DELETE FROM reviews WHERE id IN (
+                SELECT older.id FROM reviews older JOIN reviews newer
+                    ON older.author_id = newer.author_id AND older.id <> newer.id
+                WHERE older.camp_id IN ($1, $2) AND newer.camp_id IN ($1, $2)
+                    AND older.author_id <> $3
+                    AND (older.ctime, older.id) < (newer.ctime, newer.id)
+            )",
+            source_id,
+            target_id,
+            FORMER_MEMBER_ID
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE reviews SET camp_id = $2 WHERE camp_id = $1",
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO users_camps (camp_id, user_id) SELECT $2, user_id FROM users_camps WHERE camp_id = $1 ON CONFLICT DO NOTHING",
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!("DELETE FROM users_camps WHERE camp_id = $1", source_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!(
+            "UPDATE camp_sessions SET camp_id = $2 WHERE camp_id = $1",
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let target = sqlx::query_as!(
+            Camp,
+            "UPDATE camps SET image_urls = ARRAY(
+                SELECT url FROM unnest(image_urls || $2::text[]) WITH ORDINALITY AS images(url, position)
+                GROUP BY url ORDER BY min(position)
+            ) WHERE id = $1 returning *",
+            target_id,
+            &source.image_urls.clone().unwrap_or_default()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let merged_source = sqlx::query_as!(
+            Camp,
+            "UPDATE camps SET deleted_at = now() WHERE id = $1 returning *",
+            source_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE camp_redirects SET to_camp_id = $2 WHERE to_camp_id = $1",
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO camp_redirects (from_camp_id, to_camp_id) VALUES ($1, $2)
+            ON CONFLICT (from_camp_id) DO UPDATE SET to_camp_id = EXCLUDED.to_camp_id",
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        CampRevisionManager::record(
+            &mut tx,
+            &utx,
+            RevisionSource::Merge,
+            Some(target_id),
+            Some(&source),
+            &merged_source,
+        )
+        .await?;
+        CampRevisionManager::record(
+            &mut tx,
+            &utx,
+            RevisionSource::Merge,
+            Some(source_id),
+            Some(&target_before),
+            &target,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        update_calc_review_average(target_id, db).await?;
+
+        let target = sqlx::query_as!(Camp, "SELECT * FROM camps WHERE id = $1", target_id)
+            .fetch_one(db)
+            .await?;
+
+        Ok(target)
+    }
+
+    /// Where a merged camp now lives, if it was merged away.
+    pub async fn get_redirect(db: &PgPool, camp_id: i64) -> Result<Option<i64>, Error> {
+        let to_camp_id = sqlx::query_scalar!(
+            "SELECT to_camp_id FROM camp_redirects WHERE from_camp_id = $1",
+            camp_id
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(to_camp_id)
+    }
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn normalize_phone(phone: &str) -> String {
+    phone.chars().filter(char::is_ascii_digit).collect()
+}
+
+fn website_domain(website: &str) -> String {
+    let website = website.trim().to_lowercase();
+    let without_scheme = website
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(&website);
+    let host = without_scheme
+        .split(['/', ':', '?', '#'])
+        .next()
+        .unwrap_or_default();
+
+    host.strip_prefix("www.").unwrap_or(host).to_string()
+}
@@ -1,9 +1,17 @@
 #![allow(unused)]
 use crate::auth::UserCtx;
 
-use super::{CampPatch, Error};
+use super::{
+    camp_duplicate::{CampDuplicateManager, CampFingerprint, DuplicateCandidate},
+    screening::{ContentKind, ModerationStatus, ScreeningManager, Submission},
+    tag::TagManager,
+    validation::Validate,
+    CampPatch, Error,
+};
+use chrono::{serde::ts_seconds_option, DateTime, Utc};
+use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct CampRequest {
@@ -22,6 +30,77 @@ pub struct CampRequest {
     pub website: Option<String>,
     pub apt_suite_other: Option<String>,
     pub user_id: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub min_age: Option<i32>,
+    pub max_age: Option<i32>,
+    pub camp_type: Option<String>,
+    pub gender_policy: Option<String>,
+    pub price_min_cents: Option<i64>,
+    pub price_max_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub sessions: serde_json::Value,
+    pub moderation_status: String,
+    #[serde(with = "ts_seconds_option")]
+    pub approved_at: Option<DateTime<Utc>>,
+    /// The camp the request was approved as.
+    pub camp_id: Option<i64>,
+    /// Hash of the normalized text, used by screening to find copy-paste spam.
+    #[serde(skip)]
+    pub text_hash: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampRequestSubmission {
+    #[serde(flatten)]
+    pub camp_request: CampRequest,
+    pub possible_duplicates: Vec<DuplicateCandidate>,
+}
+
+/// The camp a request asks for, ready to be inserted once approved.
+impl TryFrom<CampRequest> for CampPatch {
+    type Error = Error;
+
+    fn try_from(request: CampRequest) -> Result<Self, Error> {
+        let invalid = |ex: serde_json::Error| {
+            Error::InvalidData(format!("invalid camp request {}: {}", request.id, ex))
+        };
+
+        Ok(CampPatch {
+            camp_type: from_column(request.camp_type).map_err(invalid)?,
+            gender_policy: from_column(request.gender_policy).map_err(invalid)?,
+            sessions: Some(serde_json::from_value(request.sessions).map_err(invalid)?),
+            name: Some(request.name),
+            description: Some(request.description),
+            phone_number: Some(request.phone_number),
+            street_address: Some(request.street_address),
+            city: Some(request.city),
+            state: Some(request.state),
+            country: Some(request.country),
+            zip_code: Some(request.zip_code),
+            email: Some(request.email),
+            website: request.website,
+            tags: request.tags,
+            apt_suite_other: request.apt_suite_other,
+            image_urls: request.image_urls,
+            latitude: request.latitude,
+            longitude: request.longitude,
+            min_age: request.min_age,
+            max_age: request.max_age,
+            price_min_cents: request.price_min_cents,
+            price_max_cents: request.price_max_cents,
+            currency: request.currency,
+            // New camps open as active
+            status: None,
+        })
+    }
+}
+
+/// Reads an enum stored as its snake_case name.
+fn from_column<T: DeserializeOwned>(value: Option<String>) -> Result<Option<T>, serde_json::Error> {
+    value
+        .map(|value| serde_json::from_value(serde_json::Value::String(value)))
+        .transpose()
 }
 
 pub struct CampRequestManager;
@@ -30,11 +109,29 @@ impl CampRequestManager {
     pub async fn new_request(
         db: &PgPool,
         utx: &UserCtx,
-        data: CampPatch,
-    ) -> Result<CampRequest, Error> {
+        mut data: CampPatch,
+    ) -> Result<CampRequestSubmission, Error> {
+        data.validate()?;
+
+        let submission = Submission {
+            kind: ContentKind::CampRequest,
+            user_id: &utx.user_id,
+            camp_id: None,
+            text: [&data.name, &data.description, &data.website]
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect::<Vec<&str>>()
+                .join(" "),
+        };
+        let screening = ScreeningManager::screen(db, &submission).await?;
+        let sessions = serde_json::to_value(data.sessions.unwrap_or_default())
+            .map_err(|ex| Error::InvalidData(format!("invalid sessions: {}", ex)))?;
+        let tags = TagManager::normalize_tags(db, data.tags.unwrap_or_default()).await?;
+
         let camp_request = sqlx::query_as!(
             CampRequest,
-            "insert into camp_requests (name, description, phone_number, street_address, city, state, country, email, zip_code, website, tags, image_urls, user_id) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) returning *",
+            "insert into camp_requests (name, description, phone_number, street_address, city, state, country, email, zip_code, website, tags, image_urls, user_id, latitude, longitude, min_age, max_age, camp_type, gender_policy, price_min_cents, price_max_cents, currency, sessions, moderation_status, apt_suite_other) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25) returning *",
             data.name.unwrap_or_default(),
             data.description.unwrap_or("".to_string()),
             data.phone_number.unwrap_or_default(),
@@ -45,12 +142,42 @@ impl CampRequestManager {
             data.email.unwrap_or_default(),
             data.zip_code.unwrap_or_default(),
             data.website.unwrap_or_default(),
-            &data.tags.unwrap_or_default(),
+            &tags,
             &data.image_urls.unwrap_or_default(),
             utx.user_id,
+            data.latitude,
+            data.longitude,
+            data.min_age,
+            data.max_age,
+            data.camp_type.map(|camp_type| camp_type.to_string()),
+            data.gender_policy.map(|gender_policy| gender_policy.to_string()),
+            data.price_min_cents,
+            data.price_max_cents,
+            data.currency.map(|currency| currency.to_uppercase()),
+            sessions,
+            screening.status.to_string(),
+            data.apt_suite_other,
         ).fetch_one(db).await?;
 
-        Ok(camp_request)
+        ScreeningManager::record(db, &submission, camp_request.id, &screening).await?;
+
+        let possible_duplicates =
+            CampDuplicateManager::find_duplicates(db, &CampFingerprint::from(&camp_request))
+                .await?;
+
+        Ok(CampRequestSubmission {
+            camp_request,
+            possible_duplicates,
+        })
+    }
+
+    pub async fn get_possible_duplicates(
+        db: &PgPool,
+        camp_request_id: i64,
+    ) -> Result<Vec<DuplicateCandidate>, Error> {
+        let camp_request = Self::get_camp_request(db, camp_request_id).await?;
+
+        CampDuplicateManager::find_duplicates(db, &CampFingerprint::from(&camp_request)).await
     }
 
     pub async fn delete_camp_request(
@@ -77,11 +204,82 @@ impl CampRequestManager {
         Ok(camp_request)
     }
 
+    /// Locks a request while it is being approved.
+    pub async fn lock_camp_request(
+        tx: &mut Transaction<'_, Postgres>,
+        camp_request_id: i64,
+    ) -> Result<CampRequest, Error> {
+        let camp_request = sqlx::query_as!(
+            CampRequest,
+            "SELECT * FROM camp_requests WHERE id = $1 FOR UPDATE",
+            camp_request_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(camp_request)
+    }
+
+    /// Marks a request as approved as `camp_id`, taking it out of the queue.
+    pub async fn mark_approved(
+        tx: &mut Transaction<'_, Postgres>,
+        camp_request_id: i64,
+        camp_id: i64,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE camp_requests SET approved_at = now(), camp_id = $2 WHERE id = $1",
+            camp_request_id,
+            camp_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Requests waiting to be added as camps. Held requests stay out of the
+    /// queue until a moderator publishes them.
     pub async fn get_camp_requests(db: &PgPool, _utx: UserCtx) -> Result<Vec<CampRequest>, Error> {
-        let camp_requests = sqlx::query_as!(CampRequest, "SELECT * FROM camp_requests")
-            .fetch_all(db)
-            .await?;
+        let camp_requests = sqlx::query_as!(
+            CampRequest,
+            "SELECT * FROM camp_requests WHERE moderation_status = 'published' AND approved_at IS NULL ORDER BY id"
+        )
+        .fetch_all(db)
+        .await?;
 
         Ok(camp_requests)
     }
+
+    /// Requests the screening pipeline held back, oldest first.
+    pub async fn get_held_camp_requests(
+        db: &PgPool,
+        _utx: UserCtx,
+    ) -> Result<Vec<CampRequest>, Error> {
+        let camp_requests = sqlx::query_as!(
+            CampRequest,
+            "SELECT * FROM camp_requests WHERE moderation_status = 'held' ORDER BY id"
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(camp_requests)
+    }
+
+    /// Releases a held request into the queue, so it can be added as a camp.
+    pub async fn publish_camp_request(
+        db: &PgPool,
+        _utx: UserCtx,
+        camp_request_id: i64,
+    ) -> Result<CampRequest, Error> {
+        let camp_request = sqlx::query_as!(
+            CampRequest,
+            "UPDATE camp_requests SET moderation_status = $2 WHERE id = $1 returning *",
+            camp_request_id,
+            ModerationStatus::Published.to_string()
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(camp_request)
+    }
 }
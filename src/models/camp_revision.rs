@@ -0,0 +1,140 @@
+use std::fmt;
+
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+
+use super::{Camp, Error};
+use crate::auth::UserCtx;
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct CampRevision {
+    pub id: i64,
+    pub camp_id: i64,
+    pub user_id: String,
+    #[serde(with = "ts_seconds")]
+    pub ctime: DateTime<Utc>,
+    pub source: String,
+    pub source_id: Option<i64>,
+    /// The camp's columns as they were right after this revision. Sessions
+    /// are kept in their own table and are not included.
+    pub snapshot: Value,
+    /// Changed fields as `{"field": {"from": .., "to": ..}}`.
+    pub diff: Value,
+}
+
+/// What caused a camp to change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevisionSource {
+    Edit,
+    CampRequest,
+    Rollback,
+    Delete,
+    Restore,
+    Merge,
+    Import,
+}
+
+impl fmt::Display for RevisionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self {
+            RevisionSource::Edit => "edit",
+            RevisionSource::CampRequest => "camp_request",
+            RevisionSource::Rollback => "rollback",
+            RevisionSource::Delete => "delete",
+            RevisionSource::Restore => "restore",
+            RevisionSource::Merge => "merge",
+            RevisionSource::Import => "import",
+        };
+        write!(f, "{}", source)
+    }
+}
+
+pub struct CampRevisionManager;
+
+impl CampRevisionManager {
+    /// Records `after` as a new revision of the camp, diffed against `before`.
+    pub async fn record(
+        tx: &mut Transaction<'_, Postgres>,
+        utx: &UserCtx,
+        source: RevisionSource,
+        source_id: Option<i64>,
+        before: Option<&Camp>,
+        after: &Camp,
+    ) -> Result<(), Error> {
+        let snapshot = camp_to_value(after)?;
+        let diff = match before {
+            Some(before) => diff_values(&camp_to_value(before)?, &snapshot),
+            None => json!({}),
+        };
+
+        sqlx::query!(
+            "INSERT INTO camp_revisions (camp_id, user_id, source, source_id, snapshot, diff) VALUES ($1, $2, $3, $4, $5, $6)",
+            after.id,
+            utx.user_id,
+            source.to_string(),
+            source_id,
+            snapshot,
+            diff
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_camp_revisions(db: &PgPool, camp_id: i64) -> Result<Vec<CampRevision>, Error> {
+        let revisions = sqlx::query_as!(
+            CampRevision,
+            "SELECT * FROM camp_revisions WHERE camp_id = $1 ORDER BY ctime DESC, id DESC",
+            camp_id
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(revisions)
+    }
+
+    pub async fn get_camp_revision(
+        db: &PgPool,
+        camp_id: i64,
+        revision_id: i64,
+    ) -> Result<CampRevision, Error> {
+        let revision = sqlx::query_as!(
+            CampRevision,
+            "SELECT * FROM camp_revisions WHERE camp_id = $1 AND id = $2",
+            camp_id,
+            revision_id
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(revision)
+    }
+}
+
+fn camp_to_value(camp: &Camp) -> Result<Value, Error> {
+    serde_json::to_value(camp).map_err(|ex| Error::InvalidData(format!("invalid camp: {}", ex)))
+}
+
+fn diff_values(before: &Value, after: &Value) -> Value {
+    let empty = Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+
+    let diff: Map<String, Value> = after
+        .iter()
+        // the rating is derived from reviews rather than edited
+        .filter(|(field, _)| field.as_str() != "rating")
+        .filter(|(field, value)| before.get(*field) != Some(*value))
+        .map(|(field, value)| {
+            (
+                field.clone(),
+                json!({ "from": before.get(field).cloned().unwrap_or(Value::Null), "to": value }),
+            )
+        })
+        .collect();
+
+    Value::Object(diff)
+}
@@ -0,0 +1,100 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+
+use super::{
+    validation::{self, ValidationErrors, MAX_VARCHAR_LEN},
+    Error,
+};
+
+#[derive(Debug, FromRow, Serialize, Deserialize, Clone)]
+pub struct CampSession {
+    pub id: i64,
+    pub camp_id: i64,
+    pub name: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub capacity: Option<i32>,
+    pub price_cents: Option<i64>,
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CampSessionPatch {
+    pub name: Option<String>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub capacity: Option<i32>,
+    pub price_cents: Option<i64>,
+    pub currency: Option<String>,
+}
+
+impl CampSessionPatch {
+    /// Checks the session as an element of a camp payload found at `path`.
+    pub fn validate_at(&mut self, path: &str, errors: &mut ValidationErrors) {
+        if let Some(name) = &self.name {
+            errors.check_length(&format!("{}.name", path), name, MAX_VARCHAR_LEN);
+        }
+        if self.start_date > self.end_date {
+            errors.add(
+                format!("{}.end_date", path),
+                "must not be before start_date",
+            );
+        }
+        errors.check_non_negative(&format!("{}.capacity", path), self.capacity);
+        errors.check_non_negative(&format!("{}.price_cents", path), self.price_cents);
+        if let Some(currency) = self.currency.as_mut() {
+            *currency = currency.trim().to_uppercase();
+            if !validation::is_valid_currency(currency) {
+                errors.add(
+                    format!("{}.currency", path),
+                    "must be a three letter ISO 4217 currency code",
+                );
+            }
+        }
+    }
+}
+
+pub struct CampSessionManager;
+
+impl CampSessionManager {
+    pub async fn get_camp_sessions(db: &PgPool, camp_id: i64) -> Result<Vec<CampSession>, Error> {
+        let sessions = sqlx::query_as!(
+            CampSession,
+            "SELECT * FROM camp_sessions WHERE camp_id = $1 ORDER BY start_date, id",
+            camp_id
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// Replaces every session of a camp with `sessions`.
+    pub async fn replace_camp_sessions(
+        tx: &mut Transaction<'_, Postgres>,
+        camp_id: i64,
+        sessions: Vec<CampSessionPatch>,
+    ) -> Result<(), Error> {
+        sqlx::query!("DELETE FROM camp_sessions WHERE camp_id = $1", camp_id)
+            .execute(&mut **tx)
+            .await?;
+
+        for session in sessions {
+            sqlx::query!(
+                "INSERT INTO camp_sessions (camp_id, name, start_date, end_date, capacity, price_cents, currency) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                camp_id,
+                session.name.unwrap_or_default(),
+                session.start_date,
+                session.end_date,
+                session.capacity,
+                session.price_cents,
+                session.currency.map(|currency| currency.to_uppercase()),
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,515 @@
+use std::{fmt, future::Future, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+
+use super::{screening::ModerationStatus, Camp, CampFilter, Error, Review};
+use crate::auth::UserCtx;
+
+/// Encoded chunks waiting for a slow client before the query is paused.
+const EXPORT_BUFFER: usize = 16;
+/// Rows encoded into one chunk of the response body.
+const ROWS_PER_CHUNK: usize = 100;
+/// Joins list values, e.g. tags, in a CSV cell, as the bulk import expects.
+const LIST_SEPARATOR: &str = "|";
+
+/// Column order of camp CSV exports; the same names the bulk import reads.
+const CAMP_COLUMNS: [&str; 26] = [
+    "id",
+    "name",
+    "description",
+    "phone_number",
+    "street_address",
+    "city",
+    "state",
+    "country",
+    "zip_code",
+    "email",
+    "website",
+    "tags",
+    "apt_suite_other",
+    "image_urls",
+    "latitude",
+    "longitude",
+    "min_age",
+    "max_age",
+    "camp_type",
+    "gender_policy",
+    "price_min_cents",
+    "price_max_cents",
+    "currency",
+    "status",
+    "rating",
+    "deleted_at",
+];
+
+const REVIEW_COLUMNS: [&str; 8] = [
+    "id",
+    "camp_id",
+    "author_id",
+    "ctime",
+    "rating",
+    "moderation_status",
+    "body",
+    "photo_urls",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Geojson,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Geojson => "application/geo+json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Geojson => "geojson",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Geojson => "geojson",
+        };
+        write!(f, "{}", format)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewExportFilter {
+    pub format: ExportFormat,
+    pub camp_id: Option<i64>,
+    pub moderation_status: Option<ModerationStatus>,
+}
+
+/// The body of an export, produced while the rows are read.
+pub type ExportStream = ReceiverStream<Result<String, Error>>;
+
+/// Rows that can be written out by an export.
+trait ExportRecord: Serialize {
+    const COLUMNS: &'static [&'static str];
+
+    /// `(longitude, latitude)` of the row's GeoJSON point.
+    fn coordinates(&self) -> Option<(f64, f64)> {
+        None
+    }
+}
+
+impl ExportRecord for Camp {
+    const COLUMNS: &'static [&'static str] = &CAMP_COLUMNS;
+
+    fn coordinates(&self) -> Option<(f64, f64)> {
+        self.longitude.zip(self.latitude)
+    }
+}
+
+impl ExportRecord for Review {
+    const COLUMNS: &'static [&'static str] = &REVIEW_COLUMNS;
+}
+
+pub struct CatalogExportManager;
+
+impl CatalogExportManager {
+    /// Streams the camps `/camps` would list for `utx` under `filter`, ordered
+    /// by id. GeoJSON leaves out camps without coordinates.
+    pub fn export_camps(
+        db: Arc<PgPool>,
+        utx: Option<UserCtx>,
+        filter: CampFilter,
+        format: ExportFormat,
+    ) -> ExportStream {
+        let is_admin = utx.as_ref().is_some_and(|utx| utx.is_admin);
+        let filter = filter.restrict_to(is_admin);
+
+        spawn_export(move |sender| async move {
+            let mut query = QueryBuilder::new("SELECT * FROM camps");
+            filter.push_conditions(&mut query);
+            if format == ExportFormat::Geojson {
+                query.push(" AND latitude IS NOT NULL AND longitude IS NOT NULL");
+            }
+            query.push(" ORDER BY id");
+
+            let rows = query.build_query_as::<Camp>().fetch(&*db);
+            write_rows(&sender, format, rows).await;
+        })
+    }
+
+    /// Streams every review, held ones included, ordered by id. Reviews have no
+    /// location of their own, so GeoJSON is not offered.
+    pub fn export_reviews(
+        db: Arc<PgPool>,
+        _utx: UserCtx,
+        filter: ReviewExportFilter,
+    ) -> Result<ExportStream, Error> {
+        let format = filter.format;
+        if format == ExportFormat::Geojson {
+            return Err(Error::InvalidQuery(
+                "Reviews can be exported as csv or jsonl".to_string(),
+            ));
+        }
+
+        Ok(spawn_export(move |sender| async move {
+            let mut query = QueryBuilder::new("SELECT * FROM reviews");
+            push_review_conditions(&mut query, &filter);
+            query.push(" ORDER BY id");
+
+            let rows = query.build_query_as::<Review>().fetch(&*db);
+            write_rows(&sender, format, rows).await;
+        }))
+    }
+}
+
+fn push_review_conditions(query: &mut QueryBuilder<Postgres>, filter: &ReviewExportFilter) {
+    query.push(" WHERE TRUE");
+
+    if let Some(camp_id) = filter.camp_id {
+        query.push(" AND camp_id = ").push_bind(camp_id);
+    }
+    if let Some(status) = filter.moderation_status {
+        query
+            .push(" AND moderation_status = ")
+            .push_bind(status.to_string());
+    }
+}
+
+/// Runs `export` in its own task, so the response can start before the query
+/// finishes. The bounded channel holds the query back while the client is
+/// slow, and the task ends once the client goes away.
+fn spawn_export<F, Fut>(export: F) -> ExportStream
+where
+    F: FnOnce(mpsc::Sender<Result<String, Error>>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(EXPORT_BUFFER);
+    tokio::spawn(export(sender));
+
+    ReceiverStream::new(receiver)
+}
+
+/// Encodes `rows` in chunks between the format's opening and closing. A failed
+/// read is passed on so the response is cut short rather than looking complete.
+async fn write_rows<T, S>(
+    sender: &mpsc::Sender<Result<String, Error>>,
+    format: ExportFormat,
+    rows: S,
+) where
+    T: ExportRecord,
+    S: Stream<Item = Result<T, sqlx::Error>>,
+{
+    tokio::pin!(rows);
+
+    let mut chunk = header::<T>(format);
+    let mut in_chunk = 0;
+    let mut first = true;
+
+    while let Some(row) = rows.next().await {
+        let row = match row {
+            Ok(row) => row,
+            Err(error) => {
+                println!("ERROR - {} export failed. Cause: {:?}", format, error);
+                let _ = sender.send(Err(error.into())).await;
+                return;
+            }
+        };
+
+        if encode(format, &row, first, &mut chunk) {
+            first = false;
+        }
+        in_chunk += 1;
+
+        if in_chunk == ROWS_PER_CHUNK {
+            in_chunk = 0;
+            if sender.send(Ok(std::mem::take(&mut chunk))).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    if format == ExportFormat::Geojson {
+        chunk.push_str("]}\n");
+    }
+    if !chunk.is_empty() {
+        let _ = sender.send(Ok(chunk)).await;
+    }
+}
+
+fn header<T: ExportRecord>(format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Csv => {
+            let columns: Vec<_> = T::COLUMNS.iter().map(|column| csv_field(column)).collect();
+            format!("{}\r\n", columns.join(","))
+        }
+        ExportFormat::Jsonl => String::new(),
+        ExportFormat::Geojson => r#"{"type":"FeatureCollection","features":["#.to_string(),
+    }
+}
+
+/// Appends `row` to `out`, returning whether anything was written. `first` is
+/// whether no row was written before, which GeoJSON needs to place commas.
+fn encode<T: ExportRecord>(format: ExportFormat, row: &T, first: bool, out: &mut String) -> bool {
+    let Value::Object(mut properties) = json!(row) else {
+        return false;
+    };
+
+    match format {
+        ExportFormat::Csv => {
+            let cells: Vec<_> = T::COLUMNS
+                .iter()
+                .map(|column| csv_field(&cell(properties.get(*column))))
+                .collect();
+            out.push_str(&cells.join(","));
+            out.push_str("\r\n");
+        }
+        ExportFormat::Jsonl => {
+            out.push_str(&Value::Object(properties).to_string());
+            out.push('\n');
+        }
+        ExportFormat::Geojson => {
+            let Some((longitude, latitude)) = row.coordinates() else {
+                return false;
+            };
+            properties.remove("latitude");
+            properties.remove("longitude");
+            let id = properties.get("id").cloned().unwrap_or(Value::Null);
+
+            if !first {
+                out.push(',');
+            }
+            out.push_str(&feature(id, longitude, latitude, properties).to_string());
+        }
+    }
+
+    true
+}
+
+fn feature(id: Value, longitude: f64, latitude: f64, properties: Map<String, Value>) -> Value {
+    json!({
+        "type": "Feature",
+        "id": id,
+        "geometry": {
+            "type": "Point",
+            "coordinates": [longitude, latitude],
+        },
+        "properties": properties,
+    })
+}
+
+/// A JSON value as CSV cell text: lists joined with `|`, missing values empty.
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| cell(Some(value)))
+            .collect::<Vec<_>>()
+            .join(LIST_SEPARATOR),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// Quotes a field when it holds a separator, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::importer::csv;
+
+    fn camp(id: i64, coordinates: Option<(f64, f64)>) -> Camp {
+        Camp {
+            id,
+            name: format!("Camp {}", id),
+            description: "Lakes, \"cabins\"\nand trails".to_string(),
+            country: "US".to_string(),
+            tags: Some(vec!["swimming".to_string(), "arts & crafts".to_string()]),
+            image_urls: Some(vec![]),
+            longitude: coordinates.map(|(longitude, _)| longitude),
+            latitude: coordinates.map(|(_, latitude)| latitude),
+            min_age: Some(8),
+            status: "active".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Runs `rows` through the export as a response body.
+    async fn export(format: ExportFormat, rows: Vec<Camp>) -> String {
+        let (sender, mut receiver) = mpsc::channel(EXPORT_BUFFER);
+        write_rows(
+            &sender,
+            format,
+            tokio_stream::iter(rows.into_iter().map(Ok)),
+        )
+        .await;
+        drop(sender);
+
+        let mut body = String::new();
+        while let Some(chunk) = receiver.recv().await {
+            body.push_str(&chunk.expect("no read errors"));
+        }
+        body
+    }
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        assert_eq!(csv_field("Pine Lake"), "Pine Lake");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("Pine, Lake"), "\"Pine, Lake\"");
+        assert_eq!(csv_field("5\" tent"), "\"5\"\" tent\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\ronly"), "\"cr\ronly\"");
+    }
+
+    #[test]
+    fn writes_json_values_as_cell_text() {
+        assert_eq!(cell(None), "");
+        assert_eq!(cell(Some(&Value::Null)), "");
+        assert_eq!(cell(Some(&json!("Pine"))), "Pine");
+        assert_eq!(cell(Some(&json!(8))), "8");
+        assert_eq!(cell(Some(&json!(39.5))), "39.5");
+        assert_eq!(cell(Some(&json!(true))), "true");
+        assert_eq!(
+            cell(Some(&json!(["swimming", "hiking"]))),
+            "swimming|hiking"
+        );
+        assert_eq!(cell(Some(&json!([]))), "");
+        assert_eq!(cell(Some(&json!(["a", null, 2]))), "a||2");
+    }
+
+    #[test]
+    fn encodes_camps_as_csv_the_import_reads_back() {
+        let mut out = header::<Camp>(ExportFormat::Csv);
+        assert!(encode(ExportFormat::Csv, &camp(7, None), true, &mut out));
+
+        let records = csv::parse(&out).expect("valid CSV");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].fields, CAMP_COLUMNS);
+
+        let row: HashMap<&str, &str> = CAMP_COLUMNS
+            .iter()
+            .copied()
+            .zip(records[1].fields.iter().map(String::as_str))
+            .collect();
+        assert_eq!(row["id"], "7");
+        assert_eq!(row["description"], "Lakes, \"cabins\"\nand trails");
+        assert_eq!(
+            row["tags"].split(LIST_SEPARATOR).collect::<Vec<_>>(),
+            ["swimming", "arts & crafts"]
+        );
+        assert_eq!(row["image_urls"], "");
+        assert_eq!(row["min_age"], "8");
+        assert_eq!(row["latitude"], "");
+        assert_eq!(row["deleted_at"], "");
+    }
+
+    #[test]
+    fn encodes_one_json_object_per_line() {
+        let mut out = String::new();
+        assert!(encode(ExportFormat::Jsonl, &camp(7, None), true, &mut out));
+        assert!(encode(ExportFormat::Jsonl, &camp(8, None), false, &mut out));
+
+        let lines: Vec<Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("a JSON line"))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["id"], 7);
+        assert_eq!(lines[1]["tags"], json!(["swimming", "arts & crafts"]));
+        assert!(out.ends_with('\n'));
+    }
+
+    #[test]
+    fn places_geojson_commas_between_written_features() {
+        let mut out = String::new();
+        assert!(!encode(
+            ExportFormat::Geojson,
+            &camp(6, None),
+            true,
+            &mut out
+        ));
+        assert_eq!(out, "");
+
+        assert!(encode(
+            ExportFormat::Geojson,
+            &camp(7, Some((-120.0, 39.1))),
+            true,
+            &mut out
+        ));
+        assert!(out.starts_with('{'));
+        assert!(encode(
+            ExportFormat::Geojson,
+            &camp(8, Some((-119.9, 39.2))),
+            false,
+            &mut out
+        ));
+        assert!(out.contains("},{"));
+    }
+
+    #[tokio::test]
+    async fn exports_valid_geojson() {
+        let body = export(
+            ExportFormat::Geojson,
+            vec![
+                camp(6, None),
+                camp(7, Some((-120.0, 39.1))),
+                camp(8, None),
+                camp(9, Some((-119.9, 39.2))),
+            ],
+        )
+        .await;
+
+        let collection: Value = serde_json::from_str(&body).expect("valid GeoJSON");
+        assert_eq!(collection["type"], "FeatureCollection");
+        let features = collection["features"].as_array().expect("features");
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0]["id"], 7);
+        assert_eq!(
+            features[0]["geometry"],
+            json!({"type": "Point", "coordinates": [-120.0, 39.1]})
+        );
+        assert_eq!(features[1]["properties"]["name"], "Camp 9");
+        assert!(features[1]["properties"].get("latitude").is_none());
+    }
+
+    #[tokio::test]
+    async fn exports_an_empty_geojson_collection() {
+        let body = export(ExportFormat::Geojson, vec![camp(6, None)]).await;
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).expect("valid GeoJSON"),
+            json!({"type": "FeatureCollection", "features": []})
+        );
+    }
+}
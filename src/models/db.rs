@@ -1,5 +1,5 @@
 use sqlx::PgPool;
-use std::{env, path};
+use std::env;
 
 pub async fn connect_to_db() -> Result<PgPool, Error> {
     let pool = PgPool::connect(&env::var("DATABASE_URL")?).await?;
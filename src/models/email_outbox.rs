@@ -0,0 +1,144 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+
+use super::Error;
+use crate::{jobs, mailer::SendEmail};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    Pending,
+    Sent,
+    Failed,
+}
+
+impl fmt::Display for OutboxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Sent => "sent",
+            OutboxStatus::Failed => "failed",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// An email rendered from a template, ready to be queued.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutboxEmail {
+    pub id: i64,
+    pub user_id: Option<String>,
+    pub recipient: String,
+    pub template: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub ctime: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+pub struct EmailOutboxManager;
+
+impl EmailOutboxManager {
+    /// Queues an email and the job sending it in the caller's transaction, so it
+    /// is only sent if the change it reports is committed.
+    pub async fn enqueue(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Option<&str>,
+        recipient: &str,
+        template: &str,
+        email: &RenderedEmail,
+    ) -> Result<i64, Error> {
+        let id = sqlx::query_scalar!(
+            "INSERT INTO email_outbox (user_id, recipient, template, subject, text_body, html_body) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+            user_id,
+            recipient,
+            template,
+            email.subject,
+            email.text_body,
+            email.html_body
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        jobs::enqueue(tx, &SendEmail { email_id: id }, Utc::now()).await?;
+
+        Ok(id)
+    }
+
+    /// The email, unless it was sent already.
+    pub async fn get_unsent(db: &PgPool, id: i64) -> Result<Option<OutboxEmail>, Error> {
+        let email = sqlx::query_as!(
+            OutboxEmail,
+            "SELECT * FROM email_outbox WHERE id = $1 AND status <> 'sent'",
+            id
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(email)
+    }
+
+    pub async fn mark_sent(db: &PgPool, id: i64) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE email_outbox SET status = $2, attempts = attempts + 1, sent_at = now(), last_error = NULL WHERE id = $1",
+            id,
+            OutboxStatus::Sent.to_string()
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Counts a failed attempt; the email stays pending until its job gives up.
+    pub async fn record_failure(db: &PgPool, id: i64, error: &str) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE email_outbox SET status = $2, attempts = attempts + 1, last_error = $3 WHERE id = $1",
+            id,
+            OutboxStatus::Pending.to_string(),
+            error
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn mark_failed(db: &PgPool, id: i64, error: &str) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE email_outbox SET status = $2, last_error = $3 WHERE id = $1 AND status <> 'sent'",
+            id,
+            OutboxStatus::Failed.to_string(),
+            error
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes sent and failed emails queued before `before`.
+    pub async fn prune(db: &PgPool, before: DateTime<Utc>) -> Result<u64, Error> {
+        let result = sqlx::query!(
+            "DELETE FROM email_outbox WHERE status IN ('sent', 'failed') AND ctime < $1",
+            before
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
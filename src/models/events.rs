@@ -0,0 +1,85 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use super::{
+    notification::NotificationHandler, review_activity::ReviewActivityHandler,
+    screening::ModerationStatus, webhook::WebhookHandler, Error,
+};
+
+/// Something that changed in the domain which other parts of the app react to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    CampRequestApproved {
+        camp_request_id: i64,
+        camp_id: i64,
+        requested_by: String,
+    },
+    /// Only published reviews are announced; held ones follow as `ReviewModerated`.
+    ReviewCreated {
+        review_id: i64,
+        camp_id: i64,
+        author_id: String,
+    },
+    /// The author replaced their published review with a new one.
+    ReviewUpdated {
+        review_id: i64,
+        previous_review_id: i64,
+        camp_id: i64,
+        author_id: String,
+    },
+    /// Only published reviews are announced.
+    ReviewDeleted {
+        review_id: i64,
+        camp_id: i64,
+        author_id: String,
+    },
+    ReviewModerated {
+        review_id: i64,
+        camp_id: i64,
+        author_id: String,
+        status: ModerationStatus,
+    },
+    ReviewVotedHelpful {
+        review_id: i64,
+        camp_id: i64,
+        author_id: String,
+        voter_id: String,
+    },
+    /// The average of a camp's published reviews changed; `None` once it has none.
+    CampRatingChanged { camp_id: i64, rating: Option<f32> },
+}
+
+#[async_trait]
+pub trait EventHandler: Sync {
+    async fn handle(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        event: &DomainEvent,
+    ) -> Result<(), Error>;
+}
+
+/// Everything that reacts to domain events, in the order they run.
+const HANDLERS: &[&dyn EventHandler] = &[
+    &NotificationHandler,
+    &ReviewActivityHandler,
+    &WebhookHandler,
+];
+
+pub struct EventBus;
+
+impl EventBus {
+    /// Runs every handler in the caller's transaction, so their effects are only
+    /// kept if the change that raised the event is committed.
+    pub async fn publish(
+        tx: &mut Transaction<'_, Postgres>,
+        event: DomainEvent,
+    ) -> Result<(), Error> {
+        for handler in HANDLERS {
+            handler.handle(tx, &event).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -58,6 +58,18 @@ impl UserCampJunctionManager {
         Ok(camp_user_junctions)
     }
 
+    pub async fn is_favorite(db: &PgPool, utx: &UserCtx, camp_id: i64) -> Result<bool, Error> {
+        let is_favorite = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM users_camps WHERE user_id = $1 AND camp_id = $2) AS \"is_favorite!\"",
+            utx.user_id,
+            camp_id
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(is_favorite)
+    }
+
     pub async fn favorite(db: &PgPool, utx: UserCtx, camp_id: i64) -> Result<(), Error> {
         sqlx::query!(
             "INSERT INTO users_camps (user_id, camp_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
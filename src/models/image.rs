@@ -0,0 +1,142 @@
+use std::{env, path::PathBuf, sync::OnceLock};
+
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use super::Error;
+
+/// Avatars are capped at 2 MiB unless `AVATAR_MAX_BYTES` is set.
+const DEFAULT_AVATAR_MAX_BYTES: usize = 2 * 1024 * 1024;
+/// Uploads land here, inside the statically served web folder, unless `IMAGE_UPLOAD_DIR` is set.
+const DEFAULT_UPLOAD_DIR: &str = "web-folder/uploads";
+/// Prefix of the URLs stored images are served from, overridden by `IMAGE_PUBLIC_BASE_URL`.
+const DEFAULT_PUBLIC_BASE_URL: &str = "/uploads";
+
+/// Image formats accepted for upload, recognized by their leading bytes rather
+/// than by the file name or content type the client claims.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn sniff(bytes: &[u8]) -> Option<ImageFormat> {
+        match bytes {
+            [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, ..] => Some(ImageFormat::Png),
+            [0xff, 0xd8, 0xff, ..] => Some(ImageFormat::Jpeg),
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(ImageFormat::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => {
+                Some(ImageFormat::Webp)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Webp => "webp",
+        }
+    }
+}
+
+pub fn avatar_max_bytes() -> usize {
+    static MAX_BYTES: OnceLock<usize> = OnceLock::new();
+
+    *MAX_BYTES.get_or_init(|| {
+        env::var("AVATAR_MAX_BYTES")
+            .ok()
+            .and_then(|max_bytes| max_bytes.parse().ok())
+            .unwrap_or(DEFAULT_AVATAR_MAX_BYTES)
+    })
+}
+
+pub struct ImageManager;
+
+impl ImageManager {
+    /// Checks an uploaded image and stores it, returning the URL it is served from.
+    /// Files are named after their content hash, so URLs never change meaning and
+    /// uploading the same image twice stores it once.
+    pub async fn store(bytes: &[u8], max_bytes: usize) -> Result<String, Error> {
+        if bytes.is_empty() {
+            return Err(Error::InvalidData("Image is empty".to_string()));
+        }
+        if bytes.len() > max_bytes {
+            return Err(Error::InvalidData(format!(
+                "Image is larger than {} bytes",
+                max_bytes
+            )));
+        }
+        let format = ImageFormat::sniff(bytes).ok_or_else(|| {
+            Error::InvalidData("Image must be a PNG, JPEG, GIF or WebP file".to_string())
+        })?;
+
+        let file_name = format!(
+            "{}.{}",
+            hex::encode(Sha256::digest(bytes)),
+            format.extension()
+        );
+
+        let dir = upload_dir();
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join(&file_name);
+        if !tokio::fs::try_exists(&path).await? {
+            // Written under a temporary name first so a half-written file is never served
+            let partial = dir.join(format!("{}.partial", file_name));
+            tokio::fs::write(&partial, bytes).await?;
+            tokio::fs::rename(&partial, &path).await?;
+        }
+
+        Ok(format!(
+            "{}/{}",
+            public_base_url().trim_end_matches('/'),
+            file_name
+        ))
+    }
+
+    /// Whether an avatar, review photo or camp image still uses `url`.
+    pub async fn is_referenced(db: &PgPool, url: &str) -> Result<bool, Error> {
+        let referenced = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM users WHERE avatar_url = $1)
+                OR EXISTS(SELECT 1 FROM reviews WHERE $1 = ANY(photo_urls))
+                OR EXISTS(SELECT 1 FROM camps WHERE $1 = ANY(image_urls)) AS "referenced!""#,
+            url
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(referenced)
+    }
+
+    /// Deletes a stored image by the URL `store` returned. URLs that don't point
+    /// into the upload directory, and images already gone, are ignored.
+    pub async fn remove(url: &str) -> Result<(), Error> {
+        let base_url = format!("{}/", public_base_url().trim_end_matches('/'));
+        let Some(file_name) = url.strip_prefix(&base_url) else {
+            return Ok(());
+        };
+        if file_name.is_empty() || file_name.contains(['/', '\\']) || file_name.starts_with('.') {
+            return Ok(());
+        }
+
+        match tokio::fs::remove_file(upload_dir().join(file_name)).await {
+            Err(ex) if ex.kind() != std::io::ErrorKind::NotFound => Err(ex.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn upload_dir() -> PathBuf {
+    env::var("IMAGE_UPLOAD_DIR")
+        .unwrap_or_else(|_| DEFAULT_UPLOAD_DIR.to_string())
+        .into()
+}
+
+fn public_base_url() -> String {
+    env::var("IMAGE_PUBLIC_BASE_URL").unwrap_or_else(|_| DEFAULT_PUBLIC_BASE_URL.to_string())
+}
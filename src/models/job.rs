@@ -0,0 +1,253 @@
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+
+use super::Error;
+
+/// Wait before the first retry; it doubles with every failed attempt.
+const BASE_RETRY_DELAY_SECS: i64 = 15;
+/// Longest wait between two attempts at the same job.
+const MAX_RETRY_DELAY_SECS: i64 = 60 * 60;
+const DEAD_JOBS_LIMIT: i64 = 100;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    /// Out of attempts; only an admin retry runs it again.
+    Dead,
+}
+
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Dead => "dead",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: i64,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub dedupe_key: Option<String>,
+    pub ctime: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// A job to queue, as the runner stores it.
+#[derive(Debug)]
+pub struct NewJob<'a> {
+    pub kind: &'a str,
+    pub payload: serde_json::Value,
+    pub run_at: DateTime<Utc>,
+    pub max_attempts: i32,
+    /// Skips queueing when a job with the same key exists.
+    pub dedupe_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobKindStats {
+    pub kind: String,
+    pub queued: i64,
+    /// Queued jobs that failed before and wait for their next attempt.
+    pub retrying: i64,
+    pub running: i64,
+    pub succeeded: i64,
+    pub dead: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobStats {
+    pub kinds: Vec<JobKindStats>,
+    /// How long the longest-waiting due job has been waiting, in seconds.
+    pub queue_lag_secs: Option<f64>,
+}
+
+pub struct JobManager;
+
+impl JobManager {
+    /// Queues a job in the caller's transaction, so it only runs if the change
+    /// it belongs to is committed. Returns `None` for a duplicate `dedupe_key`.
+    pub async fn enqueue(
+        tx: &mut Transaction<'_, Postgres>,
+        job: NewJob<'_>,
+    ) -> Result<Option<i64>, Error> {
+        let id = sqlx::query_scalar!(
+            "INSERT INTO jobs (kind, payload, run_at, max_attempts, dedupe_key) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (dedupe_key) DO NOTHING RETURNING id",
+            job.kind,
+            job.payload,
+            job.run_at,
+            job.max_attempts,
+            job.dedupe_key
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Takes the next due job of one of `kinds`, counting the attempt. A running
+    /// job whose lease ran out is taken over, as its worker is presumed gone.
+    /// Rows locked by another worker are skipped.
+    pub async fn claim(
+        db: &PgPool,
+        kinds: &[String],
+        lease: Duration,
+    ) -> Result<Option<JobRecord>, Error> {
+        let job = sqlx::query_as!(
+            JobRecord,
+            "UPDATE jobs SET status = 'running', attempts = attempts + 1, locked_at = now()
+            WHERE id = (
+                SELECT id FROM jobs
+                WHERE kind = ANY($1)
+                    AND ((status = 'queued' AND run_at <= now())
+                        OR (status = 'running' AND locked_at < $2 AND attempts < max_attempts))
+                ORDER BY run_at, id LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *",
+            kinds,
+            Utc::now() - lease
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(job)
+    }
+
+    pub async fn complete(db: &PgPool, id: i64) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE jobs SET status = $2, locked_at = NULL, last_error = NULL, finished_at = now() WHERE id = $1",
+            id,
+            JobStatus::Succeeded.to_string()
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Schedules another attempt with exponential backoff, or moves the job to
+    /// the dead-letter state once it is out of attempts. Returns the status it
+    /// was left in.
+    pub async fn fail(db: &PgPool, job: &JobRecord, error: &str) -> Result<JobStatus, Error> {
+        let (status, finished_at) = if job.attempts >= job.max_attempts {
+            (JobStatus::Dead, Some(Utc::now()))
+        } else {
+            (JobStatus::Queued, None)
+        };
+        let delay_secs = BASE_RETRY_DELAY_SECS
+            .saturating_mul(2_i64.saturating_pow(job.attempts.saturating_sub(1) as u32))
+            .min(MAX_RETRY_DELAY_SECS);
+
+        sqlx::query!(
+            "UPDATE jobs SET status = $2, run_at = $3, locked_at = NULL, last_error = $4, finished_at = $5 WHERE id = $1",
+            job.id,
+            status.to_string(),
+            Utc::now() + Duration::seconds(delay_secs),
+            error,
+            finished_at
+        )
+        .execute(db)
+        .await?;
+
+        Ok(status)
+    }
+
+    /// Dead-letters running jobs whose lease ran out on their last attempt, which
+    /// `claim` leaves alone, and returns them.
+    pub async fn bury_abandoned(db: &PgPool, lease: Duration) -> Result<Vec<JobRecord>, Error> {
+        let jobs = sqlx::query_as!(
+            JobRecord,
+            "UPDATE jobs SET status = $2, locked_at = NULL, last_error = 'Worker stopped during the last attempt', finished_at = now()
+            WHERE status = 'running' AND locked_at < $1 AND attempts >= max_attempts
+            RETURNING *",
+            Utc::now() - lease,
+            JobStatus::Dead.to_string()
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    /// Removes succeeded jobs that finished before `before`.
+    pub async fn prune_succeeded(db: &PgPool, before: DateTime<Utc>) -> Result<u64, Error> {
+        let result = sqlx::query!(
+            "DELETE FROM jobs WHERE status = 'succeeded' AND finished_at < $1",
+            before
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_stats(db: &PgPool) -> Result<JobStats, Error> {
+        let kinds = sqlx::query_as!(
+            JobKindStats,
+            r#"SELECT kind,
+                count(*) FILTER (WHERE status = 'queued') AS "queued!",
+                count(*) FILTER (WHERE status = 'queued' AND attempts > 0) AS "retrying!",
+                count(*) FILTER (WHERE status = 'running') AS "running!",
+                count(*) FILTER (WHERE status = 'succeeded') AS "succeeded!",
+                count(*) FILTER (WHERE status = 'dead') AS "dead!"
+            FROM jobs GROUP BY kind ORDER BY kind"#
+        )
+        .fetch_all(db)
+        .await?;
+        let queue_lag_secs = sqlx::query_scalar!(
+            "SELECT extract(epoch FROM now() - min(run_at))::float8 FROM jobs WHERE status = 'queued' AND run_at <= now()"
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(JobStats {
+            kinds,
+            queue_lag_secs,
+        })
+    }
+
+    /// The most recently buried jobs.
+    pub async fn get_dead_jobs(db: &PgPool) -> Result<Vec<JobRecord>, Error> {
+        let jobs = sqlx::query_as!(
+            JobRecord,
+            "SELECT * FROM jobs WHERE status = 'dead' ORDER BY finished_at DESC, id DESC LIMIT $1",
+            DEAD_JOBS_LIMIT
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    /// Queues a dead job again with a fresh set of attempts.
+    pub async fn retry(db: &PgPool, id: i64) -> Result<JobRecord, Error> {
+        let job = sqlx::query_as!(
+            JobRecord,
+            "UPDATE jobs SET status = 'queued', attempts = 0, run_at = now(), finished_at = NULL WHERE id = $1 AND status = 'dead' RETURNING *",
+            id
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(job)
+    }
+}
@@ -1,16 +1,38 @@
 use thiserror::Error as ThisError;
 
 mod camp;
+mod camp_calendar;
+mod camp_comparison;
+pub mod camp_detail;
+pub mod camp_duplicate;
 pub mod camp_request;
+pub mod camp_revision;
+pub mod camp_session;
+pub mod catalog_export;
 mod db;
+pub mod email_outbox;
+pub mod events;
 pub mod favorite_camps;
+pub mod image;
+pub mod job;
+pub mod notification;
 mod review;
+pub mod review_activity;
+mod rich_text;
+pub mod screening;
+pub mod tag;
 mod user;
+pub mod user_export;
+pub mod user_profile;
+pub mod validation;
+pub mod webhook;
 
-pub use camp::{Camp, CampManager, CampPatch};
+pub use camp::{Camp, CampFilter, CampManager, CampPatch, CampStatus};
+pub use camp_calendar::CampCalendarManager;
+pub use camp_comparison::CampComparisonManager;
 pub use db::connect_to_db;
 pub use review::{Review, ReviewManager, ReviewPatch};
-pub use user::{User, UserManager};
+pub use user::{User, UserManager, UserPatch, UsernameRequest, FORMER_MEMBER_ID};
 
 #[derive(ThisError, Debug)]
 pub enum Error {
@@ -25,4 +47,19 @@ pub enum Error {
 
     #[error("Failed to apply migrations")]
     MigrationFailed(#[from] sqlx::migrate::MigrateError),
+
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+
+    #[error("Unknown tags: {0:?}")]
+    UnknownTags(Vec<String>),
+
+    #[error("Likely duplicate of existing camps: {0:?}")]
+    LikelyDuplicate(Vec<camp_duplicate::DuplicateCandidate>),
+
+    #[error("Validation failed: {0:?}")]
+    Validation(#[from] validation::ValidationErrors),
 }
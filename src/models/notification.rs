@@ -0,0 +1,568 @@
+use std::{env, fmt};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, PgPool, Postgres, Transaction};
+
+use super::{
+    email_outbox::{EmailOutboxManager, RenderedEmail},
+    events::{DomainEvent, EventHandler},
+    screening::ModerationStatus,
+    user::NotificationPreferences,
+    Error,
+};
+use crate::auth::UserCtx;
+
+const APP_NAME: &str = "Camp Reviews";
+/// Where the frontend lives, for links in notifications, unless `APP_BASE_URL` is set.
+const DEFAULT_APP_BASE_URL: &str = "http://localhost:5173";
+const DEFAULT_INBOX_LIMIT: i64 = 50;
+const MAX_INBOX_LIMIT: i64 = 100;
+
+/// Something that happened which a user should hear about.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    CampRequestApproved {
+        camp_request_id: i64,
+        camp_id: i64,
+        camp_name: String,
+    },
+    ReviewHidden {
+        review_id: i64,
+        camp_id: i64,
+        camp_name: String,
+    },
+    ReviewPublished {
+        review_id: i64,
+        camp_id: i64,
+        camp_name: String,
+    },
+    /// Someone reviewed a camp the user marked as favorite.
+    NewReviewOnFavorite {
+        review_id: i64,
+        camp_id: i64,
+        camp_name: String,
+    },
+    ReviewVotedHelpful {
+        review_id: i64,
+        camp_id: i64,
+        camp_name: String,
+    },
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Notification::CampRequestApproved { .. } => "camp_request_approved",
+            Notification::ReviewHidden { .. } => "review_hidden",
+            Notification::ReviewPublished { .. } => "review_published",
+            Notification::NewReviewOnFavorite { .. } => "new_review_on_favorite",
+            Notification::ReviewVotedHelpful { .. } => "review_voted_helpful",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl Notification {
+    /// The page in the app the notification is about.
+    pub fn deep_link(&self) -> String {
+        match self {
+            Notification::CampRequestApproved { camp_id, .. }
+            | Notification::ReviewHidden { camp_id, .. }
+            | Notification::ReviewPublished { camp_id, .. }
+            | Notification::NewReviewOnFavorite { camp_id, .. }
+            | Notification::ReviewVotedHelpful { camp_id, .. } => {
+                app_url(&format!("/camps/{}", camp_id))
+            }
+        }
+    }
+
+    /// Whether the user wants to hear about this topic at all.
+    fn wanted(&self, preferences: &NotificationPreferences) -> bool {
+        let topic = match self {
+            Notification::CampRequestApproved { .. } => preferences.camp_request_updates,
+            Notification::ReviewHidden { .. }
+            | Notification::ReviewPublished { .. }
+            | Notification::ReviewVotedHelpful { .. } => preferences.review_activity,
+            Notification::NewReviewOnFavorite { .. } => preferences.favorite_camp_updates,
+        };
+
+        topic.unwrap_or(true)
+    }
+
+    /// The `NotificationPreferences` key of the topic `wanted` checks, for
+    /// queries that filter many users at once.
+    fn topic(&self) -> &'static str {
+        match self {
+            Notification::CampRequestApproved { .. } => "camp_request_updates",
+            Notification::ReviewHidden { .. }
+            | Notification::ReviewPublished { .. }
+            | Notification::ReviewVotedHelpful { .. } => "review_activity",
+            Notification::NewReviewOnFavorite { .. } => "favorite_camp_updates",
+        }
+    }
+
+    /// The email sent alongside the in-app notification, if any. Frequent,
+    /// low-stakes notifications stay in the app.
+    fn template(&self) -> Option<EmailTemplate> {
+        let template = match self {
+            Notification::CampRequestApproved { camp_name, .. } => EmailTemplate {
+                subject: format!("{} is now listed", camp_name),
+                paragraphs: vec![
+                    format!("Good news: the camp you suggested, {}, was approved and is now listed for everyone to find.", camp_name),
+                    "Thanks for helping other families discover it.".to_string(),
+                ],
+                link_label: "See the camp",
+            },
+            Notification::ReviewHidden { camp_name, .. } => EmailTemplate {
+                subject: format!("Your review of {} was hidden", camp_name),
+                paragraphs: vec![
+                    format!("A moderator hid your review of {} while it is being looked at. It is not visible to others for now.", camp_name),
+                    "If you think this was a mistake, reply to this email.".to_string(),
+                ],
+                link_label: "Go to the camp",
+            },
+            Notification::ReviewPublished { camp_name, .. } => EmailTemplate {
+                subject: format!("Your review of {} is live", camp_name),
+                paragraphs: vec![format!(
+                    "A moderator checked your review of {} and it is now visible to everyone.",
+                    camp_name
+                )],
+                link_label: "See your review",
+            },
+            Notification::NewReviewOnFavorite { .. } | Notification::ReviewVotedHelpful { .. } => {
+                return None
+            }
+        };
+
+        Some(template)
+    }
+}
+
+/// A notification as kept in the user's inbox.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InboxNotification {
+    pub id: i64,
+    pub kind: String,
+    pub payload: Json<Notification>,
+    pub link: String,
+    pub ctime: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// Newest first; `before` continues from the id of the last notification seen.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NotificationFilter {
+    #[serde(default)]
+    pub unread: bool,
+    pub limit: Option<i64>,
+    pub before: Option<i64>,
+}
+
+/// Text of one notification email; `render` wraps it in the shared layout.
+struct EmailTemplate {
+    subject: String,
+    paragraphs: Vec<String>,
+    link_label: &'static str,
+}
+
+impl EmailTemplate {
+    fn render(&self, first_name: &str, link: &str) -> RenderedEmail {
+        let greeting = match first_name.trim() {
+            "" => "Hi,".to_string(),
+            name => format!("Hi {},", name),
+        };
+        let settings_link = app_url("/profile");
+
+        let mut text_body = format!("{}\n\n", greeting);
+        for paragraph in &self.paragraphs {
+            text_body.push_str(&format!("{}\n\n", paragraph));
+        }
+        text_body.push_str(&format!(
+            "{}: {}\n\n-- \n{}\nChoose which emails you get: {}\n",
+            self.link_label, link, APP_NAME, settings_link
+        ));
+
+        let paragraphs: String = self
+            .paragraphs
+            .iter()
+            .map(|paragraph| format!("<p>{}</p>\n", escape_html(paragraph)))
+            .collect();
+        let html_body = format!(
+            "<!DOCTYPE html>\n<html>\n<body style=\"font-family: sans-serif; color: #222;\">\n\
+             <p>{greeting}</p>\n{paragraphs}\
+             <p><a href=\"{link}\">{link_label}</a></p>\n\
+             <hr>\n<p style=\"font-size: 12px; color: #777;\">{app} &middot; <a href=\"{settings}\">Choose which emails you get</a></p>\n\
+             </body>\n</html>\n",
+            greeting = escape_html(&greeting),
+            paragraphs = paragraphs,
+            link = escape_html(link),
+            link_label = self.link_label,
+            app = APP_NAME,
+            settings = escape_html(&settings_link),
+        );
+
+        RenderedEmail {
+            subject: self.subject.clone(),
+            text_body,
+            html_body,
+        }
+    }
+}
+
+pub fn app_url(path: &str) -> String {
+    let base_url = env::var("APP_BASE_URL").unwrap_or_else(|_| DEFAULT_APP_BASE_URL.to_string());
+
+    format!("{}{}", base_url.trim_end_matches('/'), path)
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+pub struct NotificationManager;
+
+impl NotificationManager {
+    /// Tells `user_id` about `notification` as part of the transaction that caused
+    /// it: always in the app and, for notifications with a template, by email.
+    /// Users who turned the topic off are skipped.
+    pub async fn notify(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        notification: Notification,
+    ) -> Result<(), Error> {
+        let Some(user) = sqlx::query!(
+            "SELECT email, first_name, notification_preferences FROM users WHERE supabase_id = $1",
+            user_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        else {
+            return Ok(());
+        };
+
+        let preferences: NotificationPreferences =
+            serde_json::from_value(user.notification_preferences).unwrap_or_default();
+        if !notification.wanted(&preferences) {
+            return Ok(());
+        }
+
+        let link = notification.deep_link();
+        sqlx::query!(
+            "INSERT INTO notifications (user_id, kind, payload, link) VALUES ($1, $2, $3, $4)",
+            user_id,
+            notification.to_string(),
+            Json(&notification) as _,
+            link
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        if user.email.is_empty() || !preferences.email.unwrap_or(true) {
+            return Ok(());
+        }
+        if let Some(template) = notification.template() {
+            let email = template.render(&user.first_name, &link);
+            EmailOutboxManager::enqueue(
+                tx,
+                Some(user_id),
+                &user.email,
+                &notification.to_string(),
+                &email,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Tells everyone who marked `camp_id` as favorite, except `except_user_id`,
+    /// in one statement however many followers there are. Users who turned the
+    /// topic off or already have this notification are skipped. Follower
+    /// notifications stay in the app, so no email is queued.
+    pub async fn notify_followers(
+        tx: &mut Transaction<'_, Postgres>,
+        camp_id: i64,
+        except_user_id: &str,
+        notification: Notification,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "INSERT INTO notifications (user_id, kind, payload, link)
+            SELECT users.supabase_id, $3, $4, $5
+            FROM users_camps JOIN users ON users.supabase_id = users_camps.user_id
+            WHERE users_camps.camp_id = $1 AND users_camps.user_id <> $2
+                AND COALESCE((users.notification_preferences ->> $6)::boolean, true)
+                AND NOT EXISTS (
+                    SELECT 1 FROM notifications
+                    WHERE notifications.user_id = users.supabase_id AND notifications.payload = $4
+                )",
+            camp_id,
+            except_user_id,
+            notification.to_string(),
+            Json(&notification) as _,
+            notification.deep_link(),
+            notification.topic()
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_notifications(
+        db: &PgPool,
+        utx: UserCtx,
+        filter: NotificationFilter,
+    ) -> Result<Vec<InboxNotification>, Error> {
+        let limit = filter
+            .limit
+            .unwrap_or(DEFAULT_INBOX_LIMIT)
+            .clamp(1, MAX_INBOX_LIMIT);
+
+        let notifications = sqlx::query_as!(
+            InboxNotification,
+            r#"SELECT id, kind, payload as "payload: Json<Notification>", link, ctime, read_at
+            FROM notifications
+            WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL) AND ($3::bigint IS NULL OR id < $3)
+            ORDER BY id DESC LIMIT $4"#,
+            utx.user_id,
+            filter.unread,
+            filter.before,
+            limit
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(notifications)
+    }
+
+    pub async fn mark_read(
+        db: &PgPool,
+        utx: UserCtx,
+        notification_id: i64,
+    ) -> Result<InboxNotification, Error> {
+        let notification = sqlx::query_as!(
+            InboxNotification,
+            r#"UPDATE notifications SET read_at = COALESCE(read_at, now())
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, kind, payload as "payload: Json<Notification>", link, ctime, read_at"#,
+            notification_id,
+            utx.user_id
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(notification)
+    }
+
+    /// Returns how many notifications were unread.
+    pub async fn mark_all_read(db: &PgPool, utx: UserCtx) -> Result<u64, Error> {
+        let result = sqlx::query!(
+            "UPDATE notifications SET read_at = now() WHERE user_id = $1 AND read_at IS NULL",
+            utx.user_id
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete_notification(
+        db: &PgPool,
+        utx: UserCtx,
+        notification_id: i64,
+    ) -> Result<(), Error> {
+        sqlx::query_scalar!(
+            "DELETE FROM notifications WHERE id = $1 AND user_id = $2 RETURNING id",
+            notification_id,
+            utx.user_id
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Turns domain events into notifications for the users they concern.
+pub struct NotificationHandler;
+
+#[async_trait]
+impl EventHandler for NotificationHandler {
+    async fn handle(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        event: &DomainEvent,
+    ) -> Result<(), Error> {
+        match event {
+            DomainEvent::CampRequestApproved {
+                camp_request_id,
+                camp_id,
+                requested_by,
+            } => {
+                let camp_name = camp_name(tx, *camp_id).await?;
+                NotificationManager::notify(
+                    tx,
+                    requested_by,
+                    Notification::CampRequestApproved {
+                        camp_request_id: *camp_request_id,
+                        camp_id: *camp_id,
+                        camp_name,
+                    },
+                )
+                .await
+            }
+            DomainEvent::ReviewCreated {
+                review_id,
+                camp_id,
+                author_id,
+            } => {
+                let camp_name = camp_name(tx, *camp_id).await?;
+                NotificationManager::notify_followers(
+                    tx,
+                    *camp_id,
+                    author_id,
+                    Notification::NewReviewOnFavorite {
+                        review_id: *review_id,
+                        camp_id: *camp_id,
+                        camp_name,
+                    },
+                )
+                .await
+            }
+            DomainEvent::ReviewModerated {
+                review_id,
+                camp_id,
+                author_id,
+                status,
+            } => {
+                let camp_name = camp_name(tx, *camp_id).await?;
+                let (review_id, camp_id) = (*review_id, *camp_id);
+                match status {
+                    ModerationStatus::Published => {
+                        NotificationManager::notify(
+                            tx,
+                            author_id,
+                            Notification::ReviewPublished {
+                                review_id,
+                                camp_id,
+                                camp_name: camp_name.clone(),
+                            },
+                        )
+                        .await?;
+                        // A held review was never announced when it was created
+                        NotificationManager::notify_followers(
+                            tx,
+                            camp_id,
+                            author_id,
+                            Notification::NewReviewOnFavorite {
+                                review_id,
+                                camp_id,
+                                camp_name,
+                            },
+                        )
+                        .await
+                    }
+                    ModerationStatus::Held => {
+                        NotificationManager::notify(
+                            tx,
+                            author_id,
+                            Notification::ReviewHidden {
+                                review_id,
+                                camp_id,
+                                camp_name,
+                            },
+                        )
+                        .await
+                    }
+                }
+            }
+            DomainEvent::ReviewVotedHelpful {
+                review_id,
+                camp_id,
+                author_id,
+                ..
+            } => {
+                let camp_name = camp_name(tx, *camp_id).await?;
+                NotificationManager::notify(
+                    tx,
+                    author_id,
+                    Notification::ReviewVotedHelpful {
+                        review_id: *review_id,
+                        camp_id: *camp_id,
+                        camp_name,
+                    },
+                )
+                .await
+            }
+            DomainEvent::ReviewUpdated { .. }
+            | DomainEvent::ReviewDeleted { .. }
+            | DomainEvent::CampRatingChanged { .. } => Ok(()),
+        }
+    }
+}
+
+async fn camp_name(tx: &mut Transaction<'_, Postgres>, camp_id: i64) -> Result<String, Error> {
+    let name = sqlx::query_scalar!("SELECT name FROM camps WHERE id = $1", camp_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topics_match_the_preference_keys() {
+        let notifications = [
+            Notification::CampRequestApproved {
+                camp_request_id: 1,
+                camp_id: 2,
+                camp_name: "Pine Lake".to_string(),
+            },
+            Notification::ReviewHidden {
+                review_id: 3,
+                camp_id: 2,
+                camp_name: "Pine Lake".to_string(),
+            },
+            Notification::ReviewPublished {
+                review_id: 3,
+                camp_id: 2,
+                camp_name: "Pine Lake".to_string(),
+            },
+            Notification::NewReviewOnFavorite {
+                review_id: 3,
+                camp_id: 2,
+                camp_name: "Pine Lake".to_string(),
+            },
+            Notification::ReviewVotedHelpful {
+                review_id: 3,
+                camp_id: 2,
+                camp_name: "Pine Lake".to_string(),
+            },
+        ];
+
+        for notification in notifications {
+            let opted_out: NotificationPreferences =
+                serde_json::from_value(serde_json::json!({ notification.topic(): false }))
+                    .expect("known preference");
+            assert!(!notification.wanted(&opted_out), "{}", notification);
+            assert!(notification.wanted(&NotificationPreferences::default()));
+        }
+    }
+}
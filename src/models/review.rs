@@ -2,20 +2,46 @@ use chrono::{serde::ts_seconds, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, PgPool};
 
-use super::Error;
+use super::{
+    events::{DomainEvent, EventBus},
+    rich_text::{render_markdown, review_body_max_length},
+    screening::{ContentKind, ModerationStatus, ScreeningManager, Submission},
+    validation::{Validate, ValidationErrors},
+    Error,
+};
 use crate::auth::UserCtx;
 
-#[derive(Debug, FromRow, Serialize, Deserialize)]
+const MIN_RATING: i32 = 1;
+const MAX_RATING: i32 = 5;
+
+#[derive(Debug, FromRow, Serialize, Deserialize, Default)]
 pub struct Review {
     pub id: i64,
     pub author_id: String,
     pub camp_id: i64,
     #[serde(with = "ts_seconds")]
     pub ctime: sqlx::types::chrono::DateTime<Utc>,
+    /// The Markdown source as written by the author.
     pub body: String,
     pub rating: i32,
+    /// `body` rendered to sanitized HTML.
+    pub body_html: String,
+    /// Held reviews are only visible to their author and moderators.
+    pub moderation_status: String,
+    pub photo_urls: Option<Vec<String>>,
+    pub staff_rating: Option<i32>,
+    pub activities_rating: Option<i32>,
+    pub facilities_rating: Option<i32>,
+    pub food_rating: Option<i32>,
+    pub safety_rating: Option<i32>,
+    /// Hash of the normalized text, only read in SQL by screening to find
+    /// copy-paste spam.
+    #[allow(dead_code)]
+    #[serde(skip)]
+    pub text_hash: Option<String>,
 }
 
+/// A published review as shown publicly, with the author's public details only.
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct ReviewWithUser {
     pub id: i64,
@@ -25,23 +51,19 @@ pub struct ReviewWithUser {
     pub ctime: sqlx::types::chrono::DateTime<Utc>,
     pub body: String,
     pub rating: i32,
+    pub body_html: String,
+    pub moderation_status: String,
+    pub photo_urls: Option<Vec<String>>,
+    pub staff_rating: Option<i32>,
+    pub activities_rating: Option<i32>,
+    pub facilities_rating: Option<i32>,
+    pub food_rating: Option<i32>,
+    pub safety_rating: Option<i32>,
     pub first_name: String,
     pub last_name: String,
-    pub email: String,
     pub username: String,
-}
-
-impl Default for Review {
-    fn default() -> Self {
-        Self {
-            id: 0,
-            author_id: String::new(),
-            camp_id: 0,
-            ctime: sqlx::types::chrono::DateTime::default(),
-            body: String::new(),
-            rating: 0,
-        }
-    }
+    pub avatar_url: Option<String>,
+    pub helpful_votes: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,6 +71,44 @@ pub struct ReviewPatch {
     pub body: String,
     pub rating: i32,
     pub photos: Option<Vec<String>>,
+    /// Optional ratings of single aspects of the camp, on the same scale as `rating`.
+    pub staff_rating: Option<i32>,
+    pub activities_rating: Option<i32>,
+    pub facilities_rating: Option<i32>,
+    pub food_rating: Option<i32>,
+    pub safety_rating: Option<i32>,
+}
+
+impl ReviewPatch {
+    fn sub_ratings(&self) -> [(&'static str, Option<i32>); 5] {
+        [
+            ("$.staff_rating", self.staff_rating),
+            ("$.activities_rating", self.activities_rating),
+            ("$.facilities_rating", self.facilities_rating),
+            ("$.food_rating", self.food_rating),
+            ("$.safety_rating", self.safety_rating),
+        ]
+    }
+}
+
+impl Validate for ReviewPatch {
+    fn validate(&mut self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+
+        self.body = self.body.trim().to_string();
+        errors.check_length("$.body", &self.body, review_body_max_length());
+        errors.check_range("$.rating", self.rating, MIN_RATING, MAX_RATING);
+        for (path, rating) in self.sub_ratings() {
+            if let Some(rating) = rating {
+                errors.check_range(path, rating, MIN_RATING, MAX_RATING);
+            }
+        }
+        for (index, url) in self.photos.iter().flatten().enumerate() {
+            errors.check_url(&format!("$.photos[{}]", index), url);
+        }
+
+        errors.into_result()
+    }
 }
 
 pub struct ReviewManager;
@@ -57,17 +117,73 @@ impl ReviewManager {
     pub async fn create(
         db: &PgPool,
         utx: UserCtx,
-        data: ReviewPatch,
+        mut data: ReviewPatch,
         camp_id: i64,
     ) -> Result<Review, Error> {
-        Self::delete_user_reviews_for_camp(db, &utx, camp_id).await?;
+        data.validate()?;
+
+        let submission = Submission {
+            kind: ContentKind::Review,
+            user_id: &utx.user_id,
+            camp_id: Some(camp_id),
+            text: data.body.clone(),
+        };
+        let screening = ScreeningManager::screen(db, &submission).await?;
+
+        let mut tx = db.begin().await?;
+
+        // A user has one review per camp, so a new one replaces theirs
+        let replaced = sqlx::query!(
+            "DELETE FROM reviews WHERE camp_id = $1 AND author_id = $2 RETURNING id, moderation_status",
+            camp_id,
+            utx.user_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let replaced_id = replaced
+            .iter()
+            .find(|review| review.moderation_status == ModerationStatus::Published.to_string())
+            .map(|review| review.id);
 
-        let review = sqlx::query_as!(Review, "INSERT INTO reviews (camp_id, author_id, body, rating) VALUES ($1, $2, $3, $4) returning *",
-    camp_id, utx.clone().user_id, &data.body, &data.rating)
+        let review = sqlx::query_as!(Review, "INSERT INTO reviews (camp_id, author_id, body, rating, body_html, moderation_status, photo_urls, staff_rating, activities_rating, facilities_rating, food_rating, safety_rating) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) returning *",
+    camp_id, utx.clone().user_id, &data.body, &data.rating, render_markdown(&data.body), screening.status.to_string(), &data.photos.unwrap_or_default(),
+    data.staff_rating, data.activities_rating, data.facilities_rating, data.food_rating, data.safety_rating)
 
-            .fetch_one(db)
+            .fetch_one(&mut *tx)
             .await?;
 
+        let author_id = review.author_id.clone();
+        let event = match (replaced_id, screening.status) {
+            (None, ModerationStatus::Published) => Some(DomainEvent::ReviewCreated {
+                review_id: review.id,
+                camp_id,
+                author_id,
+            }),
+            (Some(previous_review_id), ModerationStatus::Published) => {
+                Some(DomainEvent::ReviewUpdated {
+                    review_id: review.id,
+                    previous_review_id,
+                    camp_id,
+                    author_id,
+                })
+            }
+            // The published review is gone while its replacement waits for a moderator
+            (Some(previous_review_id), ModerationStatus::Held) => {
+                Some(DomainEvent::ReviewDeleted {
+                    review_id: previous_review_id,
+                    camp_id,
+                    author_id,
+                })
+            }
+            (None, ModerationStatus::Held) => None,
+        };
+        if let Some(event) = event {
+            EventBus::publish(&mut tx, event).await?;
+        }
+
+        tx.commit().await?;
+
+        ScreeningManager::record(db, &submission, review.id, &screening).await?;
         update_calc_review_average(camp_id, db).await?;
 
         Ok(review)
@@ -91,70 +207,248 @@ impl ReviewManager {
     }
 
     pub async fn delete(db: &PgPool, _utx: &UserCtx, review_id: i64) -> Result<String, Error> {
-        sqlx::query!("DELETE FROM reviews where id = $1", review_id,)
-            .execute(db)
-            .await?;
+        let mut tx = db.begin().await?;
 
-        update_calc_review_average(review_id, db).await?;
+        let deleted = sqlx::query!(
+            "DELETE FROM reviews where id = $1 RETURNING camp_id, author_id, moderation_status",
+            review_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(deleted) = &deleted {
+            if deleted.moderation_status == ModerationStatus::Published.to_string() {
+                EventBus::publish(
+                    &mut tx,
+                    DomainEvent::ReviewDeleted {
+                        review_id,
+                        camp_id: deleted.camp_id,
+                        author_id: deleted.author_id.clone(),
+                    },
+                )
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        if let Some(deleted) = deleted {
+            update_calc_review_average(deleted.camp_id, db).await?;
+        }
 
         Ok("Review deleted successfully".to_string())
     }
 
-    pub async fn delete_user_reviews_for_camp(
+    pub async fn get_camp_reviews(db: &PgPool, camp_id: i64) -> Result<Vec<ReviewWithUser>, Error> {
+        let reviews = sqlx::query_as::<_, ReviewWithUser>(
+            "SELECT reviews.*, first_name, last_name, username, avatar_url,
+                (SELECT COUNT(*) FROM review_helpful_votes WHERE review_id = reviews.id) AS helpful_votes
+            FROM reviews JOIN users ON author_id = supabase_id where camp_id = $1 AND moderation_status = 'published'",
+        )
+        .bind(camp_id)
+        .fetch_all(db)
+        .await?;
+
+        Ok(reviews)
+    }
+
+    /// Counts the caller's "helpful" vote on someone else's review, once per user.
+    pub async fn vote_helpful(db: &PgPool, utx: UserCtx, review_id: i64) -> Result<(), Error> {
+        let review = sqlx::query!(
+            "SELECT author_id, camp_id FROM reviews WHERE id = $1 AND moderation_status = 'published'",
+            review_id
+        )
+        .fetch_one(db)
+        .await?;
+        if review.author_id == utx.user_id {
+            return Err(Error::InvalidData(
+                "Cannot vote on your own review".to_string(),
+            ));
+        }
+
+        let mut tx = db.begin().await?;
+
+        let voted = sqlx::query!(
+            "INSERT INTO review_helpful_votes (review_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            review_id,
+            utx.user_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+        if voted {
+            EventBus::publish(
+                &mut tx,
+                DomainEvent::ReviewVotedHelpful {
+                    review_id,
+                    camp_id: review.camp_id,
+                    author_id: review.author_id,
+                    voter_id: utx.user_id,
+                },
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_helpful_vote(
         db: &PgPool,
-        utx: &UserCtx,
-        camp_id: i64,
-    ) -> Result<String, Error> {
+        utx: UserCtx,
+        review_id: i64,
+    ) -> Result<(), Error> {
         sqlx::query!(
-            "DELETE FROM reviews where camp_id = $1 AND author_id = $2",
-            camp_id,
+            "DELETE FROM review_helpful_votes WHERE review_id = $1 AND user_id = $2",
+            review_id,
             utx.user_id
         )
         .execute(db)
         .await?;
 
-        update_calc_review_average(camp_id, db).await?;
-
-        Ok("Reviews deleted successfully".to_string())
+        Ok(())
     }
 
-    pub async fn get_camp_reviews(db: &PgPool, camp_id: i64) -> Result<Vec<ReviewWithUser>, Error> {
-        let reviews = sqlx::query_as::<_, ReviewWithUser>(
-            "SELECT * FROM reviews JOIN users ON author_id = supabase_id where camp_id = $1",
+    /// Reviews the screening pipeline held back, oldest first.
+    pub async fn get_held_reviews(db: &PgPool, _utx: UserCtx) -> Result<Vec<Review>, Error> {
+        let reviews = sqlx::query_as!(
+            Review,
+            "SELECT * FROM reviews WHERE moderation_status = 'held' ORDER BY ctime, id"
         )
-        .bind(camp_id)
         .fetch_all(db)
         .await?;
 
         Ok(reviews)
     }
 
+    pub async fn publish_review(
+        db: &PgPool,
+        review_id: i64,
+        _utx: UserCtx,
+    ) -> Result<Review, Error> {
+        Self::moderate(db, review_id, ModerationStatus::Published).await
+    }
+
+    /// Takes a published review out of public view until a moderator publishes it again.
+    pub async fn hide_review(db: &PgPool, review_id: i64, _utx: UserCtx) -> Result<Review, Error> {
+        Self::moderate(db, review_id, ModerationStatus::Held).await
+    }
+
+    /// Moves a review to `status` and tells its author when that changed anything.
+    async fn moderate(
+        db: &PgPool,
+        review_id: i64,
+        status: ModerationStatus,
+    ) -> Result<Review, Error> {
+        let mut tx = db.begin().await?;
+
+        let previous = sqlx::query_scalar!(
+            "SELECT moderation_status FROM reviews WHERE id = $1 FOR UPDATE",
+            review_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let review = sqlx::query_as!(
+            Review,
+            "UPDATE reviews SET moderation_status = $2 WHERE id = $1 returning *",
+            review_id,
+            status.to_string()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if previous != review.moderation_status {
+            EventBus::publish(
+                &mut tx,
+                DomainEvent::ReviewModerated {
+                    review_id,
+                    camp_id: review.camp_id,
+                    author_id: review.author_id.clone(),
+                    status,
+                },
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        update_calc_review_average(review.camp_id, db).await?;
+
+        Ok(review)
+    }
+
     pub async fn delete_all_camp_reviews(
         db: &PgPool,
         _utx: UserCtx,
         camp_id: i64,
     ) -> Result<(), Error> {
-        let query = "DELETE FROM reviews where camp_id = $1";
+        let mut tx = db.begin().await?;
+
+        let deleted = sqlx::query!(
+            "DELETE FROM reviews where camp_id = $1 AND moderation_status = 'published' RETURNING id, author_id",
+            camp_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        sqlx::query!("DELETE FROM reviews where camp_id = $1", camp_id)
+            .execute(&mut *tx)
+            .await?;
+        for review in deleted {
+            EventBus::publish(
+                &mut tx,
+                DomainEvent::ReviewDeleted {
+                    review_id: review.id,
+                    camp_id,
+                    author_id: review.author_id,
+                },
+            )
+            .await?;
+        }
 
-        sqlx::query(query).bind(camp_id).execute(db).await?;
+        tx.commit().await?;
+
+        update_calc_review_average(camp_id, db).await?;
 
         Ok(())
     }
-}
 
-async fn update_calc_review_average(camp_id: i64, db: &PgPool) -> Result<(), Error> {
-    let camp_reviews = sqlx::query!("SELECT (rating) FROM reviews WHERE camp_id = $1", camp_id)
+    /// Recalculates the rating of every camp that has or had reviews, returning
+    /// how many camps were checked.
+    pub async fn recompute_ratings(db: &PgPool) -> Result<usize, Error> {
+        let camp_ids = sqlx::query_scalar!(
+            "SELECT id FROM camps WHERE rating IS NOT NULL OR EXISTS(SELECT 1 FROM reviews WHERE camp_id = camps.id)"
+        )
         .fetch_all(db)
         .await?;
 
-    let total_reviews = camp_reviews.len() as i32;
+        for camp_id in &camp_ids {
+            update_calc_review_average(*camp_id, db).await?;
+        }
+
+        Ok(camp_ids.len())
+    }
+}
 
-    let avg =
-        camp_reviews.iter().map(|review| review.rating).sum::<i32>() as f32 / total_reviews as f32;
+/// Recalculates a camp's rating from its published reviews and announces it
+/// when it changed.
+pub(super) async fn update_calc_review_average(camp_id: i64, db: &PgPool) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
 
-    sqlx::query!("UPDATE camps SET rating = $1 WHERE id = $2", avg, camp_id)
-        .execute(db)
-        .await?;
+    let rating = sqlx::query_scalar!(
+        "WITH average AS (SELECT avg(rating)::real AS rating FROM reviews WHERE camp_id = $1 AND moderation_status = 'published')
+        UPDATE camps SET rating = average.rating FROM average
+        WHERE id = $1 AND camps.rating IS DISTINCT FROM average.rating RETURNING camps.rating",
+        camp_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if let Some(rating) = rating {
+        EventBus::publish(&mut tx, DomainEvent::CampRatingChanged { camp_id, rating }).await?;
+    }
+
+    tx.commit().await?;
 
     Ok(())
 }
@@ -0,0 +1,237 @@
+use std::{
+    fmt,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, PgPool, Postgres, Transaction};
+use tokio::sync::broadcast;
+
+use super::{
+    events::{DomainEvent, EventHandler},
+    screening::ModerationStatus,
+    Error,
+};
+
+/// The Postgres channel every instance announces review activity on.
+const CHANNEL: &str = "review_activity";
+/// Events buffered for a slow subscriber before it starts missing them.
+const BUFFER_SIZE: usize = 256;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const REVIEW_CREATED: &str = "review_created";
+const REVIEW_UPDATED: &str = "review_updated";
+const REVIEW_DELETED: &str = "review_deleted";
+const RATING_CHANGED: &str = "rating_changed";
+/// Every `ReviewActivity` kind, as named by its `Display`.
+pub const ACTIVITY_KINDS: [&str; 4] = [
+    REVIEW_CREATED,
+    REVIEW_UPDATED,
+    REVIEW_DELETED,
+    RATING_CHANGED,
+];
+
+/// A change to what the public sees of a camp's reviews.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReviewActivity {
+    ReviewCreated {
+        camp_id: i64,
+        review_id: i64,
+    },
+    /// The review replaced `previous_review_id`, which is gone.
+    ReviewUpdated {
+        camp_id: i64,
+        review_id: i64,
+        previous_review_id: i64,
+    },
+    ReviewDeleted {
+        camp_id: i64,
+        review_id: i64,
+    },
+    RatingChanged {
+        camp_id: i64,
+        rating: Option<f32>,
+    },
+}
+
+impl fmt::Display for ReviewActivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            ReviewActivity::ReviewCreated { .. } => REVIEW_CREATED,
+            ReviewActivity::ReviewUpdated { .. } => REVIEW_UPDATED,
+            ReviewActivity::ReviewDeleted { .. } => REVIEW_DELETED,
+            ReviewActivity::RatingChanged { .. } => RATING_CHANGED,
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl ReviewActivity {
+    pub fn camp_id(&self) -> i64 {
+        match self {
+            ReviewActivity::ReviewCreated { camp_id, .. }
+            | ReviewActivity::ReviewUpdated { camp_id, .. }
+            | ReviewActivity::ReviewDeleted { camp_id, .. }
+            | ReviewActivity::RatingChanged { camp_id, .. } => *camp_id,
+        }
+    }
+
+    /// Moderation shows up as reviews appearing and disappearing, since held
+    /// reviews are not public.
+    pub fn from_event(event: &DomainEvent) -> Option<Self> {
+        let activity = match event {
+            DomainEvent::ReviewCreated {
+                review_id, camp_id, ..
+            }
+            | DomainEvent::ReviewModerated {
+                review_id,
+                camp_id,
+                status: ModerationStatus::Published,
+                ..
+            } => ReviewActivity::ReviewCreated {
+                camp_id: *camp_id,
+                review_id: *review_id,
+            },
+            DomainEvent::ReviewUpdated {
+                review_id,
+                previous_review_id,
+                camp_id,
+                ..
+            } => ReviewActivity::ReviewUpdated {
+                camp_id: *camp_id,
+                review_id: *review_id,
+                previous_review_id: *previous_review_id,
+            },
+            DomainEvent::ReviewDeleted {
+                review_id, camp_id, ..
+            }
+            | DomainEvent::ReviewModerated {
+                review_id,
+                camp_id,
+                status: ModerationStatus::Held,
+                ..
+            } => ReviewActivity::ReviewDeleted {
+                camp_id: *camp_id,
+                review_id: *review_id,
+            },
+            DomainEvent::CampRatingChanged { camp_id, rating } => ReviewActivity::RatingChanged {
+                camp_id: *camp_id,
+                rating: *rating,
+            },
+            DomainEvent::CampRequestApproved { .. } | DomainEvent::ReviewVotedHelpful { .. } => {
+                return None
+            }
+        };
+
+        Some(activity)
+    }
+}
+
+/// Announces review activity with `NOTIFY`, which Postgres only delivers once
+/// the transaction commits.
+pub struct ReviewActivityHandler;
+
+#[async_trait]
+impl EventHandler for ReviewActivityHandler {
+    async fn handle(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        event: &DomainEvent,
+    ) -> Result<(), Error> {
+        let Some(activity) = ReviewActivity::from_event(event) else {
+            return Ok(());
+        };
+        let payload = serde_json::to_string(&activity)
+            .map_err(|ex| Error::InvalidData(format!("invalid review activity: {}", ex)))?;
+
+        sqlx::query!("SELECT pg_notify($1, $2)", CHANNEL, payload)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn sender() -> &'static broadcast::Sender<ReviewActivity> {
+    static SENDER: OnceLock<broadcast::Sender<ReviewActivity>> = OnceLock::new();
+    SENDER.get_or_init(|| broadcast::channel(BUFFER_SIZE).0)
+}
+
+pub struct ReviewActivityManager;
+
+impl ReviewActivityManager {
+    /// Review activity from every instance, starting now.
+    pub fn subscribe() -> broadcast::Receiver<ReviewActivity> {
+        sender().subscribe()
+    }
+
+    /// Relays notifications from every instance, this one included, to local
+    /// subscribers until the process ends. Activity announced while the
+    /// connection is down is lost.
+    pub async fn listen(db: Arc<PgPool>) {
+        loop {
+            if let Err(ex) = Self::relay(&db).await {
+                println!("ERROR - review activity listener failed. Cause: {:?}", ex);
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn relay(db: &PgPool) -> Result<(), Error> {
+        let mut listener = PgListener::connect_with(db).await?;
+        listener.listen(CHANNEL).await?;
+
+        loop {
+            let notification = listener.recv().await?;
+            match serde_json::from_str::<ReviewActivity>(notification.payload()) {
+                // Nobody listening is not an error
+                Ok(activity) => {
+                    let _ = sender().send(activity);
+                }
+                Err(ex) => println!(
+                    "ERROR - invalid review activity {:?}. Cause: {:?}",
+                    notification.payload(),
+                    ex
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn kinds_match_display_and_serialized_type() {
+        let activities = [
+            ReviewActivity::ReviewCreated {
+                camp_id: 1,
+                review_id: 2,
+            },
+            ReviewActivity::ReviewUpdated {
+                camp_id: 1,
+                review_id: 3,
+                previous_review_id: 2,
+            },
+            ReviewActivity::ReviewDeleted {
+                camp_id: 1,
+                review_id: 3,
+            },
+            ReviewActivity::RatingChanged {
+                camp_id: 1,
+                rating: Some(4.5),
+            },
+        ];
+
+        let kinds: Vec<String> = activities.iter().map(ToString::to_string).collect();
+        assert_eq!(kinds, ACTIVITY_KINDS);
+        for activity in &activities {
+            assert_eq!(json!(activity)["type"], json!(activity.to_string()));
+        }
+    }
+}
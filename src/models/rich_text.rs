@@ -0,0 +1,124 @@
+use std::{collections::HashSet, env, sync::OnceLock};
+
+use ammonia::{Builder, UrlRelative};
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+
+/// Review bodies are capped at this many characters unless `REVIEW_BODY_MAX_LENGTH` is set.
+const DEFAULT_REVIEW_BODY_MAX_LENGTH: usize = 5000;
+
+/// Tags that survive sanitization: paragraphs, lists, emphasis and links.
+const ALLOWED_TAGS: [&str; 8] = ["p", "br", "ul", "ol", "li", "em", "strong", "a"];
+
+pub fn review_body_max_length() -> usize {
+    static MAX_LENGTH: OnceLock<usize> = OnceLock::new();
+
+    *MAX_LENGTH.get_or_init(|| {
+        env::var("REVIEW_BODY_MAX_LENGTH")
+            .ok()
+            .and_then(|max_length| max_length.parse().ok())
+            .unwrap_or(DEFAULT_REVIEW_BODY_MAX_LENGTH)
+    })
+}
+
+/// Renders the restricted Markdown subset to HTML that is safe to embed as-is.
+pub fn render_markdown(source: &str) -> String {
+    // Raw HTML in the source is shown as text rather than interpreted, and
+    // headings outside the subset are demoted to plain paragraphs.
+    let events = Parser::new_ext(source, Options::empty()).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        Event::Start(Tag::Heading { .. }) => Event::Start(Tag::Paragraph),
+        Event::End(TagEnd::Heading(_)) => Event::End(TagEnd::Paragraph),
+        event => event,
+    });
+
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, events);
+
+    sanitizer().clean(&rendered).to_string()
+}
+
+fn sanitizer() -> &'static Builder<'static> {
+    static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+
+    SANITIZER.get_or_init(|| {
+        let mut builder = Builder::empty();
+        builder
+            .tags(HashSet::from(ALLOWED_TAGS))
+            .add_tag_attributes("a", ["href"])
+            .url_schemes(HashSet::from(["http", "https", "mailto"]))
+            .url_relative(UrlRelative::Deny)
+            .link_rel(Some("nofollow ugc"));
+        builder
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_supported_subset() {
+        assert_eq!(
+            render_markdown("Loved *canoeing* and **archery**."),
+            "<p>Loved <em>canoeing</em> and <strong>archery</strong>.</p>\n"
+        );
+        assert_eq!(
+            render_markdown("- Lake\n- Cabins\n\n1. First\n2. Second"),
+            "<ul>\n<li>Lake</li>\n<li>Cabins</li>\n</ul>\n<ol>\n<li>First</li>\n<li>Second</li>\n</ol>\n"
+        );
+        assert_eq!(
+            render_markdown("First line  \nsecond line"),
+            "<p>First line<br>\nsecond line</p>\n"
+        );
+    }
+
+    #[test]
+    fn links_are_marked_as_user_content() {
+        assert_eq!(
+            render_markdown("[Pine Lake](https://pinelake.example/)"),
+            "<p><a href=\"https://pinelake.example/\" rel=\"nofollow ugc\">Pine Lake</a></p>\n"
+        );
+        assert_eq!(
+            render_markdown("[Write us](mailto:hello@pinelake.example)"),
+            "<p><a href=\"mailto:hello@pinelake.example\" rel=\"nofollow ugc\">Write us</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn unsafe_and_relative_links_lose_their_target() {
+        assert_eq!(
+            render_markdown("[click](javascript:alert(1))"),
+            "<p><a rel=\"nofollow ugc\">click</a></p>\n"
+        );
+        assert_eq!(
+            render_markdown("[admin](/admin)"),
+            "<p><a rel=\"nofollow ugc\">admin</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn raw_html_is_shown_as_text() {
+        assert_eq!(
+            render_markdown("Hi <script>alert(1)</script> there"),
+            "<p>Hi &lt;script&gt;alert(1)&lt;/script&gt; there</p>\n"
+        );
+        assert_eq!(
+            render_markdown("<img src=x onerror=alert(1)>"),
+            "&lt;img src=x onerror=alert(1)&gt;"
+        );
+    }
+
+    #[test]
+    fn unsupported_blocks_are_flattened() {
+        assert_eq!(render_markdown("# Best camp"), "<p>Best camp</p>\n");
+        // Images are dropped along with their alt text
+        assert_eq!(
+            render_markdown("![cabin](https://pinelake.example/cabin.jpg)"),
+            "<p></p>\n"
+        );
+        assert_eq!(
+            render_markdown("> quoted\n\n`code`"),
+            "\n<p>quoted</p>\n\n<p>code</p>\n"
+        );
+    }
+}
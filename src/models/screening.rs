@@ -0,0 +1,607 @@
+use std::{collections::BTreeMap, env, fmt, fs, sync::OnceLock};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use super::Error;
+
+/// Submissions scoring at or above this are held unless `SPAM_HOLD_SCORE` is set.
+const DEFAULT_HOLD_SCORE: f64 = 1.0;
+
+const DEFAULT_BLOCKLIST: [&str; 6] = [
+    "viagra",
+    "casino",
+    "payday loan",
+    "crypto giveaway",
+    "bit.ly/",
+    "tinyurl.com/",
+];
+const DEFAULT_PROFANITY: [&str; 8] = [
+    "fuck", "fucking", "shit", "bitch", "bastard", "asshole", "cunt", "dick",
+];
+
+const BURST_WINDOW_MINUTES: i64 = 10;
+const BURST_ALLOWANCE: i64 = 4;
+const NEW_ACCOUNT_HOURS: i64 = 24;
+const NEW_ACCOUNT_CAMP_ALLOWANCE: i64 = 2;
+/// Shorter texts ("Great camp!") are too common to count as copy-paste spam.
+/// Counted in characters.
+const MIN_DUPLICATE_TEXT_LEN: usize = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    Review,
+    CampRequest,
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            ContentKind::Review => "review",
+            ContentKind::CampRequest => "camp_request",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// Whether content is visible or waiting for a moderator.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationStatus {
+    Published,
+    Held,
+}
+
+impl fmt::Display for ModerationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            ModerationStatus::Published => "published",
+            ModerationStatus::Held => "held",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// User submitted content about to be published.
+#[derive(Debug)]
+pub struct Submission<'a> {
+    pub kind: ContentKind,
+    pub user_id: &'a str,
+    /// The camp being reviewed, if any.
+    pub camp_id: Option<i64>,
+    pub text: String,
+}
+
+/// What is known about the submitting account, loaded once per screening.
+#[derive(Debug, Default)]
+pub struct SubmissionHistory {
+    pub account_created_at: Option<DateTime<Utc>>,
+    /// Submissions of any kind within the burst window.
+    pub recent_submissions: i64,
+    /// Distinct camps the account reviewed since it was created, for new accounts.
+    pub camps_reviewed: i64,
+    /// Identical texts already submitted by anyone.
+    pub duplicate_texts: i64,
+}
+
+/// One heuristic in the screening pipeline. Returns a score when it fires.
+pub trait ContentCheck: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn check(&self, submission: &Submission, history: &SubmissionHistory) -> Option<f64>;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScreeningResult {
+    pub score: f64,
+    pub checks: BTreeMap<String, f64>,
+    pub status: ModerationStatus,
+}
+
+pub struct ScreeningPipeline {
+    checks: Vec<Box<dyn ContentCheck>>,
+    hold_score: f64,
+}
+
+impl ScreeningPipeline {
+    pub fn new(hold_score: f64) -> Self {
+        ScreeningPipeline {
+            checks: Vec::new(),
+            hold_score,
+        }
+    }
+
+    pub fn with_check(mut self, check: impl ContentCheck + 'static) -> Self {
+        self.checks.push(Box::new(check));
+        self
+    }
+
+    /// The pipeline configured from the environment:
+    ///
+    /// - `SPAM_BLOCKLIST_FILE`: newline separated terms, replacing the built-in blocklist
+    /// - `PROFANITY_LEXICON_FILE`: newline separated words, replacing the built-in lexicon
+    /// - `SPAM_HOLD_SCORE`: total score at which content is held
+    pub fn from_env() -> Self {
+        let hold_score = env::var("SPAM_HOLD_SCORE")
+            .ok()
+            .and_then(|score| score.parse().ok())
+            .unwrap_or(DEFAULT_HOLD_SCORE);
+
+        ScreeningPipeline::new(hold_score)
+            .with_check(BlocklistCheck {
+                terms: read_term_list("SPAM_BLOCKLIST_FILE", &DEFAULT_BLOCKLIST),
+            })
+            .with_check(ProfanityCheck {
+                words: read_term_list("PROFANITY_LEXICON_FILE", &DEFAULT_PROFANITY),
+            })
+            .with_check(LinkDensityCheck)
+            .with_check(DuplicateTextCheck)
+            .with_check(BurstCheck)
+            .with_check(NewAccountCheck)
+    }
+
+    pub fn run(&self, submission: &Submission, history: &SubmissionHistory) -> ScreeningResult {
+        let checks: BTreeMap<String, f64> = self
+            .checks
+            .iter()
+            .filter_map(|check| {
+                check
+                    .check(submission, history)
+                    .filter(|score| *score > 0.0)
+                    .map(|score| (check.name().to_string(), score))
+            })
+            .collect();
+        let score = checks.values().fold(0.0, |total, score| total + score);
+
+        ScreeningResult {
+            score,
+            checks,
+            status: if score >= self.hold_score {
+                ModerationStatus::Held
+            } else {
+                ModerationStatus::Published
+            },
+        }
+    }
+}
+
+pub struct ScreeningManager;
+
+impl ScreeningManager {
+    /// Scores a submission before it is stored.
+    pub async fn screen(
+        db: &PgPool,
+        submission: &Submission<'_>,
+    ) -> Result<ScreeningResult, Error> {
+        static PIPELINE: OnceLock<ScreeningPipeline> = OnceLock::new();
+
+        let history = Self::load_history(db, submission).await?;
+
+        Ok(PIPELINE
+            .get_or_init(ScreeningPipeline::from_env)
+            .run(submission, &history))
+    }
+
+    /// Keeps the outcome for moderators and for later burst checks.
+    pub async fn record(
+        db: &PgPool,
+        submission: &Submission<'_>,
+        content_id: i64,
+        result: &ScreeningResult,
+    ) -> Result<(), Error> {
+        let checks = serde_json::to_value(&result.checks)
+            .map_err(|ex| Error::InvalidData(format!("invalid screening checks: {}", ex)))?;
+
+        sqlx::query!(
+            "INSERT INTO content_screenings (content_type, content_id, user_id, score, held, checks) VALUES ($1, $2, $3, $4, $5, $6)",
+            submission.kind.to_string(),
+            content_id,
+            submission.user_id,
+            result.score as f32,
+            result.status == ModerationStatus::Held,
+            checks
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load_history(
+        db: &PgPool,
+        submission: &Submission<'_>,
+    ) -> Result<SubmissionHistory, Error> {
+        let account_created_at = sqlx::query_scalar!(
+            "SELECT created_at FROM users WHERE supabase_id = $1",
+            submission.user_id
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten();
+
+        let recent_submissions = sqlx::query_scalar!(
+            "SELECT COUNT(*) AS \"count!\" FROM content_screenings WHERE user_id = $1 AND ctime > $2",
+            submission.user_id,
+            Utc::now() - Duration::minutes(BURST_WINDOW_MINUTES)
+        )
+        .fetch_one(db)
+        .await?;
+
+        let camps_reviewed = match account_created_at {
+            Some(created_at) if is_new_account(created_at) => {
+                sqlx::query_scalar!(
+                    "SELECT COUNT(DISTINCT camp_id) AS \"count!\" FROM reviews WHERE author_id = $1 AND camp_id <> $2",
+                    submission.user_id,
+                    submission.camp_id.unwrap_or_default()
+                )
+                .fetch_one(db)
+                .await?
+            }
+            _ => 0,
+        };
+
+        let text = normalize_text(&submission.text);
+        let duplicate_texts = if !is_duplicate_candidate(&text) {
+            0
+        } else {
+            match submission.kind {
+                ContentKind::Review => {
+                    sqlx::query_scalar!(
+                        "SELECT COUNT(*) AS \"count!\" FROM reviews
+                        WHERE text_hash = md5($1)
+                            AND NOT (author_id = $2 AND camp_id = $3)",
+                        text,
+                        submission.user_id,
+                        submission.camp_id.unwrap_or_default()
+                    )
+                    .fetch_one(db)
+                    .await?
+                }
+                ContentKind::CampRequest => {
+                    sqlx::query_scalar!(
+                        "SELECT COUNT(*) AS \"count!\" FROM camp_requests
+                        WHERE text_hash = md5($1)",
+                        text
+                    )
+                    .fetch_one(db)
+                    .await?
+                }
+            }
+        };
+
+        Ok(SubmissionHistory {
+            account_created_at,
+            recent_submissions,
+            camps_reviewed,
+            duplicate_texts,
+        })
+    }
+}
+
+/// Any blocklisted term (spam phrases, shortener or known spam domains) holds the content.
+pub struct BlocklistCheck {
+    pub terms: Vec<String>,
+}
+
+impl ContentCheck for BlocklistCheck {
+    fn name(&self) -> &'static str {
+        "blocklist"
+    }
+
+    fn check(&self, submission: &Submission, _history: &SubmissionHistory) -> Option<f64> {
+        let text = submission.text.to_lowercase();
+        let hits = self
+            .terms
+            .iter()
+            .filter(|term| text.contains(term.as_str()))
+            .count();
+
+        (hits > 0).then_some(hits as f64)
+    }
+}
+
+pub struct ProfanityCheck {
+    pub words: Vec<String>,
+}
+
+impl ContentCheck for ProfanityCheck {
+    fn name(&self) -> &'static str {
+        "profanity"
+    }
+
+    fn check(&self, submission: &Submission, _history: &SubmissionHistory) -> Option<f64> {
+        let hits = words(&submission.text)
+            .filter(|word| self.words.iter().any(|profane| profane == word))
+            .count();
+
+        (hits > 0).then(|| (hits as f64 * 0.25).min(1.0))
+    }
+}
+
+/// A link or two is fine; a review that is mostly links is not.
+pub struct LinkDensityCheck;
+
+impl ContentCheck for LinkDensityCheck {
+    fn name(&self) -> &'static str {
+        "link_density"
+    }
+
+    fn check(&self, submission: &Submission, _history: &SubmissionHistory) -> Option<f64> {
+        let text = submission.text.to_lowercase();
+        let links = text.matches("http://").count()
+            + text.matches("https://").count()
+            + text.matches("www.").count();
+        let word_count = words(&text).count().max(1);
+
+        match links {
+            0 | 1 => None,
+            _ if links * 10 >= word_count => Some((0.4 + links as f64 * 0.2).min(1.0)),
+            _ => Some((links as f64 * 0.15).min(1.0)),
+        }
+    }
+}
+
+pub struct DuplicateTextCheck;
+
+impl ContentCheck for DuplicateTextCheck {
+    fn name(&self) -> &'static str {
+        "duplicate_text"
+    }
+
+    fn check(&self, _submission: &Submission, history: &SubmissionHistory) -> Option<f64> {
+        (history.duplicate_texts > 0).then_some(0.7)
+    }
+}
+
+pub struct BurstCheck;
+
+impl ContentCheck for BurstCheck {
+    fn name(&self) -> &'static str {
+        "burst"
+    }
+
+    fn check(&self, _submission: &Submission, history: &SubmissionHistory) -> Option<f64> {
+        let excess = history.recent_submissions - BURST_ALLOWANCE;
+
+        (excess >= 0).then(|| (0.5 + excess as f64 * 0.1).min(1.0))
+    }
+}
+
+/// Brand-new accounts reviewing many camps at once are a common sockpuppet pattern.
+pub struct NewAccountCheck;
+
+impl ContentCheck for NewAccountCheck {
+    fn name(&self) -> &'static str {
+        "new_account"
+    }
+
+    fn check(&self, submission: &Submission, history: &SubmissionHistory) -> Option<f64> {
+        let is_new = history.account_created_at.is_some_and(is_new_account);
+
+        (submission.kind == ContentKind::Review
+            && is_new
+            && history.camps_reviewed >= NEW_ACCOUNT_CAMP_ALLOWANCE)
+            .then_some(0.6)
+    }
+}
+
+fn is_new_account(created_at: DateTime<Utc>) -> bool {
+    Utc::now() - created_at < Duration::hours(NEW_ACCOUNT_HOURS)
+}
+
+fn read_term_list(variable: &str, defaults: &[&str]) -> Vec<String> {
+    let terms: Vec<String> = match env::var(variable).map(fs::read_to_string) {
+        Ok(Ok(contents)) => contents.lines().map(str::to_string).collect(),
+        Ok(Err(ex)) => {
+            println!(
+                "Failed to read {}, using defaults. Cause: {:?}",
+                variable, ex
+            );
+            defaults.iter().map(|term| term.to_string()).collect()
+        }
+        Err(_) => defaults.iter().map(|term| term.to_string()).collect(),
+    };
+
+    terms
+        .into_iter()
+        .map(|term| term.trim().to_lowercase())
+        .filter(|term| !term.is_empty() && !term.starts_with('#'))
+        .collect()
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn is_duplicate_candidate(text: &str) -> bool {
+    text.chars().count() >= MIN_DUPLICATE_TEXT_LEN
+}
+
+/// Mirrors the `text_hash` columns, which hash the same normalization.
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_pipeline() -> ScreeningPipeline {
+        ScreeningPipeline::new(DEFAULT_HOLD_SCORE)
+            .with_check(BlocklistCheck {
+                terms: DEFAULT_BLOCKLIST
+                    .iter()
+                    .map(|term| term.to_string())
+                    .collect(),
+            })
+            .with_check(ProfanityCheck {
+                words: DEFAULT_PROFANITY
+                    .iter()
+                    .map(|word| word.to_string())
+                    .collect(),
+            })
+            .with_check(LinkDensityCheck)
+            .with_check(DuplicateTextCheck)
+            .with_check(BurstCheck)
+            .with_check(NewAccountCheck)
+    }
+
+    fn review(text: &str) -> Submission<'static> {
+        Submission {
+            kind: ContentKind::Review,
+            user_id: "camper",
+            camp_id: Some(1),
+            text: text.to_string(),
+        }
+    }
+
+    fn fired(result: &ScreeningResult) -> Vec<&str> {
+        result.checks.keys().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn publishes_ordinary_reviews() {
+        let result = default_pipeline().run(
+            &review("Great counselors, the lake was clean and the food was fine."),
+            &SubmissionHistory::default(),
+        );
+
+        assert_eq!(result.status, ModerationStatus::Published);
+        assert_eq!(result.score, 0.0);
+        assert!(result.checks.is_empty());
+    }
+
+    #[test]
+    fn holds_blocklisted_terms() {
+        let result = default_pipeline().run(
+            &review("Best CASINO bonus here: bit.ly/free"),
+            &SubmissionHistory::default(),
+        );
+
+        assert_eq!(fired(&result), ["blocklist"]);
+        assert_eq!(result.score, 2.0);
+        assert_eq!(result.status, ModerationStatus::Held);
+    }
+
+    #[test]
+    fn adds_up_scores_until_the_hold_score() {
+        let mild = default_pipeline().run(
+            &review("The food was shit but the canoeing was great."),
+            &SubmissionHistory::default(),
+        );
+        assert_eq!(fired(&mild), ["profanity"]);
+        assert_eq!(mild.score, 0.25);
+        assert_eq!(mild.status, ModerationStatus::Published);
+
+        let duplicated = default_pipeline().run(
+            &review("The food was shit but the canoeing was great."),
+            &SubmissionHistory {
+                duplicate_texts: 3,
+                ..SubmissionHistory::default()
+            },
+        );
+        assert_eq!(fired(&duplicated), ["duplicate_text", "profanity"]);
+        assert_eq!(duplicated.score, 0.95);
+        assert_eq!(duplicated.status, ModerationStatus::Published);
+
+        let bursty = default_pipeline().run(
+            &review("The food was shit but the canoeing was great."),
+            &SubmissionHistory {
+                duplicate_texts: 3,
+                recent_submissions: BURST_ALLOWANCE,
+                ..SubmissionHistory::default()
+            },
+        );
+        assert_eq!(fired(&bursty), ["burst", "duplicate_text", "profanity"]);
+        assert_eq!(bursty.status, ModerationStatus::Held);
+    }
+
+    #[test]
+    fn profanity_matches_whole_words_only() {
+        let result = default_pipeline().run(
+            &review("Dickens readings at Scunthorpe cabin"),
+            &SubmissionHistory::default(),
+        );
+
+        assert!(result.checks.is_empty());
+    }
+
+    #[test]
+    fn link_density_allows_a_single_link() {
+        let one = default_pipeline().run(
+            &review("Photos are at https://pinelake.example/gallery if you want them."),
+            &SubmissionHistory::default(),
+        );
+        assert!(one.checks.is_empty());
+
+        let many = default_pipeline().run(
+            &review("https://a.example https://b.example www.c.example"),
+            &SubmissionHistory::default(),
+        );
+        assert_eq!(fired(&many), ["link_density"]);
+        assert_eq!(many.checks["link_density"], 1.0);
+        assert_eq!(many.status, ModerationStatus::Held);
+    }
+
+    #[test]
+    fn new_accounts_reviewing_many_camps_count_only_for_reviews() {
+        let history = SubmissionHistory {
+            account_created_at: Some(Utc::now() - Duration::hours(1)),
+            camps_reviewed: NEW_ACCOUNT_CAMP_ALLOWANCE,
+            ..SubmissionHistory::default()
+        };
+
+        let result = default_pipeline().run(&review("Lovely camp."), &history);
+        assert_eq!(fired(&result), ["new_account"]);
+
+        let camp_request = Submission {
+            kind: ContentKind::CampRequest,
+            camp_id: None,
+            ..review("Pine Lake Camp")
+        };
+        assert!(default_pipeline()
+            .run(&camp_request, &history)
+            .checks
+            .is_empty());
+
+        let established = SubmissionHistory {
+            account_created_at: Some(Utc::now() - Duration::days(30)),
+            camps_reviewed: NEW_ACCOUNT_CAMP_ALLOWANCE,
+            ..SubmissionHistory::default()
+        };
+        assert!(default_pipeline()
+            .run(&review("Lovely camp."), &established)
+            .checks
+            .is_empty());
+    }
+
+    #[test]
+    fn hold_score_is_configurable() {
+        let strict = ScreeningPipeline::new(0.2).with_check(ProfanityCheck {
+            words: vec!["shit".to_string()],
+        });
+        let result = strict.run(&review("shit food"), &SubmissionHistory::default());
+
+        assert_eq!(result.status, ModerationStatus::Held);
+        assert!(ScreeningPipeline::new(0.2)
+            .run(&review("shit food"), &SubmissionHistory::default())
+            .checks
+            .is_empty());
+    }
+
+    #[test]
+    fn duplicate_text_length_counts_characters() {
+        // 23 characters, but 44 bytes
+        let text = normalize_text("Отличный  лагерь    отлично");
+        assert!(text.len() >= MIN_DUPLICATE_TEXT_LEN);
+        assert!(!is_duplicate_candidate(&text));
+
+        assert!(is_duplicate_candidate(&"great camp ".repeat(3)));
+    }
+}
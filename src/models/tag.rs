@@ -0,0 +1,372 @@
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+
+use super::{validation::ValidationErrors, Error};
+use crate::auth::UserCtx;
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: i64,
+    pub slug: String,
+    pub display_name: String,
+    pub category: String,
+    pub synonyms: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagCategory {
+    Activities,
+    Amenities,
+    Accessibility,
+    Dietary,
+}
+
+impl fmt::Display for TagCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let category = match self {
+            TagCategory::Activities => "activities",
+            TagCategory::Amenities => "amenities",
+            TagCategory::Accessibility => "accessibility",
+            TagCategory::Dietary => "dietary",
+        };
+        write!(f, "{}", category)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagPatch {
+    pub slug: Option<String>,
+    pub display_name: Option<String>,
+    pub category: Option<TagCategory>,
+    /// When present, replaces all of the tag's synonyms.
+    pub synonyms: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct TagFilter {
+    pub category: Option<TagCategory>,
+}
+
+pub struct TagManager;
+
+impl TagManager {
+    pub async fn get_tags(db: &PgPool, filter: TagFilter) -> Result<Vec<Tag>, Error> {
+        let tags = sqlx::query_as!(
+            Tag,
+            r#"SELECT tags.id, tags.slug, tags.display_name, tags.category,
+                array_remove(array_agg(tag_synonyms.synonym ORDER BY tag_synonyms.synonym), NULL) AS "synonyms!"
+            FROM tags LEFT JOIN tag_synonyms ON tag_synonyms.tag_id = tags.id
+            WHERE $1::varchar IS NULL OR tags.category = $1
+            GROUP BY tags.id ORDER BY tags.category, tags.display_name"#,
+            filter.category.map(|category| category.to_string())
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(tags)
+    }
+
+    pub async fn get_tag(db: &PgPool, id: i64) -> Result<Tag, Error> {
+        let tag = sqlx::query_as!(
+            Tag,
+            r#"SELECT tags.id, tags.slug, tags.display_name, tags.category,
+                array_remove(array_agg(tag_synonyms.synonym ORDER BY tag_synonyms.synonym), NULL) AS "synonyms!"
+            FROM tags LEFT JOIN tag_synonyms ON tag_synonyms.tag_id = tags.id
+            WHERE tags.id = $1
+            GROUP BY tags.id"#,
+            id
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(tag)
+    }
+
+    pub async fn create_tag(db: &PgPool, _utx: UserCtx, data: TagPatch) -> Result<Tag, Error> {
+        let display_name = data
+            .display_name
+            .filter(|display_name| !display_name.trim().is_empty())
+            .ok_or_else(|| Error::InvalidData("display_name is required".to_string()))?;
+        let category = data
+            .category
+            .ok_or_else(|| Error::InvalidData("category is required".to_string()))?;
+        let slug = match data.slug.as_deref() {
+            Some(slug) => slug_from("$.slug", slug)?,
+            None => slug_from("$.display_name", &display_name)?,
+        };
+
+        let mut tx = db.begin().await?;
+
+        let id = sqlx::query_scalar!(
+            "INSERT INTO tags (slug, display_name, category) VALUES ($1, $2, $3) RETURNING id",
+            slug,
+            display_name.trim(),
+            category.to_string()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        replace_synonyms(&mut tx, id, data.synonyms.unwrap_or_default()).await?;
+
+        tx.commit().await?;
+
+        Self::get_tag(db, id).await
+    }
+
+    /// Updates a tag, renaming its slug on every camp and camp request that uses it.
+    pub async fn update_tag(
+        db: &PgPool,
+        id: i64,
+        data: TagPatch,
+        _utx: UserCtx,
+    ) -> Result<Tag, Error> {
+        let original_tag = Self::get_tag(db, id).await?;
+        let slug = match data.slug.as_deref() {
+            Some(slug) => slug_from("$.slug", slug)?,
+            None => original_tag.slug.clone(),
+        };
+
+        let mut tx = db.begin().await?;
+
+        sqlx::query!(
+            "UPDATE tags SET slug = $1, display_name = $2, category = $3 WHERE id = $4",
+            slug,
+            data.display_name.unwrap_or(original_tag.display_name),
+            data.category
+                .map(|category| category.to_string())
+                .unwrap_or(original_tag.category),
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if slug != original_tag.slug {
+            sqlx::query!(
+                "UPDATE camps SET tags = array_replace(tags, $1, $2) WHERE $1 = ANY(tags)",
+                original_tag.slug,
+                slug
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "UPDATE camp_requests SET tags = array_replace(tags, $1, $2) WHERE $1 = ANY(tags)",
+                original_tag.slug,
+                slug
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if let Some(synonyms) = data.synonyms {
+            replace_synonyms(&mut tx, id, synonyms).await?;
+        }
+
+        tx.commit().await?;
+
+        Self::get_tag(db, id).await
+    }
+
+    /// Deletes a tag and removes it from every camp and camp request.
+    pub async fn delete_tag(db: &PgPool, id: i64, _utx: UserCtx) -> Result<Tag, Error> {
+        let tag = Self::get_tag(db, id).await?;
+
+        let mut tx = db.begin().await?;
+
+        sqlx::query!(
+            "UPDATE camps SET tags = array_remove(tags, $1) WHERE $1 = ANY(tags)",
+            tag.slug
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE camp_requests SET tags = array_remove(tags, $1) WHERE $1 = ANY(tags)",
+            tag.slug
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!("DELETE FROM tags WHERE id = $1", id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(tag)
+    }
+
+    /// Maps free-form tags to canonical slugs through slugs, display names and synonyms.
+    pub async fn normalize_tags(db: &PgPool, tags: Vec<String>) -> Result<Vec<String>, Error> {
+        if tags.is_empty() {
+            return Ok(tags);
+        }
+
+        let mut canonical: HashMap<String, String> = HashMap::new();
+        for tag in sqlx::query!("SELECT slug, display_name FROM tags")
+            .fetch_all(db)
+            .await?
+        {
+            canonical.insert(normalize_key(&tag.display_name), tag.slug.clone());
+            canonical.insert(tag.slug.clone(), tag.slug);
+        }
+        for synonym in sqlx::query!(
+            "SELECT tag_synonyms.synonym, tags.slug FROM tag_synonyms JOIN tags ON tags.id = tag_synonyms.tag_id"
+        )
+        .fetch_all(db)
+        .await?
+        {
+            canonical.insert(synonym.synonym, synonym.slug);
+        }
+
+        let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+        let mut unknown = Vec::new();
+        for tag in tags {
+            let key = normalize_key(&tag);
+            if key.is_empty() {
+                continue;
+            }
+            match canonical
+                .get(&key)
+                .or_else(|| canonical.get(&slugify(&key)))
+            {
+                Some(slug) if !normalized.contains(slug) => normalized.push(slug.clone()),
+                Some(_) => (),
+                None => unknown.push(tag),
+            }
+        }
+
+        if !unknown.is_empty() {
+            return Err(Error::UnknownTags(unknown));
+        }
+
+        Ok(normalized)
+    }
+}
+
+/// Sets the tag's synonyms. A synonym of another tag is refused rather than
+/// moved, so one tag cannot silently take over another's synonyms.
+async fn replace_synonyms(
+    tx: &mut Transaction<'_, Postgres>,
+    tag_id: i64,
+    synonyms: Vec<String>,
+) -> Result<(), Error> {
+    sqlx::query!("DELETE FROM tag_synonyms WHERE tag_id = $1", tag_id)
+        .execute(&mut **tx)
+        .await?;
+
+    // Each normalized synonym with the index it was given at, for error paths
+    let mut paths: HashMap<String, String> = HashMap::new();
+    for (index, synonym) in synonyms.iter().enumerate() {
+        let key = normalize_key(synonym);
+        if !key.is_empty() {
+            paths
+                .entry(key)
+                .or_insert_with(|| format!("$.synonyms[{}]", index));
+        }
+    }
+    let mut synonyms: Vec<String> = paths.keys().cloned().collect();
+    synonyms.sort();
+
+    let taken = sqlx::query!(
+        "SELECT tag_synonyms.synonym, tags.slug FROM tag_synonyms JOIN tags ON tags.id = tag_synonyms.tag_id
+        WHERE tag_synonyms.synonym = ANY($1) ORDER BY tag_synonyms.synonym",
+        &synonyms
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    if !taken.is_empty() {
+        let mut errors = ValidationErrors::default();
+        for synonym in taken {
+            errors.add(
+                paths[&synonym.synonym].clone(),
+                format!("is already a synonym of {}", synonym.slug),
+            );
+        }
+        return Err(Error::Validation(errors));
+    }
+
+    sqlx::query!(
+        "INSERT INTO tag_synonyms (synonym, tag_id) SELECT synonym, $2 FROM unnest($1::varchar[]) AS synonym",
+        &synonyms,
+        tag_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|ex| match &ex {
+        // Another tag claimed one of the synonyms since the check above
+        sqlx::Error::Database(db_ex) if db_ex.is_unique_violation() => {
+            let mut errors = ValidationErrors::default();
+            errors.add("$.synonyms", "contains a synonym of another tag");
+            Error::Validation(errors)
+        }
+        _ => ex.into(),
+    })?;
+
+    Ok(())
+}
+
+fn normalize_key(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// The slug for `text`, refusing text without any Latin letter or digit, e.g.
+/// only punctuation or another script, since its slug would be empty.
+fn slug_from(path: &str, text: &str) -> Result<String, Error> {
+    let slug = slugify(text);
+    if slug.is_empty() {
+        let mut errors = ValidationErrors::default();
+        errors.add(path, "must contain a Latin letter or digit");
+        return Err(Error::Validation(errors));
+    }
+
+    Ok(slug)
+}
+
+fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .split(|ch: char| !ch.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs_join_latin_words_with_dashes() {
+        assert_eq!(slugify("Arts & Crafts"), "arts-crafts");
+        assert_eq!(slugify("  Horseback   Riding "), "horseback-riding");
+        assert_eq!(slugify("A/C"), "a-c");
+        assert_eq!(slugify("STEM 101"), "stem-101");
+    }
+
+    #[test]
+    fn text_without_latin_letters_or_digits_has_no_slug() {
+        assert_eq!(slugify("!!! ---"), "");
+        assert_eq!(slugify("水泳"), "");
+
+        match slug_from("$.display_name", "水泳") {
+            Err(Error::Validation(errors)) => {
+                assert_eq!(errors.errors.len(), 1);
+                assert_eq!(errors.errors[0].path, "$.display_name");
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        assert_eq!(
+            slug_from("$.slug", "Café Nights").expect("a slug"),
+            "caf-nights"
+        );
+    }
+
+    #[test]
+    fn keys_collapse_whitespace_and_case() {
+        assert_eq!(normalize_key("  Swimming   Lessons "), "swimming lessons");
+        assert_eq!(normalize_key(" \t "), "");
+    }
+}
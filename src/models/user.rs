@@ -1,33 +1,233 @@
+use chrono::{
+    serde::{ts_seconds, ts_seconds_option},
+    DateTime, Duration, Utc,
+};
+
+use super::image::{avatar_max_bytes, ImageManager};
+use super::validation::{Validate, ValidationErrors, MAX_VARCHAR_LEN};
 use super::Error;
 use super::Review;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::auth::{self, UserCtx};
+use crate::jobs::{self, RemoveImage};
 
-use crate::auth::UserCtx;
+/// Names that would shadow a `/users/...` route or could pass for staff.
+const RESERVED_USERNAMES: [&str; 12] = [
+    "admin",
+    "administrator",
+    "export",
+    "favorite",
+    "me",
+    "moderator",
+    "notifications",
+    "profile",
+    "reviews",
+    "staff",
+    "support",
+    "username",
+];
+/// How long a reservation holds a username for the user who made it.
+const USERNAME_RESERVATION_MINUTES: i64 = 15;
+/// A username that was changed away from stays with its previous owner this long.
+const USERNAME_RELEASE_DAYS: i64 = 30;
+const MAX_BIO_LEN: usize = 1000;
+/// The placeholder author that content of deleted accounts is reattributed to.
+pub const FORMER_MEMBER_ID: &str = "former-member";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
+    /// Always taken from the authenticated caller; a value in a request body is ignored.
+    #[serde(default)]
     pub supabase_id: String,
     pub first_name: String,
     pub last_name: String,
     pub email: String,
     pub username: Option<String>,
+    #[serde(default, skip_deserializing, with = "ts_seconds_option")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_deserializing)]
+    pub avatar_url: Option<String>,
+    #[serde(default, skip_deserializing)]
+    pub bio: String,
+    #[serde(default, skip_deserializing)]
+    pub home_location: String,
+    /// The stored `NotificationPreferences`, holding only the keys the user changed.
+    #[serde(default, skip_deserializing)]
+    pub notification_preferences: serde_json::Value,
+}
+
+/// Which notifications a user wants. Unset preferences are on.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NotificationPreferences {
+    /// Send notifications by email as well as in the app.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<bool>,
+    /// Moderation decisions and helpful votes on the user's reviews.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review_activity: Option<bool>,
+    /// Changes to camps the user marked as favorite.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favorite_camp_updates: Option<bool>,
+    /// Progress of the user's camp requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camp_request_updates: Option<bool>,
+}
+
+/// Profile fields a user can change; absent fields are left as they are.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UserPatch {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub username: Option<String>,
+    pub bio: Option<String>,
+    pub home_location: Option<String>,
+    /// Merged into the stored preferences, so only changed keys need to be sent.
+    pub notification_preferences: Option<NotificationPreferences>,
+}
+
+impl Validate for UserPatch {
+    fn validate(&mut self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+
+        let trim = |value: &mut Option<String>| {
+            if let Some(value) = value.as_mut() {
+                *value = value.trim().to_string();
+            }
+        };
+        trim(&mut self.first_name);
+        trim(&mut self.last_name);
+        trim(&mut self.username);
+        trim(&mut self.bio);
+        trim(&mut self.home_location);
+
+        if let Some(first_name) = &self.first_name {
+            errors.check_length("$.first_name", first_name, MAX_VARCHAR_LEN);
+        }
+        if let Some(last_name) = &self.last_name {
+            errors.check_length("$.last_name", last_name, MAX_VARCHAR_LEN);
+        }
+        if let Some(username) = &self.username {
+            check_username_choice(&mut errors, "$.username", username);
+        }
+        if let Some(bio) = &self.bio {
+            errors.check_length("$.bio", bio, MAX_BIO_LEN);
+        }
+        if let Some(home_location) = &self.home_location {
+            errors.check_length("$.home_location", home_location, MAX_VARCHAR_LEN);
+        }
+
+        errors.into_result()
+    }
+}
+
+impl Validate for User {
+    fn validate(&mut self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+
+        self.first_name = self.first_name.trim().to_string();
+        self.last_name = self.last_name.trim().to_string();
+        self.email = self.email.trim().to_string();
+
+        errors.check_length("$.first_name", &self.first_name, MAX_VARCHAR_LEN);
+        errors.check_length("$.last_name", &self.last_name, MAX_VARCHAR_LEN);
+        errors.check_email("$.email", &self.email);
+        if let Some(username) = self.username.as_mut() {
+            *username = username.trim().to_string();
+            if !username.is_empty() {
+                check_username_choice(&mut errors, "$.username", username);
+            }
+        }
+
+        errors.into_result()
+    }
+}
+
+/// Body of the username reservation and change requests.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsernameRequest {
+    pub username: String,
+}
+
+impl Validate for UsernameRequest {
+    fn validate(&mut self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+
+        self.username = self.username.trim().to_string();
+        check_username_choice(&mut errors, "$.username", &self.username);
+
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsernameReservation {
+    pub username: String,
+    #[serde(with = "ts_seconds")]
+    pub expires_at: DateTime<Utc>,
+}
+
+fn check_username_choice(errors: &mut ValidationErrors, path: &str, username: &str) {
+    errors.check_username(path, username);
+    if RESERVED_USERNAMES.contains(&username.to_lowercase().as_str()) {
+        errors.add(path, "is reserved");
+    }
+}
+
+fn username_taken() -> Error {
+    let mut errors = ValidationErrors::default();
+    errors.add("$.username", "is already taken");
+
+    Error::Validation(errors)
+}
+
+/// Maps a race on the case-insensitive username index to the same error as the explicit check.
+fn map_username_conflict(ex: sqlx::Error) -> Error {
+    match &ex {
+        sqlx::Error::Database(db_ex) if db_ex.is_unique_violation() => username_taken(),
+        _ => ex.into(),
+    }
 }
 
 pub struct UserManager;
 
 impl UserManager {
-    pub async fn new_user(db: &PgPool, data: User, _utx: UserCtx) -> Result<User, Error> {
-        let user = sqlx::query_as!(
-            User,
-            "INSERT INTO users (supabase_id, first_name, last_name, email, username) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+    /// Completes the caller's profile. Authentication already created their row,
+    /// so this fills it in; an email from the identity provider is kept.
+    pub async fn new_user(db: &PgPool, mut data: User, utx: UserCtx) -> Result<User, Error> {
+        data.supabase_id = utx.user_id;
+        data.validate()?;
+
+        let mut tx = db.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO users (supabase_id, first_name, last_name, email) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (supabase_id) DO UPDATE SET
+                first_name = excluded.first_name,
+                last_name = excluded.last_name,
+                email = CASE WHEN users.email = '' THEN excluded.email ELSE users.email END",
             data.supabase_id,
             data.first_name,
             data.last_name,
-            data.email,
-            data.username.unwrap_or_default()
+            data.email
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(username) = data.username.filter(|username| !username.is_empty()) {
+            Self::rename(&mut tx, &data.supabase_id, &username).await?;
+        }
+
+        let user = sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE supabase_id = $1",
+            data.supabase_id
         )
-      .fetch_one(db).await?;
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
 
         Ok(user)
     }
@@ -44,14 +244,313 @@ impl UserManager {
         Ok(user)
     }
 
+    /// Holds a username for the caller for a few minutes, e.g. while a signup or
+    /// rename form is being filled in.
+    pub async fn reserve_username(
+        db: &PgPool,
+        utx: UserCtx,
+        mut data: UsernameRequest,
+    ) -> Result<UsernameReservation, Error> {
+        data.validate()?;
+
+        let mut tx = db.begin().await?;
+
+        Self::ensure_username_available(&mut tx, &utx.user_id, &data.username).await?;
+
+        // One pending reservation per user; names kept after a rename don't count
+        sqlx::query!(
+            "DELETE FROM username_reservations WHERE user_id = $1 AND expires_at <= now() + make_interval(mins => $2)",
+            utx.user_id,
+            USERNAME_RESERVATION_MINUTES as i32
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let expires_at = Utc::now() + Duration::minutes(USERNAME_RESERVATION_MINUTES);
+        sqlx::query!(
+            "INSERT INTO username_reservations (username, user_id, expires_at) VALUES (lower($1), $2, $3)
+             ON CONFLICT (username) DO UPDATE SET user_id = excluded.user_id, expires_at = excluded.expires_at",
+            data.username,
+            utx.user_id,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(UsernameReservation {
+            username: data.username,
+            expires_at,
+        })
+    }
+
+    /// Renames the caller. The new name must be free or reserved by the caller, and
+    /// the old one stays reserved for them so nobody can take it over right away.
+    pub async fn change_username(
+        db: &PgPool,
+        utx: UserCtx,
+        mut data: UsernameRequest,
+    ) -> Result<User, Error> {
+        data.validate()?;
+
+        let mut tx = db.begin().await?;
+
+        Self::rename(&mut tx, &utx.user_id, &data.username).await?;
+        let user = sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE supabase_id = $1",
+            utx.user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    /// Updates the caller's profile. A new username goes through the same checks
+    /// and reservations as `change_username`.
+    pub async fn update_user(
+        db: &PgPool,
+        utx: UserCtx,
+        mut data: UserPatch,
+    ) -> Result<User, Error> {
+        data.validate()?;
+
+        let mut tx = db.begin().await?;
+
+        if let Some(username) = &data.username {
+            Self::rename(&mut tx, &utx.user_id, username).await?;
+        }
+
+        let preferences = data
+            .notification_preferences
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|ex| Error::InvalidData(ex.to_string()))?;
+
+        let user = sqlx::query_as!(
+            User,
+            "UPDATE users SET
+                first_name = COALESCE($2, first_name),
+                last_name = COALESCE($3, last_name),
+                bio = COALESCE($4, bio),
+                home_location = COALESCE($5, home_location),
+                notification_preferences = notification_preferences || COALESCE($6, '{}'::jsonb)
+            WHERE supabase_id = $1 RETURNING *",
+            utx.user_id,
+            data.first_name,
+            data.last_name,
+            data.bio,
+            data.home_location,
+            preferences
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    /// Stores an uploaded avatar and points the caller's profile at it.
+    pub async fn set_avatar(db: &PgPool, utx: UserCtx, image: &[u8]) -> Result<User, Error> {
+        let avatar_url = ImageManager::store(image, avatar_max_bytes()).await?;
+
+        Self::replace_avatar(db, &utx.user_id, Some(avatar_url)).await
+    }
+
+    pub async fn remove_avatar(db: &PgPool, utx: UserCtx) -> Result<User, Error> {
+        Self::replace_avatar(db, &utx.user_id, None).await
+    }
+
+    /// Points the profile at `avatar_url` and queues the previous avatar's
+    /// file for removal once the change is committed.
+    async fn replace_avatar(
+        db: &PgPool,
+        user_id: &str,
+        avatar_url: Option<String>,
+    ) -> Result<User, Error> {
+        let mut tx = db.begin().await?;
+
+        let previous = sqlx::query_scalar!(
+            "SELECT avatar_url FROM users WHERE supabase_id = $1 FOR UPDATE",
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let user = sqlx::query_as!(
+            User,
+            "UPDATE users SET avatar_url = $2 WHERE supabase_id = $1 RETURNING *",
+            user_id,
+            avatar_url
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if let Some(previous) = previous.filter(|previous| Some(previous) != avatar_url.as_ref()) {
+            jobs::enqueue(&mut tx, &RemoveImage { url: previous }, Utc::now()).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    /// Moves `user_id` to `username`, using up their reservation of it and keeping
+    /// their previous username reserved for them.
+    async fn rename(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        username: &str,
+    ) -> Result<(), Error> {
+        Self::ensure_username_available(tx, user_id, username).await?;
+
+        let previous = sqlx::query_scalar!(
+            "SELECT username FROM users WHERE supabase_id = $1 FOR UPDATE",
+            user_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE users SET username = $2 WHERE supabase_id = $1",
+            user_id,
+            username
+        )
+        .execute(&mut **tx)
+        .await
+        .map_err(map_username_conflict)?;
+
+        sqlx::query!(
+            "DELETE FROM username_reservations WHERE username = lower($1) AND user_id = $2",
+            username,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        if !previous.is_empty() && !previous.eq_ignore_ascii_case(username) {
+            sqlx::query!(
+                "INSERT INTO username_reservations (username, user_id, expires_at) VALUES (lower($1), $2, $3)
+                 ON CONFLICT (username) DO UPDATE SET user_id = excluded.user_id, expires_at = excluded.expires_at",
+                previous,
+                user_id,
+                Utc::now() + Duration::days(USERNAME_RELEASE_DAYS)
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Fails unless `username` is neither used by nor reserved for another user.
+    async fn ensure_username_available(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        username: &str,
+    ) -> Result<(), Error> {
+        let taken = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE lower(username) = lower($1) AND supabase_id <> $2)
+                 OR EXISTS(SELECT 1 FROM username_reservations WHERE username = lower($1) AND user_id <> $2 AND expires_at > now()) AS \"taken!\"",
+            username,
+            user_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        if taken {
+            return Err(username_taken());
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the caller's account in one transaction. Reviews, camp requests and
+    /// the moderation and edit history stay, reattributed to the former member
+    /// placeholder; favorites, votes and reservations are removed.
     pub async fn delete_user(db: &PgPool, utx: UserCtx) -> Result<(), Error> {
+        if utx.user_id == FORMER_MEMBER_ID {
+            return Err(Error::InvalidData(
+                "The former member placeholder cannot be deleted".to_string(),
+            ));
+        }
+
+        let mut tx = db.begin().await?;
+
+        let avatar_url = sqlx::query_scalar!(
+            "SELECT avatar_url FROM users WHERE supabase_id = $1 FOR UPDATE",
+            utx.user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE reviews SET author_id = $2 WHERE author_id = $1",
+            utx.user_id,
+            FORMER_MEMBER_ID
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE camp_requests SET user_id = $2 WHERE user_id = $1",
+            utx.user_id,
+            FORMER_MEMBER_ID
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE camp_revisions SET user_id = $2 WHERE user_id = $1",
+            utx.user_id,
+            FORMER_MEMBER_ID
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE content_screenings SET user_id = $2 WHERE user_id = $1",
+            utx.user_id,
+            FORMER_MEMBER_ID
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Queued and sent emails hold the address and what was written to it
+        sqlx::query!("DELETE FROM email_outbox WHERE user_id = $1", utx.user_id)
+            .execute(&mut *tx)
+            .await?;
+        // Favorites are the only camp list users keep; there are no other
+        // private lists to remove
+        sqlx::query!("DELETE FROM users_camps WHERE user_id = $1", utx.user_id)
+            .execute(&mut *tx)
+            .await?;
+        // Admin rights, helpful votes and username reservations cascade
         sqlx::query!("DELETE FROM users WHERE supabase_id = $1", utx.user_id)
-            .execute(db)
+            .execute(&mut *tx)
             .await?;
 
+        if let Some(avatar_url) = avatar_url {
+            jobs::enqueue(&mut tx, &RemoveImage { url: avatar_url }, Utc::now()).await?;
+        }
+
+        tx.commit().await?;
+        auth::forget_user(&utx.user_id);
+
         Ok(())
     }
 
+    /// Drops reservations and held usernames whose time is up.
+    pub async fn purge_expired_reservations(db: &PgPool) -> Result<u64, Error> {
+        let result = sqlx::query!("DELETE FROM username_reservations WHERE expires_at <= now()")
+            .execute(db)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn get_user_reviews(db: &PgPool, utx: UserCtx) -> Result<Vec<Review>, Error> {
         let reviews = sqlx::query_as!(
             Review,
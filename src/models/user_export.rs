@@ -0,0 +1,140 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, PgPool};
+
+use super::{
+    camp_request::CampRequest,
+    camp_revision::CampRevision,
+    notification::{InboxNotification, Notification},
+    user::{User, UsernameReservation},
+    Error, Review,
+};
+use crate::auth::UserCtx;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedFavorite {
+    pub camp_id: i64,
+    pub camp_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedHelpfulVote {
+    pub review_id: i64,
+    #[serde(with = "ts_seconds")]
+    pub ctime: DateTime<Utc>,
+}
+
+/// The outcome of screening one of the user's submissions for spam.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedScreening {
+    pub content_type: String,
+    pub content_id: i64,
+    #[serde(with = "ts_seconds")]
+    pub ctime: DateTime<Utc>,
+    pub score: f32,
+    pub held: bool,
+    pub checks: serde_json::Value,
+}
+
+/// Everything stored about one user, for data portability requests.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserExport {
+    #[serde(with = "ts_seconds")]
+    pub exported_at: DateTime<Utc>,
+    pub user: User,
+    pub is_admin: bool,
+    pub reviews: Vec<Review>,
+    pub helpful_votes: Vec<ExportedHelpfulVote>,
+    pub favorites: Vec<ExportedFavorite>,
+    pub camp_requests: Vec<CampRequest>,
+    pub camp_revisions: Vec<CampRevision>,
+    pub content_screenings: Vec<ExportedScreening>,
+    pub username_reservations: Vec<UsernameReservation>,
+    pub notifications: Vec<InboxNotification>,
+}
+
+pub struct UserExportManager;
+
+impl UserExportManager {
+    pub async fn export(db: &PgPool, utx: UserCtx) -> Result<UserExport, Error> {
+        let user_id = utx.user_id.as_str();
+
+        let (
+            user,
+            reviews,
+            helpful_votes,
+            favorites,
+            camp_requests,
+            camp_revisions,
+            content_screenings,
+            username_reservations,
+            notifications,
+        ) = tokio::try_join!(
+            sqlx::query_as!(User, "SELECT * FROM users WHERE supabase_id = $1", user_id)
+                .fetch_one(db),
+            sqlx::query_as!(
+                Review,
+                "SELECT * FROM reviews WHERE author_id = $1 ORDER BY ctime, id",
+                user_id
+            )
+            .fetch_all(db),
+            sqlx::query_as!(
+                ExportedHelpfulVote,
+                "SELECT review_id, ctime FROM review_helpful_votes WHERE user_id = $1 ORDER BY ctime",
+                user_id
+            )
+            .fetch_all(db),
+            sqlx::query_as!(
+                ExportedFavorite,
+                "SELECT camps.id AS camp_id, camps.name AS camp_name FROM users_camps JOIN camps ON camps.id = users_camps.camp_id WHERE user_id = $1 ORDER BY camps.id",
+                user_id
+            )
+            .fetch_all(db),
+            sqlx::query_as!(
+                CampRequest,
+                "SELECT * FROM camp_requests WHERE user_id = $1 ORDER BY id",
+                user_id
+            )
+            .fetch_all(db),
+            sqlx::query_as!(
+                CampRevision,
+                "SELECT * FROM camp_revisions WHERE user_id = $1 ORDER BY ctime, id",
+                user_id
+            )
+            .fetch_all(db),
+            sqlx::query_as!(
+                ExportedScreening,
+                "SELECT content_type, content_id, ctime, score, held, checks FROM content_screenings WHERE user_id = $1 ORDER BY ctime, id",
+                user_id
+            )
+            .fetch_all(db),
+            sqlx::query_as!(
+                UsernameReservation,
+                "SELECT username, expires_at FROM username_reservations WHERE user_id = $1 ORDER BY expires_at",
+                user_id
+            )
+            .fetch_all(db),
+            sqlx::query_as!(
+                InboxNotification,
+                r#"SELECT id, kind, payload as "payload: Json<Notification>", link, ctime, read_at
+                FROM notifications WHERE user_id = $1 ORDER BY id"#,
+                user_id
+            )
+            .fetch_all(db),
+        )?;
+
+        Ok(UserExport {
+            exported_at: Utc::now(),
+            user,
+            is_admin: utx.is_admin,
+            reviews,
+            helpful_votes,
+            favorites,
+            camp_requests,
+            camp_revisions,
+            content_screenings,
+            username_reservations,
+            notifications,
+        })
+    }
+}
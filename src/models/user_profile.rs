@@ -0,0 +1,112 @@
+use chrono::{serde::ts_seconds_option, DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use super::Error;
+
+/// Achievements shown on a public profile, derived from the user's activity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Badge {
+    /// Published a first review.
+    FirstReview,
+    /// Published at least 10 reviews.
+    ProlificReviewer,
+    /// Reviews were voted helpful at least 25 times.
+    Helpful,
+    /// Shared at least 5 photos with reviews.
+    Photographer,
+    /// Member for over a year.
+    Veteran,
+}
+
+impl Badge {
+    fn earned(stats: &ProfileStats) -> Vec<Badge> {
+        let veteran = stats
+            .joined_at
+            .is_some_and(|joined_at| Utc::now() - joined_at >= Duration::days(365));
+
+        [
+            (Badge::FirstReview, stats.review_count >= 1),
+            (Badge::ProlificReviewer, stats.review_count >= 10),
+            (Badge::Helpful, stats.helpful_votes >= 25),
+            (Badge::Photographer, stats.photo_count >= 5),
+            (Badge::Veteran, veteran),
+        ]
+        .into_iter()
+        .filter_map(|(badge, earned)| earned.then_some(badge))
+        .collect()
+    }
+}
+
+struct ProfileStats {
+    username: String,
+    first_name: String,
+    last_name: String,
+    avatar_url: Option<String>,
+    joined_at: Option<DateTime<Utc>>,
+    review_count: i64,
+    helpful_votes: i64,
+    photo_count: i64,
+}
+
+/// What anyone can see about a user. Never carries the email or the account id.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicProfile {
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    #[serde(with = "ts_seconds_option")]
+    pub joined_at: Option<DateTime<Utc>>,
+    pub review_count: i64,
+    pub helpful_votes: i64,
+    pub badges: Vec<Badge>,
+}
+
+impl From<ProfileStats> for PublicProfile {
+    fn from(stats: ProfileStats) -> Self {
+        let badges = Badge::earned(&stats);
+
+        // First name and last initial, falling back to the username
+        let display_name = match stats.last_name.chars().next() {
+            Some(initial) if !stats.first_name.is_empty() => {
+                format!("{} {}.", stats.first_name, initial)
+            }
+            _ if !stats.first_name.is_empty() => stats.first_name,
+            _ => stats.username.clone(),
+        };
+
+        PublicProfile {
+            username: stats.username,
+            display_name,
+            avatar_url: stats.avatar_url,
+            joined_at: stats.joined_at,
+            review_count: stats.review_count,
+            helpful_votes: stats.helpful_votes,
+            badges,
+        }
+    }
+}
+
+pub struct UserProfileManager;
+
+impl UserProfileManager {
+    /// Looks a user up by username, ignoring case. Only published reviews count.
+    pub async fn get_public_profile(db: &PgPool, username: &str) -> Result<PublicProfile, Error> {
+        let stats = sqlx::query_as!(
+            ProfileStats,
+            r#"SELECT u.username, u.first_name, u.last_name, u.avatar_url, u.created_at AS joined_at,
+                (SELECT COUNT(*) FROM reviews r WHERE r.author_id = u.supabase_id AND r.moderation_status = 'published') AS "review_count!",
+                (SELECT COUNT(*) FROM review_helpful_votes v JOIN reviews r ON r.id = v.review_id
+                    WHERE r.author_id = u.supabase_id AND r.moderation_status = 'published') AS "helpful_votes!",
+                (SELECT COALESCE(SUM(cardinality(r.photo_urls)), 0) FROM reviews r
+                    WHERE r.author_id = u.supabase_id AND r.moderation_status = 'published') AS "photo_count!"
+            FROM users u WHERE lower(u.username) = lower($1) AND u.username <> ''"#,
+            username
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(stats.into())
+    }
+}
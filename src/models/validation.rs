@@ -0,0 +1,389 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+/// Longest value a `varchar(255)` column accepts.
+pub const MAX_VARCHAR_LEN: usize = 255;
+
+/// Payloads that are checked (and normalized in place) before they reach the database.
+pub trait Validate {
+    fn validate(&mut self) -> Result<(), ValidationErrors>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldError {
+    /// JSON path of the offending field, e.g. `$.sessions[0].end_date`.
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(ThisError, Debug, Serialize, Deserialize, Clone, Default)]
+#[error("{} invalid field(s)", errors.len())]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn add(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn check_length(&mut self, path: &str, value: &str, max: usize) {
+        if value.chars().count() > max {
+            self.add(path, format!("must be at most {} characters", max));
+        }
+    }
+
+    pub fn check_range<T: PartialOrd + std::fmt::Display>(
+        &mut self,
+        path: &str,
+        value: T,
+        min: T,
+        max: T,
+    ) {
+        if value < min || value > max {
+            self.add(path, format!("must be between {} and {}", min, max));
+        }
+    }
+
+    pub fn check_non_negative<T: PartialOrd + Default>(&mut self, path: &str, value: Option<T>) {
+        if value.is_some_and(|value| value < T::default()) {
+            self.add(path, "must not be negative");
+        }
+    }
+
+    pub fn check_email(&mut self, path: &str, email: &str) {
+        if !email.is_empty() && !is_valid_email(email) {
+            self.add(path, "must be a valid email address");
+        }
+    }
+
+    pub fn check_username(&mut self, path: &str, username: &str) {
+        if !is_valid_username(username) {
+            self.add(
+                path,
+                "must be 3 to 30 letters, digits, '.', '_' or '-', starting with a letter or digit",
+            );
+        }
+    }
+
+    pub fn check_url(&mut self, path: &str, value: &str) {
+        if !value.is_empty() && !is_valid_url(value) {
+            self.add(path, "must be an absolute http or https URL");
+        }
+    }
+}
+
+pub fn is_valid_email(email: &str) -> bool {
+    static EMAIL: OnceLock<Regex> = OnceLock::new();
+    let email_regex = EMAIL.get_or_init(|| {
+        Regex::new(r"^[A-Za-z0-9.!#$%&'*+/=?^_`{|}~-]+@[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?(?:\.[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?)+$")
+            .expect("valid email regex")
+    });
+
+    email.len() <= MAX_VARCHAR_LEN && email_regex.is_match(email)
+}
+
+pub fn is_valid_username(username: &str) -> bool {
+    static USERNAME: OnceLock<Regex> = OnceLock::new();
+    let username_regex = USERNAME.get_or_init(|| {
+        Regex::new(r"^[A-Za-z0-9][A-Za-z0-9._-]{2,29}$").expect("valid username regex")
+    });
+
+    username_regex.is_match(username)
+}
+
+pub fn is_valid_url(value: &str) -> bool {
+    match url::Url::parse(value) {
+        Ok(url) => matches!(url.scheme(), "http" | "https") && url.host_str().is_some(),
+        Err(_) => false,
+    }
+}
+
+/// Normalizes a phone number to E.164, using the camp's country for national numbers.
+pub fn normalize_phone(phone: &str, country: Option<&str>) -> Result<String, String> {
+    let trimmed = phone.trim();
+    let digits: String = trimmed.chars().filter(char::is_ascii_digit).collect();
+    if trimmed
+        .chars()
+        .any(|ch| !(ch.is_ascii_digit() || " +-().".contains(ch)))
+    {
+        return Err("may only contain digits, spaces and + - ( ) .".to_string());
+    }
+
+    let international = if trimmed.starts_with('+') {
+        digits
+    } else if let Some(rest) = digits.strip_prefix("00") {
+        rest.to_string()
+    } else {
+        let country = country
+            .filter(|country| !country.is_empty())
+            .ok_or("needs a leading +country code when no country is set")?;
+        let calling_code =
+            calling_code(country).ok_or("needs a leading +country code for this country")?;
+
+        if calling_code == "1" && digits.len() == 11 && digits.starts_with('1') {
+            digits
+        } else {
+            format!("{}{}", calling_code, digits.trim_start_matches('0'))
+        }
+    };
+
+    if !(8..=15).contains(&international.len()) || international.starts_with('0') {
+        return Err("is not a valid E.164 phone number".to_string());
+    }
+    // North American numbers are always a 3 digit area code and 7 digit number
+    if international.starts_with('1') && international.len() != 11 {
+        return Err("must have a 3 digit area code and 7 digit number".to_string());
+    }
+
+    Ok(format!("+{}", international))
+}
+
+pub fn is_valid_currency(currency: &str) -> bool {
+    currency.len() == 3 && currency.chars().all(|ch| ch.is_ascii_uppercase())
+}
+
+pub fn is_valid_country(country: &str) -> bool {
+    ISO_3166_ALPHA_2.binary_search(&country).is_ok()
+}
+
+/// Checks the postal code format for countries with a known pattern; other countries pass.
+pub fn is_valid_postal_code(country: &str, postal_code: &str) -> bool {
+    static PATTERNS: OnceLock<HashMap<&'static str, Regex>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        POSTAL_CODE_PATTERNS
+            .iter()
+            .flat_map(|(countries, pattern)| {
+                let regex = Regex::new(pattern).expect("valid postal code regex");
+                countries
+                    .iter()
+                    .map(move |country| (*country, regex.clone()))
+            })
+            .collect()
+    });
+
+    patterns
+        .get(country)
+        .is_none_or(|regex| regex.is_match(postal_code.trim()))
+}
+
+const POSTAL_CODE_PATTERNS: [(&[&str], &str); 11] = [
+    (&["US"], r"^\d{5}(-\d{4})?$"),
+    (&["CA"], r"^[A-Za-z]\d[A-Za-z][ -]?\d[A-Za-z]\d$"),
+    (&["GB"], r"^[A-Za-z]{1,2}\d[A-Za-z\d]?\s?\d[A-Za-z]{2}$"),
+    (&["AU", "NZ", "CH", "AT", "BE", "DK", "NO"], r"^\d{4}$"),
+    (&["DE", "FR", "ES", "IT", "MX", "FI"], r"^\d{5}$"),
+    (&["NL"], r"^\d{4}\s?[A-Za-z]{2}$"),
+    (&["SE"], r"^\d{3}\s?\d{2}$"),
+    (&["IE"], r"^[A-Za-z]\d[\dWw]\s?[A-Za-z\d]{4}$"),
+    (&["JP"], r"^\d{3}-?\d{4}$"),
+    (&["IN"], r"^\d{6}$"),
+    (&["BR"], r"^\d{5}-?\d{3}$"),
+];
+
+fn calling_code(country: &str) -> Option<&'static str> {
+    let code = match country {
+        "US" | "CA" => "1",
+        "GB" => "44",
+        "IE" => "353",
+        "AU" => "61",
+        "NZ" => "64",
+        "DE" => "49",
+        "FR" => "33",
+        "ES" => "34",
+        "IT" => "39",
+        "NL" => "31",
+        "BE" => "32",
+        "CH" => "41",
+        "AT" => "43",
+        "SE" => "46",
+        "NO" => "47",
+        "DK" => "45",
+        "FI" => "358",
+        "MX" => "52",
+        "BR" => "55",
+        "JP" => "81",
+        "IN" => "91",
+        "IL" => "972",
+        "ZA" => "27",
+        _ => return None,
+    };
+
+    Some(code)
+}
+
+/// Officially assigned ISO 3166-1 alpha-2 codes, sorted for binary search.
+const ISO_3166_ALPHA_2: [&str; 249] = [
+    "AD", "AE", "AF", "AG", "AI", "AL", "AM", "AO", "AQ", "AR", "AS", "AT", "AU", "AW", "AX", "AZ",
+    "BA", "BB", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BL", "BM", "BN", "BO", "BQ", "BR", "BS",
+    "BT", "BV", "BW", "BY", "BZ", "CA", "CC", "CD", "CF", "CG", "CH", "CI", "CK", "CL", "CM", "CN",
+    "CO", "CR", "CU", "CV", "CW", "CX", "CY", "CZ", "DE", "DJ", "DK", "DM", "DO", "DZ", "EC", "EE",
+    "EG", "EH", "ER", "ES", "ET", "FI", "FJ", "FK", "FM", "FO", "FR", "GA", "GB", "GD", "GE", "GF",
+    "GG", "GH", "GI", "GL", "GM", "GN", "GP", "GQ", "GR", "GS", "GT", "GU", "GW", "GY", "HK", "HM",
+    "HN", "HR", "HT", "HU", "ID", "IE", "IL", "IM", "IN", "IO", "IQ", "IR", "IS", "IT", "JE", "JM",
+    "JO", "JP", "KE", "KG", "KH", "KI", "KM", "KN", "KP", "KR", "KW", "KY", "KZ", "LA", "LB", "LC",
+    "LI", "LK", "LR", "LS", "LT", "LU", "LV", "LY", "MA", "MC", "MD", "ME", "MF", "MG", "MH", "MK",
+    "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA",
+    "NC", "NE", "NF", "NG", "NI", "NL", "NO", "NP", "NR", "NU", "NZ", "OM", "PA", "PE", "PF", "PG",
+    "PH", "PK", "PL", "PM", "PN", "PR", "PS", "PT", "PW", "PY", "QA", "RE", "RO", "RS", "RU", "RW",
+    "SA", "SB", "SC", "SD", "SE", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SR", "SS",
+    "ST", "SV", "SX", "SY", "SZ", "TC", "TD", "TF", "TG", "TH", "TJ", "TK", "TL", "TM", "TN", "TO",
+    "TR", "TT", "TV", "TW", "TZ", "UA", "UG", "UM", "US", "UY", "UZ", "VA", "VC", "VE", "VG", "VI",
+    "VN", "VU", "WF", "WS", "YE", "YT", "ZA", "ZM", "ZW",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn country_codes_are_sorted_for_binary_search() {
+        assert!(ISO_3166_ALPHA_2.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(is_valid_country("US"));
+        assert!(is_valid_country("AD"));
+        assert!(is_valid_country("ZW"));
+        assert!(!is_valid_country("us"));
+        assert!(!is_valid_country("UK"));
+        assert!(!is_valid_country(""));
+    }
+
+    #[test]
+    fn national_numbers_use_the_country_calling_code() {
+        assert_eq!(
+            normalize_phone("(555) 010-0199", Some("US")).as_deref(),
+            Ok("+15550100199")
+        );
+        assert_eq!(
+            normalize_phone("1 555 010 0199", Some("CA")).as_deref(),
+            Ok("+15550100199")
+        );
+        assert_eq!(
+            normalize_phone("020 7946 0958", Some("GB")).as_deref(),
+            Ok("+442079460958")
+        );
+        assert_eq!(
+            normalize_phone("030 1234567", Some("DE")).as_deref(),
+            Ok("+49301234567")
+        );
+    }
+
+    #[test]
+    fn international_numbers_keep_their_calling_code() {
+        assert_eq!(
+            normalize_phone("+44 20 7946 0958", None).as_deref(),
+            Ok("+442079460958")
+        );
+        assert_eq!(
+            normalize_phone("0044 20 7946 0958", Some("US")).as_deref(),
+            Ok("+442079460958")
+        );
+        assert_eq!(
+            normalize_phone("+1.555.010.0199", Some("GB")).as_deref(),
+            Ok("+15550100199")
+        );
+    }
+
+    #[test]
+    fn north_american_numbers_need_ten_digits() {
+        assert!(normalize_phone("555-1234", Some("US")).is_err());
+        assert!(normalize_phone("+1 555 1234", None).is_err());
+        assert!(normalize_phone("+1 555 010 01999", None).is_err());
+        assert!(normalize_phone("1 555 010 0199 9", Some("US")).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_phone_numbers() {
+        assert!(normalize_phone("call 555 0100", Some("US")).is_err());
+        assert!(normalize_phone("020 7946 0958", None).is_err());
+        assert!(normalize_phone("020 7946 0958", Some("")).is_err());
+        // No calling code is known for this country
+        assert!(normalize_phone("123 4567", Some("AQ")).is_err());
+        assert!(normalize_phone("+12", None).is_err());
+        assert!(normalize_phone("+1234567890123456", None).is_err());
+        assert!(normalize_phone("+0 20 7946 0958", None).is_err());
+    }
+
+    #[test]
+    fn postal_codes_follow_the_country_pattern() {
+        assert!(is_valid_postal_code("US", "55044"));
+        assert!(is_valid_postal_code("US", "55044-1234"));
+        assert!(!is_valid_postal_code("US", "5504"));
+        assert!(is_valid_postal_code("CA", "K1A 0B1"));
+        assert!(is_valid_postal_code("GB", "N1 9GU"));
+        assert!(is_valid_postal_code("GB", " SW1A 1AA "));
+        assert!(!is_valid_postal_code("GB", "12345"));
+        // Countries sharing a pattern all get it
+        assert!(is_valid_postal_code("NO", "0150"));
+        assert!(!is_valid_postal_code("NO", "01500"));
+        assert!(is_valid_postal_code("NL", "1012 AB"));
+        assert!(is_valid_postal_code("JP", "100-0001"));
+        // Countries without a known pattern accept anything
+        assert!(is_valid_postal_code("AQ", "anything"));
+    }
+
+    #[test]
+    fn emails_usernames_urls_and_currencies() {
+        assert!(is_valid_email("hello@pinelake.example"));
+        assert!(is_valid_email("first.last+camp@sub.example.org"));
+        assert!(!is_valid_email("hello@localhost"));
+        assert!(!is_valid_email("no at sign.example"));
+        assert!(!is_valid_email(&format!("{}@example.com", "a".repeat(250))));
+
+        assert!(is_valid_username("camp_fan.42"));
+        assert!(!is_valid_username("ab"));
+        assert!(!is_valid_username("_leading"));
+        assert!(!is_valid_username("has space"));
+
+        assert!(is_valid_url("https://pinelake.example/camps?id=1"));
+        assert!(is_valid_url("http://localhost:8080"));
+        assert!(!is_valid_url("ftp://pinelake.example"));
+        assert!(!is_valid_url("/relative/path"));
+        assert!(!is_valid_url("mailto:hello@pinelake.example"));
+
+        assert!(is_valid_currency("USD"));
+        assert!(!is_valid_currency("usd"));
+        assert!(!is_valid_currency("US"));
+    }
+
+    #[test]
+    fn collects_field_errors_by_path() {
+        let mut errors = ValidationErrors::default();
+        errors.check_length("$.name", "Pine Lake", 9);
+        errors.check_range("$.rating", 3, 1, 5);
+        errors.check_non_negative("$.price", Some(0));
+        errors.check_email("$.email", "");
+        errors.check_url("$.website", "");
+        assert!(errors.clone().into_result().is_ok());
+
+        errors.check_length("$.name", "Pine Lakes", 9);
+        errors.check_range("$.rating", 6, 1, 5);
+        errors.check_non_negative("$.price", Some(-1));
+        errors.check_email("$.email", "nope");
+        errors.check_url("$.website", "nope");
+        let paths: Vec<String> = errors
+            .into_result()
+            .expect_err("invalid fields")
+            .errors
+            .into_iter()
+            .map(|error| error.path)
+            .collect();
+        assert_eq!(
+            paths,
+            ["$.name", "$.rating", "$.price", "$.email", "$.website"]
+        );
+    }
+}
@@ -0,0 +1,513 @@
+use std::fmt;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use super::{
+    events::{DomainEvent, EventHandler},
+    review_activity::{ReviewActivity, ACTIVITY_KINDS},
+    validation::{Validate, ValidationErrors, MAX_VARCHAR_LEN},
+    Error,
+};
+use crate::{
+    auth::UserCtx,
+    jobs::{self, Job},
+    webhooks::DeliverWebhook,
+};
+
+const MIN_SECRET_LEN: usize = 16;
+const DELIVERY_LOG_LIMIT: i64 = 100;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl fmt::Display for DeliveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// An endpoint pushed review activity for one camp, or every camp.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    pub id: i64,
+    pub camp_id: Option<i64>,
+    pub url: String,
+    /// Only shown once, when the subscription is created.
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Activity kinds to send; empty sends all of them.
+    pub event_types: Vec<String>,
+    pub active: bool,
+    pub created_by: Option<String>,
+    pub ctime: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    pub subscription: WebhookSubscription,
+    /// Key for checking the `Webhook-Signature` header of deliveries.
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewWebhook {
+    pub url: String,
+    pub camp_id: Option<i64>,
+    /// Generated when left out.
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+impl Validate for NewWebhook {
+    fn validate(&mut self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+
+        self.url = self.url.trim().to_string();
+        errors.check_url("$.url", &self.url);
+        if let Some(secret) = &self.secret {
+            if secret.len() < MIN_SECRET_LEN {
+                errors.add(
+                    "$.secret",
+                    format!("must be at least {} characters", MIN_SECRET_LEN),
+                );
+            }
+            errors.check_length("$.secret", secret, MAX_VARCHAR_LEN);
+        }
+        check_event_types(&mut errors, &self.event_types);
+
+        errors.into_result()
+    }
+}
+
+/// Fields an admin can change; absent fields are left as they are.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WebhookPatch {
+    pub url: Option<String>,
+    pub event_types: Option<Vec<String>>,
+    pub active: Option<bool>,
+}
+
+impl Validate for WebhookPatch {
+    fn validate(&mut self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+
+        if let Some(url) = self.url.as_mut() {
+            *url = url.trim().to_string();
+            errors.check_url("$.url", url);
+        }
+        if let Some(event_types) = &self.event_types {
+            check_event_types(&mut errors, event_types);
+        }
+
+        errors.into_result()
+    }
+}
+
+fn check_event_types(errors: &mut ValidationErrors, event_types: &[String]) {
+    for (index, event_type) in event_types.iter().enumerate() {
+        if !ACTIVITY_KINDS.contains(&event_type.as_str()) {
+            errors.add(
+                format!("$.event_types[{}]", index),
+                format!("must be one of {}", ACTIVITY_KINDS.join(", ")),
+            );
+        }
+    }
+}
+
+/// One event queued for one subscription, with the result of its latest attempt.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub ctime: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeliveryAttempt {
+    pub id: i64,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub ctime: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookDeliveryDetail {
+    #[serde(flatten)]
+    pub delivery: WebhookDelivery,
+    pub attempt_log: Vec<DeliveryAttempt>,
+}
+
+/// A delivery the dispatcher is about to send, with where and how to sign it.
+#[derive(Debug)]
+pub struct DueDelivery {
+    pub id: i64,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub url: String,
+    pub secret: String,
+}
+
+/// The outcome of sending a delivery once.
+#[derive(Debug)]
+pub struct AttemptResult {
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+}
+
+/// The public side of a review, as sent to subscribers.
+#[derive(Debug, Serialize)]
+struct WebhookReview {
+    id: i64,
+    camp_id: i64,
+    rating: i32,
+    body: String,
+    body_html: String,
+    photo_urls: Option<Vec<String>>,
+    ctime: DateTime<Utc>,
+}
+
+pub struct WebhookManager;
+
+impl WebhookManager {
+    pub async fn create_subscription(
+        db: &PgPool,
+        utx: UserCtx,
+        mut data: NewWebhook,
+    ) -> Result<CreatedWebhook, Error> {
+        data.validate()?;
+
+        let secret = data.secret.unwrap_or_else(generate_secret);
+        let subscription = sqlx::query_as!(
+            WebhookSubscription,
+            "INSERT INTO webhook_subscriptions (camp_id, url, secret, event_types, created_by) VALUES ($1, $2, $3, $4, $5) returning *",
+            data.camp_id,
+            data.url,
+            secret,
+            &data.event_types,
+            utx.user_id
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(CreatedWebhook {
+            secret: subscription.secret.clone(),
+            subscription,
+        })
+    }
+
+    pub async fn get_subscriptions(db: &PgPool) -> Result<Vec<WebhookSubscription>, Error> {
+        let subscriptions = sqlx::query_as!(
+            WebhookSubscription,
+            "SELECT * FROM webhook_subscriptions ORDER BY id"
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    /// Activating a subscription sends the deliveries held while it was inactive.
+    pub async fn update_subscription(
+        db: &PgPool,
+        id: i64,
+        mut data: WebhookPatch,
+    ) -> Result<WebhookSubscription, Error> {
+        data.validate()?;
+
+        let mut tx = db.begin().await?;
+        let subscription = sqlx::query_as!(
+            WebhookSubscription,
+            "UPDATE webhook_subscriptions SET url = COALESCE($2, url), event_types = COALESCE($3, event_types), active = COALESCE($4, active) WHERE id = $1 returning *",
+            id,
+            data.url,
+            data.event_types.as_deref(),
+            data.active
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if data.active == Some(true) {
+            let pending = sqlx::query_scalar!(
+                "SELECT id FROM webhook_deliveries WHERE subscription_id = $1 AND status = 'pending'",
+                id
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+            schedule(&mut tx, &pending).await?;
+        }
+        tx.commit().await?;
+
+        Ok(subscription)
+    }
+
+    /// Also drops the subscription's delivery log.
+    pub async fn delete_subscription(db: &PgPool, id: i64) -> Result<(), Error> {
+        sqlx::query_scalar!(
+            "DELETE FROM webhook_subscriptions WHERE id = $1 RETURNING id",
+            id
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The latest deliveries of a subscription, newest first.
+    pub async fn get_deliveries(
+        db: &PgPool,
+        subscription_id: i64,
+    ) -> Result<Vec<WebhookDelivery>, Error> {
+        let deliveries = sqlx::query_as!(
+            WebhookDelivery,
+            "SELECT * FROM webhook_deliveries WHERE subscription_id = $1 ORDER BY id DESC LIMIT $2",
+            subscription_id,
+            DELIVERY_LOG_LIMIT
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    pub async fn get_delivery(db: &PgPool, id: i64) -> Result<WebhookDeliveryDetail, Error> {
+        let delivery = sqlx::query_as!(
+            WebhookDelivery,
+            "SELECT * FROM webhook_deliveries WHERE id = $1",
+            id
+        )
+        .fetch_one(db)
+        .await?;
+        let attempt_log = sqlx::query_as!(
+            DeliveryAttempt,
+            "SELECT id, status_code, error, duration_ms, ctime FROM webhook_delivery_attempts WHERE delivery_id = $1 ORDER BY id",
+            id
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(WebhookDeliveryDetail {
+            delivery,
+            attempt_log,
+        })
+    }
+
+    /// Queues a delivery to be sent again right away, with a fresh set of
+    /// attempts. Earlier attempts stay in its log. Deliveries of an inactive
+    /// subscription are refused, as they would not be sent until it is activated.
+    pub async fn redeliver(db: &PgPool, id: i64) -> Result<WebhookDelivery, Error> {
+        let subscription = sqlx::query!(
+            "SELECT webhook_subscriptions.id, active
+            FROM webhook_deliveries JOIN webhook_subscriptions ON subscription_id = webhook_subscriptions.id
+            WHERE webhook_deliveries.id = $1",
+            id
+        )
+        .fetch_one(db)
+        .await?;
+        if !subscription.active {
+            return Err(Error::InvalidQuery(format!(
+                "Webhook subscription {} is inactive; activate it before redelivering",
+                subscription.id
+            )));
+        }
+
+        let mut tx = db.begin().await?;
+        let delivery = sqlx::query_as!(
+            WebhookDelivery,
+            "UPDATE webhook_deliveries SET status = $2, attempts = 0 WHERE id = $1 returning *",
+            id,
+            DeliveryStatus::Pending.to_string()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        schedule(&mut tx, &[delivery.id]).await?;
+        tx.commit().await?;
+
+        Ok(delivery)
+    }
+
+    /// A pending delivery of an active subscription, with where to send it.
+    pub async fn get_due_delivery(db: &PgPool, id: i64) -> Result<Option<DueDelivery>, Error> {
+        let delivery = sqlx::query_as!(
+            DueDelivery,
+            "SELECT webhook_deliveries.id, event_type, payload, url, secret
+            FROM webhook_deliveries JOIN webhook_subscriptions ON subscription_id = webhook_subscriptions.id
+            WHERE webhook_deliveries.id = $1 AND status = 'pending' AND active",
+            id
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(delivery)
+    }
+
+    /// Logs an attempt and completes the delivery if it succeeded.
+    pub async fn record_attempt(db: &PgPool, id: i64, result: &AttemptResult) -> Result<(), Error> {
+        let mut tx = db.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO webhook_delivery_attempts (delivery_id, status_code, error, duration_ms) VALUES ($1, $2, $3, $4)",
+            id,
+            result.status_code,
+            result.error,
+            result.duration_ms
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let (status, delivered_at) = match &result.error {
+            None => (DeliveryStatus::Delivered, Some(Utc::now())),
+            Some(_) => (DeliveryStatus::Pending, None),
+        };
+
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET status = $2, attempts = attempts + 1, last_status_code = $3, last_error = $4, delivered_at = $5 WHERE id = $1",
+            id,
+            status.to_string(),
+            result.status_code,
+            result.error,
+            delivered_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn mark_failed(db: &PgPool, id: i64) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET status = $2 WHERE id = $1 AND status = 'pending'",
+            id,
+            DeliveryStatus::Failed.to_string()
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Has the deliveries sent right away: waiting jobs are brought forward with a
+/// fresh set of attempts, and deliveries without one get a new job.
+async fn schedule(tx: &mut Transaction<'_, Postgres>, delivery_ids: &[i64]) -> Result<(), Error> {
+    let delivery_keys: Vec<String> = delivery_ids.iter().map(i64::to_string).collect();
+    let scheduled = sqlx::query_scalar!(
+        r#"UPDATE jobs SET run_at = now(), attempts = 0
+        WHERE kind = $1 AND status IN ('queued', 'running') AND payload ->> 'delivery_id' = ANY($2)
+        RETURNING (payload ->> 'delivery_id')::bigint AS "delivery_id!""#,
+        DeliverWebhook::KIND,
+        &delivery_keys
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for delivery_id in delivery_ids {
+        if !scheduled.contains(delivery_id) {
+            let job = DeliverWebhook {
+                delivery_id: *delivery_id,
+            };
+            jobs::enqueue(tx, &job, Utc::now()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+/// Queues public review activity for every subscription that wants it.
+pub struct WebhookHandler;
+
+#[async_trait]
+impl EventHandler for WebhookHandler {
+    async fn handle(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        event: &DomainEvent,
+    ) -> Result<(), Error> {
+        let Some(activity) = ReviewActivity::from_event(event) else {
+            return Ok(());
+        };
+        let event_type = activity.to_string();
+
+        let subscription_ids = sqlx::query_scalar!(
+            "SELECT id FROM webhook_subscriptions
+            WHERE active AND (camp_id IS NULL OR camp_id = $1) AND (cardinality(event_types) = 0 OR $2 = ANY(event_types))",
+            activity.camp_id(),
+            event_type
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        if subscription_ids.is_empty() {
+            return Ok(());
+        }
+
+        let review_id = match activity {
+            ReviewActivity::ReviewCreated { review_id, .. }
+            | ReviewActivity::ReviewUpdated { review_id, .. } => Some(review_id),
+            ReviewActivity::ReviewDeleted { .. } | ReviewActivity::RatingChanged { .. } => None,
+        };
+        let review = match review_id {
+            Some(review_id) => Some(
+                sqlx::query_as!(
+                    WebhookReview,
+                    "SELECT id, camp_id, rating, body, body_html, photo_urls, ctime FROM reviews WHERE id = $1",
+                    review_id
+                )
+                .fetch_one(&mut **tx)
+                .await?,
+            ),
+            None => None,
+        };
+
+        let mut payload = json!(activity);
+        payload["occurred_at"] = json!(Utc::now());
+        if let Some(review) = review {
+            payload["review"] = json!(review);
+        }
+
+        let delivery_ids = sqlx::query_scalar!(
+            "INSERT INTO webhook_deliveries (subscription_id, event_type, payload) SELECT id, $2, $3 FROM unnest($1::bigint[]) AS id RETURNING id",
+            &subscription_ids,
+            event_type,
+            payload
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        schedule(tx, &delivery_ids).await?;
+
+        Ok(())
+    }
+}
@@ -1,29 +1,64 @@
 use std::sync::Arc;
 
 use super::{
-    custom_warp_filters::{do_auth, with_db},
+    custom_warp_filters::{do_admin, do_auth, do_limited_auth, with_db, with_importer},
     json_response,
+    rate_limit::RateLimiter,
 };
 
 use sqlx::PgPool;
 use warp::{reply::Json, Filter};
 
-use crate::{auth::UserCtx, models::camp_request::CampRequestManager};
+use crate::{
+    auth::UserCtx,
+    importer::{CampImportRequest, CampImporter},
+    models::camp_request::CampRequestManager,
+};
 
-use crate::models::{Camp, CampManager, CampPatch};
+use crate::models::CampPatch;
 
 pub fn camp_requests_rest_filters(
     db: Arc<PgPool>,
+    limiter: Arc<RateLimiter>,
+    importer: Arc<CampImporter>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    let common = with_db(db.clone()).and(do_auth(db));
+    let common = with_db(db.clone()).and(do_auth(db.clone()));
+    let admin = with_db(db.clone()).and(do_admin(db.clone()));
+    let create = with_db(db.clone()).and(do_limited_auth(db, limiter, "camp_requests.create"));
     let camp_requests_path = warp::path("camp_requests");
 
+    let import_camp_request_path = camp_requests_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(with_importer(importer))
+        .and(warp::path("import"))
+        .and(warp::path::end())
+        .and(warp::body::json::<CampImportRequest>())
+        .and_then(import_camp_request);
+
+    // Limited last, so requests that do not reach the handler keep their tokens
     let new_camp_request_path = camp_requests_path
-        .and(common.clone())
         .and(warp::post())
+        .and(warp::path::end())
         .and(warp::body::json::<CampPatch>())
+        .and(create)
         .and_then(new_camp_request);
 
+    let get_held_camp_requests_path = camp_requests_path
+        .and(warp::path("held"))
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::path::end())
+        .and_then(get_held_camp_requests);
+
+    let publish_camp_request_path = camp_requests_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("publish"))
+        .and(warp::path::end())
+        .and_then(publish_camp_request);
+
     let get_camp_requests_path = camp_requests_path
         .and(common.clone())
         .and(warp::get())
@@ -36,27 +71,80 @@ pub fn camp_requests_rest_filters(
         .and(warp::path::param::<i64>())
         .and_then(delete_camp_request);
 
-    new_camp_request_path
+    let get_possible_duplicates_path = camp_requests_path
+        .and(common.clone())
+        .and(warp::get())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("duplicates"))
+        .and(warp::path::end())
+        .and_then(get_possible_duplicates);
+
+    import_camp_request_path
+        .or(get_held_camp_requests_path)
+        .or(publish_camp_request_path)
+        .or(new_camp_request_path)
         .or(get_camp_requests_path)
+        .or(get_possible_duplicates_path)
         .or(delete_camp_request_path)
 }
 
 pub async fn new_camp_request(
+    data: CampPatch,
     db: Arc<PgPool>,
     utx: UserCtx,
-    data: CampPatch,
 ) -> Result<Json, warp::Rejection> {
     let new_camp = CampRequestManager::new_request(&db, &utx, data).await?;
 
     json_response(new_camp)
 }
 
+/// Pre-fills a camp request from the camp's website for moderator review.
+pub async fn import_camp_request(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    importer: Arc<CampImporter>,
+    data: CampImportRequest,
+) -> Result<Json, warp::Rejection> {
+    let import = importer.import(&db, &utx, &data.website).await?;
+
+    json_response(import)
+}
+
 pub async fn get_camp_requests(db: Arc<PgPool>, utx: UserCtx) -> Result<Json, warp::Rejection> {
     let camp_requests = CampRequestManager::get_camp_requests(&db, utx).await?;
 
     json_response(camp_requests)
 }
 
+pub async fn get_held_camp_requests(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+) -> Result<Json, warp::Rejection> {
+    let camp_requests = CampRequestManager::get_held_camp_requests(&db, utx).await?;
+
+    json_response(camp_requests)
+}
+
+pub async fn publish_camp_request(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    camp_request_id: i64,
+) -> Result<Json, warp::Rejection> {
+    let camp_request = CampRequestManager::publish_camp_request(&db, utx, camp_request_id).await?;
+
+    json_response(camp_request)
+}
+
+pub async fn get_possible_duplicates(
+    db: Arc<PgPool>,
+    _utx: UserCtx,
+    camp_request_id: i64,
+) -> Result<Json, warp::Rejection> {
+    let duplicates = CampRequestManager::get_possible_duplicates(&db, camp_request_id).await?;
+
+    json_response(duplicates)
+}
+
 pub async fn delete_camp_request(
     db: Arc<PgPool>,
     utx: UserCtx,
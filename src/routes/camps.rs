@@ -1,43 +1,66 @@
 use std::sync::Arc;
 
-use super::{
-    custom_warp_filters::{do_auth, with_db},
-    Error,
-};
+use super::custom_warp_filters::{do_admin, do_auth, optional_auth, with_db};
+use super::export_response;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::PgPool;
-use warp::{reply::Json, Filter};
+use warp::{reply::Json, Filter, Reply};
 
 use crate::auth::UserCtx;
+use crate::importer::bulk::{self, ImportOptions};
 
-use crate::models::{Camp, CampManager, CampPatch};
+use crate::models::{
+    camp_detail::{CampDetailManager, CampInclude},
+    camp_duplicate::CampDuplicateManager,
+    camp_revision::CampRevisionManager,
+    camp_session::CampSessionManager,
+    catalog_export::{CatalogExportManager, ExportQuery},
+    CampCalendarManager, CampComparisonManager, CampFilter, CampManager, CampPatch, CampStatus,
+    Error as ModelError,
+};
 
 pub fn camp_rest_filters(
     db: Arc<PgPool>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let camps_path = warp::path("camps");
 
-    let common = with_db(db.clone()).and(do_auth(db));
+    let common = with_db(db.clone()).and(do_auth(db.clone()));
+    let admin = with_db(db.clone()).and(do_admin(db.clone()));
+    let public = with_db(db.clone()).and(optional_auth(db.clone()));
 
     let new_camp_path = camps_path
         .and(warp::post())
-        .and(common.clone())
+        .and(admin.clone())
         .and(warp::path::param::<i64>())
         .and(warp::path::end())
+        .and(warp::query::<CreateCampQuery>())
         .and_then(create_camp);
 
     let get_all_camps_path = camps_path
         .and(warp::get())
-        .and(common.clone())
+        .and(public.clone())
         .and(warp::path::end())
+        .and(warp::query::<CampFilter>())
         .and_then(get_all_camps);
 
+    let export_camps_path = camps_path
+        .and(warp::path("export"))
+        .and(warp::get())
+        .and(public.clone())
+        .and(warp::path::end())
+        .and(warp::query::<ExportQuery>())
+        .and(warp::query::<CampFilter>())
+        .and_then(export_camps);
+
     let get_camp_path = camps_path
         .and(warp::get())
-        .and(common.clone())
+        .and(public.clone())
         .and(warp::path::param::<i64>())
         .and(warp::path::end())
+        .and(warp::query::<CampDetailQuery>())
+        // Kept as sent, to carry it over to a merged camp's redirect
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
         .and_then(get_camp);
 
     let patch_camp_path = camps_path
@@ -50,10 +73,70 @@ pub fn camp_rest_filters(
 
     let delete_camp_path = camps_path
         .and(warp::delete())
-        .and(common.clone())
+        .and(admin.clone())
         .and(warp::path::param::<i64>())
         .and_then(delete_camp);
 
+    let restore_camp_path = camps_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("restore"))
+        .and(warp::path::end())
+        .and_then(restore_camp);
+
+    let merge_camp_path = camps_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("merge"))
+        .and(warp::path::end())
+        .and(warp::body::json::<MergeCampBody>())
+        .and_then(merge_camp);
+
+    let get_camp_revisions_path = camps_path
+        .and(warp::get())
+        .and(common.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("revisions"))
+        .and(warp::path::end())
+        .and_then(get_camp_revisions);
+
+    let rollback_camp_path = camps_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("revisions"))
+        .and(warp::path::param::<i64>())
+        .and(warp::path("restore"))
+        .and(warp::path::end())
+        .and_then(rollback_camp);
+
+    let get_camp_sessions_path = camps_path
+        .and(warp::get())
+        .and(common.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("sessions"))
+        .and(warp::path::end())
+        .and_then(get_camp_sessions);
+
+    // Calendar apps subscribe without our auth header, so the .ics feeds are public
+    let camp_calendar_path = camps_path
+        .and(warp::get())
+        .and(with_db(db.clone()))
+        .and(warp::path::param::<i64>())
+        .and(warp::path("sessions.ics"))
+        .and(warp::path::end())
+        .and_then(get_camp_calendar);
+
+    let catalog_calendar_path = camps_path
+        .and(warp::path("sessions.ics"))
+        .and(warp::get())
+        .and(with_db(db.clone()))
+        .and(warp::path::end())
+        .and(warp::query::<CampFilter>())
+        .and_then(get_catalog_calendar);
+
     let get_camp_reviews_path = camps_path
         .and(warp::get())
         .and(common.clone())
@@ -63,37 +146,134 @@ pub fn camp_rest_filters(
     let get_featured_camps_path = camps_path
         .and(warp::path("featured"))
         .and(warp::get())
-        .and(common.clone())
+        .and(public.clone())
         .and(warp::path::end())
         .and_then(get_featured_camps);
 
+    let compare_camps_path = camps_path
+        .and(warp::path("compare"))
+        .and(warp::get())
+        .and(common.clone())
+        .and(warp::path::end())
+        .and(warp::query::<CompareQuery>())
+        .and_then(compare_camps);
+
+    let import_camps_path = camps_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::path("import"))
+        .and(warp::path::end())
+        .and(warp::query::<ImportOptions>())
+        .and(warp::body::content_length_limit(bulk::MAX_IMPORT_BYTES))
+        .and(warp::body::bytes())
+        .and_then(import_camps);
+
     new_camp_path
+        .or(import_camps_path)
+        .or(compare_camps_path)
         .or(get_camp_path)
         .or(delete_camp_path)
+        .or(restore_camp_path)
+        .or(merge_camp_path)
+        .or(get_camp_revisions_path)
+        .or(rollback_camp_path)
+        .or(get_camp_sessions_path)
+        .or(camp_calendar_path)
+        .or(catalog_calendar_path)
         .or(get_camp_reviews_path)
         .or(patch_camp_path)
         .or(get_all_camps_path)
         .or(get_featured_camps_path)
+        .or(export_camps_path)
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateCampQuery {
+    force: Option<bool>,
 }
 
 async fn create_camp(
     db: Arc<PgPool>,
     utx: UserCtx,
     camp_request_id: i64,
+    query: CreateCampQuery,
 ) -> Result<Json, warp::Rejection> {
-    let new_camp = CampManager::add_camp(&db, utx, camp_request_id).await?;
+    let new_camp =
+        CampManager::add_camp(&db, utx, camp_request_id, query.force.unwrap_or(false)).await?;
 
     json_response(new_camp)
 }
 
-async fn get_camp(db: Arc<PgPool>, utx: UserCtx, camp_id: i64) -> Result<Json, warp::Rejection> {
-    let camp = CampManager::get_camp(&db, camp_id, utx).await?;
+/// The request body is the CSV or JSON Lines file itself.
+async fn import_camps(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    options: ImportOptions,
+    data: warp::hyper::body::Bytes,
+) -> Result<Json, warp::Rejection> {
+    let data = std::str::from_utf8(&data)
+        .map_err(|ex| ModelError::InvalidData(format!("the file is not UTF-8: {}", ex)))?;
+    let report = bulk::import_camps(&db, &utx, data, &options).await?;
+
+    json_response(report)
+}
 
-    json_response(camp)
+#[derive(Debug, Deserialize)]
+struct CampDetailQuery {
+    /// Comma separated parts to embed, e.g. `reviews,rating_histogram`.
+    include: Option<String>,
+}
+
+/// Camps that were merged away permanently redirect to the camp they were merged into.
+async fn get_camp(
+    db: Arc<PgPool>,
+    utx: Option<UserCtx>,
+    camp_id: i64,
+    query: CampDetailQuery,
+    raw_query: String,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let includes = match &query.include {
+        Some(include) => CampInclude::parse_list(include)?,
+        None => CampInclude::DEFAULT.to_vec(),
+    };
+
+    if let Some(to_camp_id) = CampDuplicateManager::get_redirect(&db, camp_id).await? {
+        let location = match raw_query.as_str() {
+            "" => format!("/camps/{}", to_camp_id),
+            raw_query => format!("/camps/{}?{}", to_camp_id, raw_query),
+        };
+        let location = warp::http::Uri::try_from(location)
+            .map_err(|ex| ModelError::InvalidData(ex.to_string()))?;
+
+        return Ok(warp::redirect::permanent(location).into_response());
+    }
+
+    let camp = CampDetailManager::get_camp_detail(&db, camp_id, utx, &includes).await?;
+
+    Ok(json_response(camp)?.into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct MergeCampBody {
+    into: i64,
+}
+
+async fn merge_camp(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    camp_id: i64,
+    body: MergeCampBody,
+) -> Result<Json, warp::Rejection> {
+    let merged_camp = CampDuplicateManager::merge(&db, camp_id, body.into, utx).await?;
+
+    json_response(merged_camp)
 }
 
-async fn get_featured_camps(db: Arc<PgPool>, _utx: UserCtx) -> Result<Json, warp::Rejection> {
-    let featured_camps = CampManager::get_featured_camps(&db).await?;
+async fn get_featured_camps(
+    db: Arc<PgPool>,
+    utx: Option<UserCtx>,
+) -> Result<Json, warp::Rejection> {
+    let featured_camps = CampManager::get_featured_camps(&db, utx).await?;
 
     json_response(featured_camps)
 }
@@ -104,12 +284,49 @@ async fn delete_camp(db: Arc<PgPool>, utx: UserCtx, camp_id: i64) -> Result<Json
     json_response(deleted_camp)
 }
 
+async fn restore_camp(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    camp_id: i64,
+) -> Result<Json, warp::Rejection> {
+    let restored_camp = CampManager::restore_camp(&db, camp_id, utx).await?;
+
+    json_response(restored_camp)
+}
+
+async fn get_camp_revisions(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    camp_id: i64,
+) -> Result<Json, warp::Rejection> {
+    // Revisions show every past value, so they are as visible as the camp itself
+    CampManager::get_visible_camp(&db, camp_id, Some(&utx)).await?;
+    let revisions = CampRevisionManager::get_camp_revisions(&db, camp_id).await?;
+
+    json_response(revisions)
+}
+
+async fn rollback_camp(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    camp_id: i64,
+    revision_id: i64,
+) -> Result<Json, warp::Rejection> {
+    let camp = CampManager::rollback_camp(&db, camp_id, revision_id, utx).await?;
+
+    json_response(camp)
+}
+
 async fn update_camp(
     db: Arc<PgPool>,
     utx: UserCtx,
     camp_id: i64,
     data: CampPatch,
 ) -> Result<Json, warp::Rejection> {
+    // Hiding takes a camp out of every listing, which is for moderators only
+    if data.status == Some(CampStatus::Hidden) {
+        utx.require_admin()?;
+    }
     let updated_camp = CampManager::update_camp(&db, camp_id, data, utx).await?;
 
     json_response(updated_camp)
@@ -125,12 +342,95 @@ async fn get_camp_reviews(
     json_response(reviews)
 }
 
-async fn get_all_camps(db: Arc<PgPool>, utx: UserCtx) -> Result<Json, warp::Rejection> {
-    let camps = CampManager::get_all_camps(&db, utx).await?;
+#[derive(Debug, Deserialize)]
+struct CompareQuery {
+    ids: String,
+    origin_lat: Option<f64>,
+    origin_lng: Option<f64>,
+}
+
+async fn compare_camps(
+    db: Arc<PgPool>,
+    _utx: UserCtx,
+    query: CompareQuery,
+) -> Result<Json, warp::Rejection> {
+    let ids = query
+        .ids
+        .split(',')
+        .map(|id| id.trim().parse::<i64>())
+        .collect::<Result<Vec<i64>, _>>()
+        .map_err(|_| ModelError::InvalidQuery(format!("invalid camp ids '{}'", query.ids)))?;
+
+    let origin = match (query.origin_lat, query.origin_lng) {
+        (Some(lat), Some(lng)) => Some((lat, lng)),
+        (None, None) => None,
+        _ => {
+            return Err(ModelError::InvalidQuery(
+                "origin_lat and origin_lng must be given together".to_string(),
+            )
+            .into())
+        }
+    };
+
+    let comparison = CampComparisonManager::compare(&db, &ids, origin).await?;
+
+    json_response(comparison)
+}
+
+async fn get_camp_sessions(
+    db: Arc<PgPool>,
+    _utx: UserCtx,
+    camp_id: i64,
+) -> Result<Json, warp::Rejection> {
+    let sessions = CampSessionManager::get_camp_sessions(&db, camp_id).await?;
+
+    json_response(sessions)
+}
+
+async fn get_camp_calendar(
+    db: Arc<PgPool>,
+    camp_id: i64,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let calendar = CampCalendarManager::camp_calendar(&db, camp_id).await?;
+
+    Ok(calendar_response(calendar))
+}
+
+async fn get_catalog_calendar(
+    db: Arc<PgPool>,
+    filter: CampFilter,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let calendar = CampCalendarManager::catalog_calendar(&db, filter).await?;
+
+    Ok(calendar_response(calendar))
+}
+
+async fn get_all_camps(
+    db: Arc<PgPool>,
+    utx: Option<UserCtx>,
+    filter: CampFilter,
+) -> Result<Json, warp::Rejection> {
+    let camps = CampManager::get_all_camps(&db, utx, filter).await?;
 
     json_response(camps)
 }
 
+/// Streams the catalog in the requested format, honoring the same filters as `/camps`.
+async fn export_camps(
+    db: Arc<PgPool>,
+    utx: Option<UserCtx>,
+    query: ExportQuery,
+    filter: CampFilter,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let export = CatalogExportManager::export_camps(db, utx, filter, query.format);
+
+    export_response(export, query.format, "camps")
+}
+
+fn calendar_response(calendar: String) -> impl warp::Reply {
+    warp::reply::with_header(calendar, "Content-Type", "text/calendar; charset=utf-8")
+}
+
 fn json_response<D: Serialize>(data: D) -> Result<Json, warp::Rejection> {
     let response = json!(data);
     Ok(warp::reply::json(&response))
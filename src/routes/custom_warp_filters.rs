@@ -1,8 +1,12 @@
 use std::{convert::Infallible, sync::Arc};
 
+use std::net::SocketAddr;
+
+use super::{rate_limit::RateLimiter, RateLimitRejection};
 use crate::auth::{utx_from_token, UserCtx};
+use crate::importer::CampImporter;
 use sqlx::PgPool;
-use warp::{Filter, Rejection};
+use warp::{http::HeaderMap, Filter, Rejection};
 
 const AUTH_HEADER: &str = "Supabase-Auth-Token";
 
@@ -26,3 +30,100 @@ pub fn do_auth(db: Arc<PgPool>) -> impl Filter<Extract = (UserCtx,), Error = Rej
             }
         })
 }
+
+/// Like `do_auth`, but lets anonymous requests through as `None`.
+pub fn optional_auth(
+    db: Arc<PgPool>,
+) -> impl Filter<Extract = (Option<UserCtx>,), Error = Rejection> + Clone {
+    warp::any()
+        .and(with_db(db))
+        .and(warp::header::optional::<String>(AUTH_HEADER))
+        .and_then(|db: Arc<PgPool>, supa_auth: Option<String>| async move {
+            match supa_auth {
+                Some(supa_auth) => {
+                    let utx = utx_from_token(&db, &supa_auth).await?;
+                    Ok::<Option<UserCtx>, Rejection>(Some(utx))
+                }
+                None => Ok(None),
+            }
+        })
+}
+
+pub fn do_admin(db: Arc<PgPool>) -> impl Filter<Extract = (UserCtx,), Error = Rejection> + Clone {
+    do_auth(db).and_then(|utx: UserCtx| async move {
+        utx.require_admin()?;
+        Ok::<UserCtx, Rejection>(utx)
+    })
+}
+
+pub fn with_rate_limiter(
+    limiter: Arc<RateLimiter>,
+) -> impl Filter<Extract = (Arc<RateLimiter>,), Error = Infallible> + Clone {
+    warp::any().map(move || limiter.clone())
+}
+
+pub fn with_importer(
+    importer: Arc<CampImporter>,
+) -> impl Filter<Extract = (Arc<CampImporter>,), Error = Infallible> + Clone {
+    warp::any().map(move || importer.clone())
+}
+
+/// `do_auth` that also counts the request against `route`'s limit for the user.
+pub fn do_limited_auth(
+    db: Arc<PgPool>,
+    limiter: Arc<RateLimiter>,
+    route: &'static str,
+) -> impl Filter<Extract = (UserCtx,), Error = Rejection> + Clone {
+    do_auth(db).and(with_rate_limiter(limiter)).and_then(
+        move |utx: UserCtx, limiter: Arc<RateLimiter>| async move {
+            let decision = limiter.check(route, &format!("user:{}", utx.user_id)).await;
+            if !decision.allowed {
+                return Err(warp::reject::custom(RateLimitRejection(decision)));
+            }
+
+            Ok::<UserCtx, Rejection>(utx)
+        },
+    )
+}
+
+/// Counts an anonymous request against `route`'s limit for the client IP.
+pub fn do_ip_rate_limit(
+    limiter: Arc<RateLimiter>,
+    route: &'static str,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::headers_cloned())
+        .and(with_rate_limiter(limiter))
+        .and_then(
+            move |addr: Option<SocketAddr>, headers: HeaderMap, limiter: Arc<RateLimiter>| async move {
+                let forwarded = joined_header(&headers, "forwarded");
+                let x_forwarded_for = joined_header(&headers, "x-forwarded-for");
+                let ip = limiter
+                    .client_ip(
+                        addr.map(|addr| addr.ip()),
+                        forwarded.as_deref(),
+                        x_forwarded_for.as_deref(),
+                    )
+                    .map(|ip| ip.to_string())
+                    .unwrap_or_default();
+                let decision = limiter.check(route, &format!("ip:{}", ip)).await;
+                if !decision.allowed {
+                    return Err(warp::reject::custom(RateLimitRejection(decision)));
+                }
+
+                Ok::<(), Rejection>(())
+            },
+        )
+        .untuple_one()
+}
+
+/// All values of a header that may be repeated, as one comma separated list.
+fn joined_header(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<&str> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+
+    (!values.is_empty()).then(|| values.join(","))
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use sqlx::PgPool;
+use warp::{reply::Json, Filter};
+
+use super::{
+    custom_warp_filters::{do_admin, with_db},
+    json_response,
+};
+use crate::auth::UserCtx;
+use crate::models::job::JobManager;
+
+pub fn job_rest_filters(
+    db: Arc<PgPool>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let jobs_path = warp::path("jobs");
+
+    let admin = with_db(db.clone()).and(do_admin(db));
+
+    let get_stats_path = jobs_path
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::path("stats"))
+        .and(warp::path::end())
+        .and_then(get_stats);
+
+    let get_dead_jobs_path = jobs_path
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::path("dead"))
+        .and(warp::path::end())
+        .and_then(get_dead_jobs);
+
+    let retry_job_path = jobs_path
+        .and(warp::post())
+        .and(admin)
+        .and(warp::path::param::<i64>())
+        .and(warp::path("retry"))
+        .and(warp::path::end())
+        .and_then(retry_job);
+
+    get_stats_path.or(get_dead_jobs_path).or(retry_job_path)
+}
+
+/// Job counts per kind and status, and how far behind the queue is.
+async fn get_stats(db: Arc<PgPool>, _utx: UserCtx) -> Result<Json, warp::Rejection> {
+    let stats = JobManager::get_stats(&db).await?;
+
+    json_response(stats)
+}
+
+async fn get_dead_jobs(db: Arc<PgPool>, _utx: UserCtx) -> Result<Json, warp::Rejection> {
+    let jobs = JobManager::get_dead_jobs(&db).await?;
+
+    json_response(jobs)
+}
+
+async fn retry_job(db: Arc<PgPool>, _utx: UserCtx, id: i64) -> Result<Json, warp::Rejection> {
+    let job = JobManager::retry(&db, id).await?;
+
+    json_response(job)
+}
@@ -3,23 +3,37 @@ use std::{convert::Infallible, sync::Arc};
 use serde::Serialize;
 use serde_json::json;
 use sqlx::PgPool;
-use warp::{reject::Rejection, reply::Json, Filter};
+use warp::{reject::Rejection, reply::Json, Filter, Reply};
 
 use crate::{
-    auth, models,
+    auth,
+    importer::{self, CampImporter},
+    models::{
+        self,
+        catalog_export::{ExportFormat, ExportStream},
+    },
     routes::{
         camp_requests::camp_requests_rest_filters, camps::camp_rest_filters,
-        users::user_rest_filters,
+        jobs::job_rest_filters, tags::tag_rest_filters, users::user_rest_filters,
+        webhooks::webhook_rest_filters,
     },
 };
 
-use self::reviews::review_rest_filters;
+use self::{
+    custom_warp_filters::do_ip_rate_limit,
+    rate_limit::{RateLimitDecision, RateLimiter},
+    reviews::review_rest_filters,
+};
 
 mod camp_requests;
 mod camps;
 mod custom_warp_filters;
+mod jobs;
+mod rate_limit;
 mod reviews;
+mod tags;
 mod users;
+mod webhooks;
 
 pub async fn start_web(web_port: u16, db: Arc<PgPool>) -> Result<(), Error> {
     let cors = warp::cors()
@@ -31,10 +45,21 @@ pub async fn start_web(web_port: u16, db: Arc<PgPool>) -> Result<(), Error> {
         .allow_headers(vec!["Supabase-Auth-Token", "Content-Type", "content-type"])
         .allow_methods(vec!["GET", "POST", "HEAD", "DELETE", "PATCH", "OPTIONS"]);
 
-    let api = review_rest_filters(db.clone())
+    let limiter = Arc::new(RateLimiter::from_env());
+
+    let api = review_rest_filters(db.clone(), limiter.clone())
         .or(user_rest_filters(db.clone()))
         .or(camp_rest_filters(db.clone()))
-        .or(camp_requests_rest_filters(db.clone()));
+        .or(camp_requests_rest_filters(
+            db.clone(),
+            limiter.clone(),
+            Arc::new(CampImporter::from_env()),
+        ))
+        .or(tag_rest_filters(db.clone()))
+        .or(webhook_rest_filters(db.clone()))
+        .or(job_rest_filters(db.clone()));
+    // Every API request counts once against the default per-IP limit
+    let api = do_ip_rate_limit(limiter, "default").and(api);
 
     let content = warp::fs::dir("web-folder/".to_string());
 
@@ -56,15 +81,47 @@ async fn handle_rejection(err: Rejection) -> Result<impl warp::Reply, Infallible
     let mut _error_code = warp::http::StatusCode::BAD_REQUEST;
     let mut error_message = String::new();
 
-    match err.find::<WebErrorMessage>() {
-        Some(e) => error_message = e.message.to_owned(),
-        None => (),
-    };
+    if let Some(e) = err.find::<ValidationRejection>() {
+        let result = json!({ "error": "Validation failed", "fields": e.0.errors });
+        let result = warp::reply::json(&result);
+
+        return Ok(
+            warp::reply::with_status(result, warp::http::StatusCode::UNPROCESSABLE_ENTITY)
+                .into_response(),
+        );
+    }
+
+    if let Some(RateLimitRejection(decision)) = err.find::<RateLimitRejection>() {
+        let result = json!({ "error": "Too many requests" });
+        let mut response = warp::reply::with_status(
+            warp::reply::json(&result),
+            warp::http::StatusCode::TOO_MANY_REQUESTS,
+        )
+        .into_response();
+
+        let headers = response.headers_mut();
+        headers.insert("Retry-After", decision.retry_after_secs.into());
+        for (name, value) in decision.headers() {
+            if let Ok(value) = value.parse() {
+                headers.insert(name, value);
+            }
+        }
+
+        return Ok(response);
+    }
+
+    // Most routes authenticate before matching their path, so an auth failure from an
+    // unrelated route must not hide the error of the route that actually matched.
+    if let Some(e) = err.find::<WebErrorMessage>() {
+        error_message = e.message.to_owned();
+    } else if let Some(e) = err.find::<AuthRejection>() {
+        error_message = e.0.to_owned();
+    }
 
     let result = json!({ "error": error_message });
     let result = warp::reply::json(&result);
 
-    Ok(warp::reply::with_status(result, _error_code))
+    Ok(warp::reply::with_status(result, _error_code).into_response())
 }
 
 pub fn json_response<D: Serialize>(data: D) -> Result<Json, warp::Rejection> {
@@ -72,6 +129,25 @@ pub fn json_response<D: Serialize>(data: D) -> Result<Json, warp::Rejection> {
     Ok(warp::reply::json(&response))
 }
 
+/// Sends an export as a file download, writing each chunk as soon as it is ready.
+pub fn export_response(
+    export: ExportStream,
+    format: ExportFormat,
+    name: &str,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let file_name = format!("{}.{}", name, format.extension());
+    let response = warp::http::Response::builder()
+        .header("Content-Type", format.content_type())
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .body(warp::hyper::Body::wrap_stream(export))
+        .map_err(|ex| WebErrorMessage::rejection("export", ex.to_string()))?;
+
+    Ok(response)
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Web server failed to start because web-folder '{0}' not found.")]
@@ -83,6 +159,7 @@ pub enum Error {
 
 // region: Warp Custom Error
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct WebErrorMessage {
     pub typ: &'static str,
     pub message: String,
@@ -96,21 +173,57 @@ impl WebErrorMessage {
     }
 }
 
+/// Carries every field error of a rejected payload so it can be answered with a 422.
+#[derive(Debug)]
+pub struct ValidationRejection(pub models::validation::ValidationErrors);
+
+impl warp::reject::Reject for ValidationRejection {}
+
+/// A request over its route's rate limit, answered with a 429.
+#[derive(Debug)]
+pub struct RateLimitRejection(pub RateLimitDecision);
+
+impl warp::reject::Reject for RateLimitRejection {}
+
+/// A failed authentication, reported only when no route produced a more specific error.
+#[derive(Debug)]
+pub struct AuthRejection(pub String);
+
+impl warp::reject::Reject for AuthRejection {}
+
 impl From<self::Error> for warp::Rejection {
     fn from(other: self::Error) -> Self {
-        WebErrorMessage::rejection("web::Error", format!("{:?}", other))
+        match other {
+            self::Error::FailAuthMissingXAuth => {
+                warp::reject::custom(AuthRejection(format!("{:?}", other)))
+            }
+            _ => WebErrorMessage::rejection("web::Error", format!("{:?}", other)),
+        }
     }
 }
 
 impl From<models::Error> for warp::Rejection {
     fn from(other: models::Error) -> Self {
+        if let models::Error::Validation(errors) = other {
+            return warp::reject::custom(ValidationRejection(errors));
+        }
+
         WebErrorMessage::rejection("web::Error", format!("{:?}", other))
     }
 }
 
+impl From<importer::Error> for warp::Rejection {
+    fn from(other: importer::Error) -> Self {
+        match other {
+            importer::Error::Model(ex) => ex.into(),
+            _ => WebErrorMessage::rejection("importer::Error", other.to_string()),
+        }
+    }
+}
+
 impl From<auth::Error> for warp::Rejection {
     fn from(other: auth::Error) -> Self {
-        WebErrorMessage::rejection("web::Error", format!("{:?}", other))
+        warp::reject::custom(AuthRejection(format!("{:?}", other)))
     }
 }
 
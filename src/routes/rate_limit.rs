@@ -0,0 +1,426 @@
+use std::{
+    collections::HashMap,
+    env,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use async_trait::async_trait;
+
+/// Routes without their own entry in `RATE_LIMITS` share this limit.
+const DEFAULT_ROUTE: &str = "default";
+const DEFAULT_LIMITS: [(&str, RateLimit); 3] = [
+    (DEFAULT_ROUTE, RateLimit::new(120, 60)),
+    ("reviews.create", RateLimit::new(20, 3600)),
+    ("camp_requests.create", RateLimit::new(5, 3600)),
+];
+/// Idle buckets are dropped once the in-memory store holds this many keys.
+const MAX_IN_MEMORY_BUCKETS: usize = 10_000;
+
+/// A token bucket holding `capacity` tokens, refilled completely every `period_secs`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period_secs: u64,
+}
+
+impl RateLimit {
+    pub const fn new(capacity: u32, period_secs: u64) -> Self {
+        RateLimit {
+            capacity,
+            period_secs,
+        }
+    }
+
+    fn tokens_per_sec(&self) -> f64 {
+        self.capacity as f64 / self.period_secs.max(1) as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: RateLimit,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset_secs: u64,
+    /// Seconds until the next request would be allowed.
+    pub retry_after_secs: u64,
+}
+
+impl RateLimitDecision {
+    /// `RateLimit-*` headers as in the IETF ratelimit-headers draft.
+    pub fn headers(&self) -> [(&'static str, String); 3] {
+        [
+            ("RateLimit-Limit", self.limit.capacity.to_string()),
+            ("RateLimit-Remaining", self.remaining.to_string()),
+            ("RateLimit-Reset", self.reset_secs.to_string()),
+        ]
+    }
+}
+
+/// Where buckets are kept. The in-memory store is per process; a shared backend
+/// lets several API instances enforce one limit.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Takes one token from the bucket at `key`, if there is one.
+    async fn take(&self, key: &str, limit: RateLimit) -> RateLimitDecision;
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct InMemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn take(&self, key: &str, limit: RateLimit) -> RateLimitDecision {
+        self.take_at(key, limit, Instant::now())
+    }
+}
+
+impl InMemoryRateLimitStore {
+    fn take_at(&self, key: &str, limit: RateLimit, now: Instant) -> RateLimitDecision {
+        let capacity = limit.capacity as f64;
+        let rate = limit.tokens_per_sec();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|ex| ex.into_inner());
+        if buckets.len() >= MAX_IN_MEMORY_BUCKETS {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < capacity
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        RateLimitDecision {
+            allowed,
+            limit,
+            remaining: bucket.tokens.floor() as u32,
+            reset_secs: ((capacity - bucket.tokens) / rate).ceil() as u64,
+            retry_after_secs: if allowed {
+                0
+            } else {
+                ((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64
+            },
+        }
+    }
+}
+
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    limits: HashMap<String, RateLimit>,
+    /// Peers whose `Forwarded` or `X-Forwarded-For` headers are believed.
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>, limits: HashMap<String, RateLimit>) -> Self {
+        RateLimiter {
+            store,
+            limits,
+            trusted_proxies: Vec::new(),
+        }
+    }
+
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+
+    /// In-memory limiter with the defaults, overridden by `RATE_LIMITS`, e.g.
+    /// `default=120/60,reviews.create=20/3600` (requests per seconds, per route).
+    ///
+    /// `TRUSTED_PROXIES` lists the IPs of reverse proxies in front of the API,
+    /// e.g. `10.0.0.2,10.0.0.3`. Requests from them are limited by the client
+    /// IP they forward rather than by the proxy's own.
+    pub fn from_env() -> Self {
+        let mut limits: HashMap<String, RateLimit> = DEFAULT_LIMITS
+            .iter()
+            .map(|(route, limit)| (route.to_string(), *limit))
+            .collect();
+
+        if let Ok(config) = env::var("RATE_LIMITS") {
+            for entry in config.split(',').filter(|entry| !entry.trim().is_empty()) {
+                match parse_limit(entry) {
+                    Some((route, limit)) => {
+                        limits.insert(route, limit);
+                    }
+                    None => println!("Ignoring invalid RATE_LIMITS entry '{}'", entry),
+                }
+            }
+        }
+
+        let mut trusted_proxies = Vec::new();
+        if let Ok(config) = env::var("TRUSTED_PROXIES") {
+            for entry in config.split(',').filter(|entry| !entry.trim().is_empty()) {
+                match entry.trim().parse() {
+                    Ok(ip) => trusted_proxies.push(ip),
+                    Err(_) => println!("Ignoring invalid TRUSTED_PROXIES entry '{}'", entry),
+                }
+            }
+        }
+
+        RateLimiter::new(Arc::new(InMemoryRateLimitStore::default()), limits)
+            .with_trusted_proxies(trusted_proxies)
+    }
+
+    pub fn limit_for(&self, route: &str) -> RateLimit {
+        self.limits
+            .get(route)
+            .or_else(|| self.limits.get(DEFAULT_ROUTE))
+            .copied()
+            .unwrap_or(DEFAULT_LIMITS[0].1)
+    }
+
+    /// The IP a request is limited by. Forwarding headers are only read when
+    /// the peer is a trusted proxy, and then from the nearest hop backwards
+    /// until an address that is not a trusted proxy, so clients cannot pick
+    /// their own key by sending the headers themselves. `Forwarded` wins over
+    /// `X-Forwarded-For` when both are present.
+    pub fn client_ip(
+        &self,
+        peer: Option<IpAddr>,
+        forwarded: Option<&str>,
+        x_forwarded_for: Option<&str>,
+    ) -> Option<IpAddr> {
+        let mut client = peer?;
+        if !self.trusted_proxies.contains(&client) {
+            return Some(client);
+        }
+
+        let hops: Vec<Option<IpAddr>> = match (forwarded, x_forwarded_for) {
+            (Some(forwarded), _) => forwarded_hops(forwarded),
+            (None, Some(x_forwarded_for)) => x_forwarded_for
+                .split(',')
+                .map(|hop| parse_hop(hop.trim()))
+                .collect(),
+            (None, None) => Vec::new(),
+        };
+        for hop in hops.into_iter().rev() {
+            // An unknown or obfuscated hop ends the chain at the last proxy
+            let Some(ip) = hop else { break };
+            client = ip;
+            if !self.trusted_proxies.contains(&ip) {
+                break;
+            }
+        }
+
+        Some(client)
+    }
+
+    /// Counts one request by `key` (a user or an IP) against `route`'s limit.
+    pub async fn check(&self, route: &str, key: &str) -> RateLimitDecision {
+        let limit = self.limit_for(route);
+
+        self.store.take(&format!("{}:{}", route, key), limit).await
+    }
+}
+
+fn parse_limit(entry: &str) -> Option<(String, RateLimit)> {
+    let (route, limit) = entry.trim().split_once('=')?;
+    let (capacity, period_secs) = limit.split_once('/')?;
+    let limit = RateLimit::new(
+        capacity.trim().parse().ok()?,
+        period_secs.trim().parse().ok()?,
+    );
+
+    (limit.capacity > 0 && limit.period_secs > 0).then(|| (route.trim().to_string(), limit))
+}
+
+/// The `for=` addresses of a `Forwarded` header (RFC 7239), one per hop.
+fn forwarded_hops(forwarded: &str) -> Vec<Option<IpAddr>> {
+    forwarded
+        .split(',')
+        .map(|element| {
+            element.split(';').find_map(|pair| {
+                let (name, value) = pair.trim().split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("for")
+                    .then(|| value.trim().trim_matches('"'))
+            })
+        })
+        .map(|node| node.and_then(parse_hop))
+        .collect()
+}
+
+/// An IP, optionally with a port and with IPv6 in brackets, e.g.
+/// `192.0.2.60:4711` or `[2001:db8::17]:4711`.
+fn parse_hop(node: &str) -> Option<IpAddr> {
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+        .or_else(|| {
+            node.strip_prefix('[')
+                .and_then(|node| node.strip_suffix(']'))?
+                .parse()
+                .ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().expect("valid IP")
+    }
+
+    fn behind_proxies() -> RateLimiter {
+        RateLimiter::new(Arc::new(InMemoryRateLimitStore::default()), HashMap::new())
+            .with_trusted_proxies(vec![ip("10.0.0.2"), ip("10.0.0.3")])
+    }
+
+    #[test]
+    fn bucket_runs_dry_and_refills_over_time() {
+        let store = InMemoryRateLimitStore::default();
+        let limit = RateLimit::new(3, 60);
+        let start = Instant::now();
+
+        let remaining: Vec<u32> = (0..3)
+            .map(|_| {
+                let decision = store.take_at("ip:1", limit, start);
+                assert!(decision.allowed);
+                decision.remaining
+            })
+            .collect();
+        assert_eq!(remaining, [2, 1, 0]);
+
+        let denied = store.take_at("ip:1", limit, start);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after_secs, 20);
+        assert_eq!(denied.reset_secs, 60);
+
+        // One token comes back every 20 seconds
+        let later = store.take_at("ip:1", limit, start + Duration::from_secs(20));
+        assert!(later.allowed);
+        assert_eq!(later.remaining, 0);
+        assert!(
+            !store
+                .take_at("ip:1", limit, start + Duration::from_secs(21))
+                .allowed
+        );
+
+        // A long idle time refills the bucket only up to its capacity
+        let idle = store.take_at("ip:1", limit, start + Duration::from_secs(3600));
+        assert!(idle.allowed);
+        assert_eq!(idle.remaining, 2);
+    }
+
+    #[test]
+    fn buckets_are_kept_per_key() {
+        let store = InMemoryRateLimitStore::default();
+        let limit = RateLimit::new(1, 60);
+        let now = Instant::now();
+
+        assert!(store.take_at("ip:1", limit, now).allowed);
+        assert!(!store.take_at("ip:1", limit, now).allowed);
+        assert!(store.take_at("ip:2", limit, now).allowed);
+    }
+
+    #[test]
+    fn parses_route_limits() {
+        assert_eq!(
+            parse_limit(" reviews.create = 20 / 3600 "),
+            Some(("reviews.create".to_string(), RateLimit::new(20, 3600)))
+        );
+        assert_eq!(parse_limit("default=0/60"), None);
+        assert_eq!(parse_limit("default=10/0"), None);
+        assert_eq!(parse_limit("default=10"), None);
+        assert_eq!(parse_limit("default"), None);
+    }
+
+    #[test]
+    fn unknown_routes_share_the_default_limit() {
+        let limiter = RateLimiter::new(
+            Arc::new(InMemoryRateLimitStore::default()),
+            HashMap::from([
+                (DEFAULT_ROUTE.to_string(), RateLimit::new(50, 60)),
+                ("reviews.create".to_string(), RateLimit::new(2, 60)),
+            ]),
+        );
+
+        assert_eq!(limiter.limit_for("reviews.create"), RateLimit::new(2, 60));
+        assert_eq!(limiter.limit_for("camps.export"), RateLimit::new(50, 60));
+    }
+
+    #[test]
+    fn forwarding_headers_are_ignored_from_untrusted_peers() {
+        let limiter = behind_proxies();
+
+        assert_eq!(
+            limiter.client_ip(Some(ip("203.0.113.7")), None, Some("198.51.100.1")),
+            Some(ip("203.0.113.7"))
+        );
+        assert_eq!(
+            limiter.client_ip(Some(ip("203.0.113.7")), Some("for=198.51.100.1"), None),
+            Some(ip("203.0.113.7"))
+        );
+        assert_eq!(limiter.client_ip(None, None, Some("198.51.100.1")), None);
+    }
+
+    #[test]
+    fn trusted_proxies_forward_the_nearest_untrusted_hop() {
+        let limiter = behind_proxies();
+        let proxy = Some(ip("10.0.0.2"));
+
+        assert_eq!(
+            limiter.client_ip(proxy, None, Some("198.51.100.1")),
+            Some(ip("198.51.100.1"))
+        );
+        // A client can prepend anything; only hops added by proxies count
+        assert_eq!(
+            limiter.client_ip(proxy, None, Some("1.2.3.4, 198.51.100.1, 10.0.0.3")),
+            Some(ip("198.51.100.1"))
+        );
+        assert_eq!(limiter.client_ip(proxy, None, None), proxy);
+        assert_eq!(limiter.client_ip(proxy, None, Some("garbage")), proxy);
+    }
+
+    #[test]
+    fn reads_the_forwarded_header() {
+        let limiter = behind_proxies();
+        let proxy = Some(ip("10.0.0.2"));
+
+        assert_eq!(
+            limiter.client_ip(
+                proxy,
+                Some("for=1.2.3.4, for=198.51.100.1;proto=https;by=10.0.0.2"),
+                Some("5.6.7.8")
+            ),
+            Some(ip("198.51.100.1"))
+        );
+        assert_eq!(
+            limiter.client_ip(proxy, Some(r#"For="[2001:db8:cafe::17]:4711""#), None),
+            Some(ip("2001:db8:cafe::17"))
+        );
+        assert_eq!(
+            limiter.client_ip(proxy, Some("for=198.51.100.1:4711"), None),
+            Some(ip("198.51.100.1"))
+        );
+        assert_eq!(
+            limiter.client_ip(proxy, Some("for=198.51.100.1, for=_hidden"), None),
+            proxy
+        );
+    }
+}
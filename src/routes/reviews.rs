@@ -1,37 +1,84 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use serde::Serialize;
 use serde_json::json;
 use sqlx::PgPool;
-use warp::{reply::Json, Filter};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    StreamExt,
+};
+use warp::{reply::Json, sse::Event, Filter};
 
 use crate::auth::UserCtx;
-use crate::models::ReviewPatch;
+use crate::models::{
+    catalog_export::{CatalogExportManager, ReviewExportFilter},
+    favorite_camps::UserCampJunctionManager,
+    review_activity::ReviewActivityManager,
+    CampManager, ReviewPatch,
+};
 
 use super::models::ReviewManager;
 
-use super::custom_warp_filters::{do_auth, with_db};
+use super::custom_warp_filters::{do_admin, do_auth, do_limited_auth, optional_auth, with_db};
+use super::export_response;
+use super::rate_limit::RateLimiter;
 
 pub fn review_rest_filters(
     db: Arc<PgPool>,
+    limiter: Arc<RateLimiter>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let reviews_path = warp::path("reviews");
 
-    let common = with_db(db.clone()).and(do_auth(db));
+    let common = with_db(db.clone()).and(do_auth(db.clone()));
+    let create = with_db(db.clone()).and(do_limited_auth(db.clone(), limiter, "reviews.create"));
+    let public = with_db(db.clone()).and(optional_auth(db.clone()));
+    let admin = with_db(db.clone()).and(do_admin(db));
+
+    let get_held_reviews_route = reviews_path
+        .and(warp::path("held"))
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::path::end())
+        .and_then(get_held_reviews);
+
+    let export_reviews_route = reviews_path
+        .and(warp::path("export"))
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::path::end())
+        .and(warp::query::<ReviewExportFilter>())
+        .and_then(export_reviews);
+
+    let publish_review_route = reviews_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("publish"))
+        .and(warp::path::end())
+        .and_then(publish_review);
+
+    let hide_review_route = reviews_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("hide"))
+        .and(warp::path::end())
+        .and_then(hide_review);
 
     let get_camp_reviews_route = reviews_path
         .and(warp::get())
-        .and(common.clone())
+        .and(public.clone())
         .and(warp::path::param::<i64>())
         .and(warp::path::end())
         .and_then(get_camp_reviews);
 
+    // Limited last, so requests that do not reach the handler keep their tokens
     let create_review_route = reviews_path
         .and(warp::post())
-        .and(common.clone())
-        .and(warp::body::json::<ReviewPatch>())
         .and(warp::path::param::<i64>())
         .and(warp::path::end())
+        .and(warp::body::json::<ReviewPatch>())
+        .and(create)
         .and_then(create_review);
 
     let delete_review_route = reviews_path
@@ -49,7 +96,47 @@ pub fn review_rest_filters(
         .and(warp::path::end())
         .and_then(delete_all_camp_reviews);
 
-    get_camp_reviews_route
+    let vote_helpful_route = reviews_path
+        .and(warp::post())
+        .and(common.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("helpful"))
+        .and(warp::path::end())
+        .and_then(vote_helpful);
+
+    let remove_helpful_vote_route = reviews_path
+        .and(warp::delete())
+        .and(common.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("helpful"))
+        .and(warp::path::end())
+        .and_then(remove_helpful_vote);
+
+    let stream_favorites_activity_route = reviews_path
+        .and(warp::get())
+        .and(common.clone())
+        .and(warp::path("favorites"))
+        .and(warp::path("live"))
+        .and(warp::path::end())
+        .and_then(stream_favorites_activity);
+
+    let stream_camp_activity_route = reviews_path
+        .and(warp::get())
+        .and(public)
+        .and(warp::path::param::<i64>())
+        .and(warp::path("live"))
+        .and(warp::path::end())
+        .and_then(stream_camp_activity);
+
+    get_held_reviews_route
+        .or(export_reviews_route)
+        .or(stream_favorites_activity_route)
+        .or(stream_camp_activity_route)
+        .or(publish_review_route)
+        .or(hide_review_route)
+        .or(vote_helpful_route)
+        .or(remove_helpful_vote_route)
+        .or(get_camp_reviews_route)
         .or(create_review_route)
         .or(delete_review_route)
         .or(delete_camp_reviews_route)
@@ -57,7 +144,7 @@ pub fn review_rest_filters(
 
 async fn get_camp_reviews(
     db: Arc<PgPool>,
-    _utx: UserCtx,
+    _utx: Option<UserCtx>,
     camp_id: i64,
 ) -> Result<Json, warp::Rejection> {
     let reviews = ReviewManager::get_camp_reviews(&db, camp_id).await?;
@@ -65,17 +152,119 @@ async fn get_camp_reviews(
     json_response(reviews)
 }
 
-async fn create_review(
+async fn stream_camp_activity(
+    db: Arc<PgPool>,
+    utx: Option<UserCtx>,
+    camp_id: i64,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    CampManager::get_visible_camp(&db, camp_id, utx.as_ref()).await?;
+
+    Ok(activity_stream(HashSet::from([camp_id])))
+}
+
+/// Follows the camps that were favorites when the stream was opened.
+async fn stream_favorites_activity(
     db: Arc<PgPool>,
     utx: UserCtx,
-    data: ReviewPatch,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let camp_ids = UserCampJunctionManager::qyery_by_user_id(&db, utx.user_id)
+        .await?
+        .into_iter()
+        .map(|favorite| favorite.camp_id)
+        .collect();
+
+    Ok(activity_stream(camp_ids))
+}
+
+/// Server-sent review activity for `camp_ids`, named after its kind, e.g.
+/// `review_created`. A `resync` event tells a client that fell behind to refetch.
+fn activity_stream(camp_ids: HashSet<i64>) -> impl warp::Reply {
+    let events =
+        BroadcastStream::new(ReviewActivityManager::subscribe()).filter_map(move |activity| {
+            match activity {
+                Ok(activity) if camp_ids.contains(&activity.camp_id()) => Some(
+                    Event::default()
+                        .event(activity.to_string())
+                        .json_data(&activity),
+                ),
+                Ok(_) => None,
+                Err(BroadcastStreamRecvError::Lagged(_)) => {
+                    Some(Ok(Event::default().event("resync").data("")))
+                }
+            }
+        });
+
+    warp::sse::reply(warp::sse::keep_alive().stream(events))
+}
+
+async fn create_review(
     camp_id: i64,
+    data: ReviewPatch,
+    db: Arc<PgPool>,
+    utx: UserCtx,
 ) -> Result<Json, warp::Rejection> {
     let review = ReviewManager::create(&db, utx, data, camp_id).await?;
 
     json_response(review)
 }
 
+async fn export_reviews(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    filter: ReviewExportFilter,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let format = filter.format;
+    let export = CatalogExportManager::export_reviews(db, utx, filter)?;
+
+    export_response(export, format, "reviews")
+}
+
+async fn get_held_reviews(db: Arc<PgPool>, utx: UserCtx) -> Result<Json, warp::Rejection> {
+    let reviews = ReviewManager::get_held_reviews(&db, utx).await?;
+
+    json_response(reviews)
+}
+
+async fn publish_review(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    review_id: i64,
+) -> Result<Json, warp::Rejection> {
+    let review = ReviewManager::publish_review(&db, review_id, utx).await?;
+
+    json_response(review)
+}
+
+async fn vote_helpful(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    review_id: i64,
+) -> Result<Json, warp::Rejection> {
+    ReviewManager::vote_helpful(&db, utx, review_id).await?;
+
+    json_response(())
+}
+
+async fn remove_helpful_vote(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    review_id: i64,
+) -> Result<Json, warp::Rejection> {
+    ReviewManager::remove_helpful_vote(&db, utx, review_id).await?;
+
+    json_response(())
+}
+
+async fn hide_review(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    review_id: i64,
+) -> Result<Json, warp::Rejection> {
+    let review = ReviewManager::hide_review(&db, review_id, utx).await?;
+
+    json_response(review)
+}
+
 async fn delete_review(
     db: Arc<PgPool>,
     utx: UserCtx,
@@ -100,3 +289,40 @@ fn json_response<D: Serialize>(data: D) -> Result<Json, warp::Rejection> {
     let response = json!(data);
     Ok(warp::reply::json(&response))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::routes::{
+        rate_limit::{InMemoryRateLimitStore, RateLimit},
+        RateLimitRejection,
+    };
+
+    #[tokio::test]
+    async fn malformed_reviews_are_rejected_before_the_rate_limit() {
+        // Never connected to, as the request fails before reaching the database
+        let db = PgPool::connect_lazy("postgres://localhost/unused").expect("valid URL");
+        let limiter = Arc::new(RateLimiter::new(
+            Arc::new(InMemoryRateLimitStore::default()),
+            HashMap::from([("reviews.create".to_string(), RateLimit::new(0, 60))]),
+        ));
+        let filter = review_rest_filters(Arc::new(db), limiter);
+
+        let rejection = warp::test::request()
+            .method("POST")
+            .path("/reviews/1")
+            .header("Supabase-Auth-Token", "bob")
+            .body("not a review")
+            .filter(&filter)
+            .await
+            .err()
+            .expect("rejected");
+
+        assert!(rejection
+            .find::<warp::filters::body::BodyDeserializeError>()
+            .is_some());
+        assert!(rejection.find::<RateLimitRejection>().is_none());
+    }
+}
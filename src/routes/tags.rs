@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use sqlx::PgPool;
+use warp::{reply::Json, Filter};
+
+use super::{
+    custom_warp_filters::{do_admin, do_auth, with_db},
+    json_response,
+};
+use crate::auth::UserCtx;
+use crate::models::tag::{TagFilter, TagManager, TagPatch};
+
+pub fn tag_rest_filters(
+    db: Arc<PgPool>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let tags_path = warp::path("tags");
+
+    let common = with_db(db.clone()).and(do_auth(db.clone()));
+    let admin = with_db(db.clone()).and(do_admin(db));
+
+    let get_tags_path = tags_path
+        .and(warp::get())
+        .and(common.clone())
+        .and(warp::path::end())
+        .and(warp::query::<TagFilter>())
+        .and_then(get_tags);
+
+    let create_tag_path = tags_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::path::end())
+        .and(warp::body::json::<TagPatch>())
+        .and_then(create_tag);
+
+    let update_tag_path = tags_path
+        .and(warp::patch())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path::end())
+        .and(warp::body::json::<TagPatch>())
+        .and_then(update_tag);
+
+    let delete_tag_path = tags_path
+        .and(warp::delete())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path::end())
+        .and_then(delete_tag);
+
+    get_tags_path
+        .or(create_tag_path)
+        .or(update_tag_path)
+        .or(delete_tag_path)
+}
+
+async fn get_tags(
+    db: Arc<PgPool>,
+    _utx: UserCtx,
+    filter: TagFilter,
+) -> Result<Json, warp::Rejection> {
+    let tags = TagManager::get_tags(&db, filter).await?;
+
+    json_response(tags)
+}
+
+async fn create_tag(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    data: TagPatch,
+) -> Result<Json, warp::Rejection> {
+    let tag = TagManager::create_tag(&db, utx, data).await?;
+
+    json_response(tag)
+}
+
+async fn update_tag(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    tag_id: i64,
+    data: TagPatch,
+) -> Result<Json, warp::Rejection> {
+    let tag = TagManager::update_tag(&db, tag_id, data, utx).await?;
+
+    json_response(tag)
+}
+
+async fn delete_tag(db: Arc<PgPool>, utx: UserCtx, tag_id: i64) -> Result<Json, warp::Rejection> {
+    let tag = TagManager::delete_tag(&db, tag_id, utx).await?;
+
+    json_response(tag)
+}
@@ -7,8 +7,12 @@ use warp::{reply::Json, Filter};
 
 use crate::auth::UserCtx;
 use crate::models::{
-    favorite_camps::{UserCampJunction, UserCampJunctionManager},
-    User, UserManager,
+    favorite_camps::UserCampJunctionManager,
+    image::avatar_max_bytes,
+    notification::{NotificationFilter, NotificationManager},
+    user_export::UserExportManager,
+    user_profile::UserProfileManager,
+    CampCalendarManager, User, UserManager, UserPatch, UsernameRequest,
 };
 
 use super::custom_warp_filters::{do_auth, with_db};
@@ -18,14 +22,15 @@ pub fn user_rest_filters(
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let users_path = warp::path("users");
 
-    let common = with_db(db.clone()).and(do_auth(db));
+    let common = with_db(db.clone()).and(do_auth(db.clone()));
+    let public = with_db(db);
 
     // region: Paths
     let new_user_path = users_path
         .and(warp::post())
         .and(common.clone())
-        .and(warp::body::json::<User>())
         .and(warp::path::end())
+        .and(warp::body::json::<User>())
         .and_then(create_user);
 
     let get_user_path = users_path
@@ -34,6 +39,29 @@ pub fn user_rest_filters(
         .and(warp::path::end())
         .and_then(get_user);
 
+    let update_user_path = users_path
+        .and(warp::patch())
+        .and(common.clone())
+        .and(warp::path::end())
+        .and(warp::body::json::<UserPatch>())
+        .and_then(update_user);
+
+    let set_avatar_path = users_path
+        .and(warp::post())
+        .and(common.clone())
+        .and(warp::path("avatar"))
+        .and(warp::path::end())
+        .and(warp::body::content_length_limit(avatar_max_bytes() as u64))
+        .and(warp::body::bytes())
+        .and_then(set_avatar);
+
+    let remove_avatar_path = users_path
+        .and(warp::delete())
+        .and(common.clone())
+        .and(warp::path("avatar"))
+        .and(warp::path::end())
+        .and_then(remove_avatar);
+
     let delete_user_path = users_path
         .and(warp::delete())
         .and(common.clone())
@@ -70,6 +98,15 @@ pub fn user_rest_filters(
         .and(warp::path::end())
         .and_then(get_favorite_camps);
 
+    let favorites_calendar_path = users_path
+        .and(warp::get())
+        .and(common.clone())
+        .and(warp::path("favorite"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("sessions.ics"))
+        .and(warp::path::end())
+        .and_then(get_favorites_calendar);
+
     let check_if_camp_is_favorite_path = users_path
         .and(warp::get())
         .and(common.clone())
@@ -78,15 +115,91 @@ pub fn user_rest_filters(
         .and(warp::path::end())
         .and_then(check_if_camp_is_in_favorites);
 
+    let reserve_username_path = users_path
+        .and(warp::post())
+        .and(common.clone())
+        .and(warp::path("username"))
+        .and(warp::path("reservation"))
+        .and(warp::path::end())
+        .and(warp::body::json::<UsernameRequest>())
+        .and_then(reserve_username);
+
+    let change_username_path = users_path
+        .and(warp::post())
+        .and(common.clone())
+        .and(warp::path("username"))
+        .and(warp::path::end())
+        .and(warp::body::json::<UsernameRequest>())
+        .and_then(change_username);
+
+    let export_user_path = users_path
+        .and(warp::get())
+        .and(common.clone())
+        .and(warp::path("export"))
+        .and(warp::path::end())
+        .and_then(export_user);
+
+    let get_notifications_path = users_path
+        .and(warp::get())
+        .and(common.clone())
+        .and(warp::path("notifications"))
+        .and(warp::path::end())
+        .and(warp::query::<NotificationFilter>())
+        .and_then(get_notifications);
+
+    let mark_all_notifications_read_path = users_path
+        .and(warp::post())
+        .and(common.clone())
+        .and(warp::path("notifications"))
+        .and(warp::path("read"))
+        .and(warp::path::end())
+        .and_then(mark_all_notifications_read);
+
+    let mark_notification_read_path = users_path
+        .and(warp::post())
+        .and(common.clone())
+        .and(warp::path("notifications"))
+        .and(warp::path::param::<i64>())
+        .and(warp::path("read"))
+        .and(warp::path::end())
+        .and_then(mark_notification_read);
+
+    let delete_notification_path = users_path
+        .and(warp::delete())
+        .and(common.clone())
+        .and(warp::path("notifications"))
+        .and(warp::path::param::<i64>())
+        .and(warp::path::end())
+        .and_then(delete_notification);
+
+    let get_public_profile_path = users_path
+        .and(warp::get())
+        .and(public)
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and_then(get_public_profile);
+
     // endregion: Paths
     new_user_path
         .or(get_user_path)
+        .or(update_user_path)
+        .or(set_avatar_path)
+        .or(remove_avatar_path)
         .or(delete_user_path)
         .or(get_user_reviews_path)
         .or(add_camp_to_favorites_path)
         .or(remove_camp_from_favorites_path)
         .or(get_favorite_camps_path)
+        .or(favorites_calendar_path)
         .or(check_if_camp_is_favorite_path)
+        .or(reserve_username_path)
+        .or(change_username_path)
+        .or(export_user_path)
+        .or(get_notifications_path)
+        .or(mark_all_notifications_read_path)
+        .or(mark_notification_read_path)
+        .or(delete_notification_path)
+        .or(get_public_profile_path)
 }
 
 // region: Handlers
@@ -102,10 +215,37 @@ async fn get_user(db: Arc<PgPool>, utx: UserCtx) -> Result<Json, warp::Rejection
     json_response(user)
 }
 
+async fn update_user(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    data: UserPatch,
+) -> Result<Json, warp::Rejection> {
+    let user = UserManager::update_user(&db, utx, data).await?;
+
+    json_response(user)
+}
+
+/// The request body is the image itself, not a multipart form.
+async fn set_avatar(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    image: warp::hyper::body::Bytes,
+) -> Result<Json, warp::Rejection> {
+    let user = UserManager::set_avatar(&db, utx, &image).await?;
+
+    json_response(user)
+}
+
+async fn remove_avatar(db: Arc<PgPool>, utx: UserCtx) -> Result<Json, warp::Rejection> {
+    let user = UserManager::remove_avatar(&db, utx).await?;
+
+    json_response(user)
+}
+
 async fn delete_user(db: Arc<PgPool>, utx: UserCtx) -> Result<Json, warp::Rejection> {
-    let deleted_user = UserManager::delete_user(&db, utx).await?;
+    UserManager::delete_user(&db, utx).await?;
 
-    json_response(deleted_user)
+    json_response(())
 }
 
 async fn get_user_reviews(db: Arc<PgPool>, utx: UserCtx) -> Result<Json, warp::Rejection> {
@@ -114,6 +254,83 @@ async fn get_user_reviews(db: Arc<PgPool>, utx: UserCtx) -> Result<Json, warp::R
     json_response(reviews)
 }
 
+async fn reserve_username(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    data: UsernameRequest,
+) -> Result<Json, warp::Rejection> {
+    let reservation = UserManager::reserve_username(&db, utx, data).await?;
+
+    json_response(reservation)
+}
+
+async fn change_username(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    data: UsernameRequest,
+) -> Result<Json, warp::Rejection> {
+    let user = UserManager::change_username(&db, utx, data).await?;
+
+    json_response(user)
+}
+
+/// Sent as a JSON file download rather than an inline response.
+async fn export_user(db: Arc<PgPool>, utx: UserCtx) -> Result<impl warp::Reply, warp::Rejection> {
+    let file_name = format!("account-export-{}.json", utx.user_id);
+    let export = UserExportManager::export(&db, utx).await?;
+
+    Ok(warp::reply::with_header(
+        json_response(export)?,
+        "Content-Disposition",
+        format!("attachment; filename=\"{}\"", file_name),
+    ))
+}
+
+async fn get_notifications(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    filter: NotificationFilter,
+) -> Result<Json, warp::Rejection> {
+    let notifications = NotificationManager::get_notifications(&db, utx, filter).await?;
+
+    json_response(notifications)
+}
+
+async fn mark_notification_read(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    notification_id: i64,
+) -> Result<Json, warp::Rejection> {
+    let notification = NotificationManager::mark_read(&db, utx, notification_id).await?;
+
+    json_response(notification)
+}
+
+async fn mark_all_notifications_read(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+) -> Result<Json, warp::Rejection> {
+    let marked = NotificationManager::mark_all_read(&db, utx).await?;
+
+    json_response(json!({ "marked_read": marked }))
+}
+
+async fn delete_notification(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    notification_id: i64,
+) -> Result<Json, warp::Rejection> {
+    NotificationManager::delete_notification(&db, utx, notification_id).await?;
+
+    json_response("Notification deleted")
+}
+
+async fn get_public_profile(db: Arc<PgPool>, username: String) -> Result<Json, warp::Rejection> {
+    let profile = UserProfileManager::get_public_profile(&db, &username).await?;
+
+    json_response(profile)
+}
+
 async fn add_camp_to_favorites_handler(
     db: Arc<PgPool>,
     utx: UserCtx,
@@ -134,6 +351,20 @@ async fn get_favorite_camps(
     json_response(camps)
 }
 
+async fn get_favorites_calendar(
+    db: Arc<PgPool>,
+    _utx: UserCtx,
+    user_id: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let calendar = CampCalendarManager::favorites_calendar(&db, &user_id).await?;
+
+    Ok(warp::reply::with_header(
+        calendar,
+        "Content-Type",
+        "text/calendar; charset=utf-8",
+    ))
+}
+
 async fn check_if_camp_is_in_favorites(
     db: Arc<PgPool>,
     utx: UserCtx,
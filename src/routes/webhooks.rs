@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use sqlx::PgPool;
+use warp::{reply::Json, Filter};
+
+use super::{
+    custom_warp_filters::{do_admin, with_db},
+    json_response,
+};
+use crate::auth::UserCtx;
+use crate::models::webhook::{NewWebhook, WebhookManager, WebhookPatch};
+
+/// Subscriptions are managed by admins on behalf of camp operators.
+pub fn webhook_rest_filters(
+    db: Arc<PgPool>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let webhooks_path = warp::path("webhooks");
+
+    let admin = with_db(db.clone()).and(do_admin(db));
+
+    let get_webhooks_path = webhooks_path
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::path::end())
+        .and_then(get_webhooks);
+
+    let create_webhook_path = webhooks_path
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::path::end())
+        .and(warp::body::json::<NewWebhook>())
+        .and_then(create_webhook);
+
+    let update_webhook_path = webhooks_path
+        .and(warp::patch())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path::end())
+        .and(warp::body::json::<WebhookPatch>())
+        .and_then(update_webhook);
+
+    let delete_webhook_path = webhooks_path
+        .and(warp::delete())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path::end())
+        .and_then(delete_webhook);
+
+    let get_deliveries_path = webhooks_path
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::path::param::<i64>())
+        .and(warp::path("deliveries"))
+        .and(warp::path::end())
+        .and_then(get_deliveries);
+
+    let get_delivery_path = webhooks_path
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::path("deliveries"))
+        .and(warp::path::param::<i64>())
+        .and(warp::path::end())
+        .and_then(get_delivery);
+
+    let redeliver_path = webhooks_path
+        .and(warp::post())
+        .and(admin)
+        .and(warp::path("deliveries"))
+        .and(warp::path::param::<i64>())
+        .and(warp::path("redeliver"))
+        .and(warp::path::end())
+        .and_then(redeliver);
+
+    get_webhooks_path
+        .or(create_webhook_path)
+        .or(update_webhook_path)
+        .or(delete_webhook_path)
+        .or(get_deliveries_path)
+        .or(get_delivery_path)
+        .or(redeliver_path)
+}
+
+async fn get_webhooks(db: Arc<PgPool>, _utx: UserCtx) -> Result<Json, warp::Rejection> {
+    let subscriptions = WebhookManager::get_subscriptions(&db).await?;
+
+    json_response(subscriptions)
+}
+
+/// The response is the only time the secret is shown.
+async fn create_webhook(
+    db: Arc<PgPool>,
+    utx: UserCtx,
+    data: NewWebhook,
+) -> Result<Json, warp::Rejection> {
+    let webhook = WebhookManager::create_subscription(&db, utx, data).await?;
+
+    json_response(webhook)
+}
+
+async fn update_webhook(
+    db: Arc<PgPool>,
+    _utx: UserCtx,
+    id: i64,
+    data: WebhookPatch,
+) -> Result<Json, warp::Rejection> {
+    let subscription = WebhookManager::update_subscription(&db, id, data).await?;
+
+    json_response(subscription)
+}
+
+async fn delete_webhook(db: Arc<PgPool>, _utx: UserCtx, id: i64) -> Result<Json, warp::Rejection> {
+    WebhookManager::delete_subscription(&db, id).await?;
+
+    json_response(())
+}
+
+async fn get_deliveries(
+    db: Arc<PgPool>,
+    _utx: UserCtx,
+    subscription_id: i64,
+) -> Result<Json, warp::Rejection> {
+    let deliveries = WebhookManager::get_deliveries(&db, subscription_id).await?;
+
+    json_response(deliveries)
+}
+
+async fn get_delivery(db: Arc<PgPool>, _utx: UserCtx, id: i64) -> Result<Json, warp::Rejection> {
+    let delivery = WebhookManager::get_delivery(&db, id).await?;
+
+    json_response(delivery)
+}
+
+async fn redeliver(db: Arc<PgPool>, _utx: UserCtx, id: i64) -> Result<Json, warp::Rejection> {
+    let delivery = WebhookManager::redeliver(&db, id).await?;
+
+    json_response(delivery)
+}
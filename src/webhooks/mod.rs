@@ -0,0 +1,270 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::PgPool;
+
+use crate::{
+    jobs::{self, Job},
+    models::webhook::{AttemptResult, DueDelivery, WebhookManager},
+};
+
+/// Limit for one request to a subscriber, connecting included.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How much of a failed response body is kept in the delivery log.
+const MAX_LOGGED_BODY_LEN: usize = 500;
+
+/// Sends webhook deliveries to their subscribers.
+pub struct WebhookDispatcher {
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent("CampReviews-Webhooks/1.0")
+            .build()
+            .unwrap_or_default();
+
+        WebhookDispatcher { client }
+    }
+
+    /// One dispatcher shares its connection pool between all deliveries.
+    pub fn shared() -> &'static WebhookDispatcher {
+        static DISPATCHER: OnceLock<WebhookDispatcher> = OnceLock::new();
+
+        DISPATCHER.get_or_init(WebhookDispatcher::new)
+    }
+
+    /// Posts the payload signed with the subscription's secret. Anything but a
+    /// 2xx response counts as a failure.
+    async fn send(&self, delivery: &DueDelivery) -> AttemptResult {
+        let body = delivery.payload.to_string();
+        let timestamp = Utc::now().timestamp();
+        let started = Instant::now();
+
+        let response = self
+            .client
+            .post(&delivery.url)
+            .header("Content-Type", "application/json")
+            .header("Webhook-Id", delivery.id.to_string())
+            .header("Webhook-Event", &delivery.event_type)
+            .header(
+                "Webhook-Signature",
+                signature_header(&delivery.secret, timestamp, &body),
+            )
+            .body(body)
+            .send()
+            .await;
+
+        let (status_code, error) = match response {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16() as i32), None)
+            }
+            Ok(response) => {
+                let status = response.status();
+                let mut body = response.text().await.unwrap_or_default();
+                if let Some((index, _)) = body.char_indices().nth(MAX_LOGGED_BODY_LEN) {
+                    body.truncate(index);
+                }
+                (
+                    Some(status.as_u16() as i32),
+                    Some(format!("HTTP {}: {}", status, body.trim())),
+                )
+            }
+            Err(ex) => (None, Some(ex.to_string())),
+        };
+
+        AttemptResult {
+            status_code,
+            error,
+            duration_ms: started.elapsed().as_millis().min(i32::MAX as u128) as i32,
+        }
+    }
+}
+
+/// Sends one delivery. The job runner retries failed attempts, and every
+/// attempt is kept in the delivery's log.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeliverWebhook {
+    pub delivery_id: i64,
+}
+
+#[async_trait]
+impl Job for DeliverWebhook {
+    const KIND: &'static str = "deliver_webhook";
+    /// About twelve hours of retries with the runner's backoff.
+    const MAX_ATTEMPTS: i32 = 20;
+
+    async fn run(&self, db: &PgPool) -> Result<(), jobs::Error> {
+        // Deliveries of an inactive subscription are queued again when it is activated
+        let Some(delivery) = WebhookManager::get_due_delivery(db, self.delivery_id).await? else {
+            return Ok(());
+        };
+
+        let result = WebhookDispatcher::shared().send(&delivery).await;
+        WebhookManager::record_attempt(db, delivery.id, &result).await?;
+
+        match result.error {
+            Some(error) => Err(jobs::Error::Webhook(error)),
+            None => Ok(()),
+        }
+    }
+
+    async fn buried(&self, db: &PgPool, _error: &str) -> Result<(), jobs::Error> {
+        WebhookManager::mark_failed(db, self.delivery_id).await?;
+
+        Ok(())
+    }
+}
+
+/// `t=<unix seconds>,v1=<hex HMAC-SHA256 of "<t>.<body>">`. Receivers recompute
+/// the HMAC and reject old timestamps to stop replays.
+pub fn signature_header(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use warp::{http::HeaderMap, hyper::body::Bytes, Filter};
+
+    use super::*;
+
+    const SECRET: &str = "whsec_test_secret_0123";
+
+    /// Serves `status` with `body` to every request and passes on what was received.
+    fn subscriber(
+        status: u16,
+        body: &'static str,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<(HeaderMap, Bytes)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let route = warp::post()
+            .and(warp::header::headers_cloned())
+            .and(warp::body::bytes())
+            .map(move |headers: HeaderMap, request: Bytes| {
+                let _ = sender.send((headers, request));
+                warp::reply::with_status(
+                    body,
+                    warp::http::StatusCode::from_u16(status).expect("valid status"),
+                )
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        (addr, receiver)
+    }
+
+    fn delivery(url: String) -> DueDelivery {
+        DueDelivery {
+            id: 7,
+            event_type: "review_created".to_string(),
+            payload: json!({"type": "review_created", "camp_id": 1, "review_id": 2}),
+            url,
+            secret: SECRET.to_string(),
+        }
+    }
+
+    fn verify(secret: &str, header: &str, body: &[u8]) -> bool {
+        let Some((timestamp, signature)) = header
+            .strip_prefix("t=")
+            .and_then(|header| header.split_once(",v1="))
+        else {
+            return false;
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key");
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+
+        hex::decode(signature).is_ok_and(|signature| mac.verify_slice(&signature).is_ok())
+    }
+
+    #[test]
+    fn signs_the_timestamp_and_body() {
+        assert_eq!(
+            signature_header(SECRET, 1_700_000_000, r#"{"type":"review_created"}"#),
+            "t=1700000000,v1=d17045e027d3639a3e8e38165b6b6ee787f776ffc8c7aedf661ab7e479dded93"
+        );
+        assert_ne!(
+            signature_header(SECRET, 1_700_000_001, r#"{"type":"review_created"}"#),
+            signature_header(SECRET, 1_700_000_000, r#"{"type":"review_created"}"#)
+        );
+        assert!(!verify(
+            "another-secret",
+            &signature_header(SECRET, 1_700_000_000, "{}"),
+            b"{}"
+        ));
+    }
+
+    #[tokio::test]
+    async fn posts_signed_deliveries() {
+        let (addr, mut received) = subscriber(204, "");
+        let dispatcher = WebhookDispatcher::new();
+
+        let result = dispatcher
+            .send(&delivery(format!("http://{}/hooks", addr)))
+            .await;
+        assert_eq!(result.status_code, Some(204));
+        assert_eq!(result.error, None);
+
+        let (headers, body) = received.recv().await.expect("a request");
+        assert_eq!(headers["content-type"], "application/json");
+        assert_eq!(headers["webhook-id"], "7");
+        assert_eq!(headers["webhook-event"], "review_created");
+        let signature = headers["webhook-signature"].to_str().expect("ASCII header");
+        assert!(verify(SECRET, signature, &body));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).expect("JSON body"),
+            json!({"type": "review_created", "camp_id": 1, "review_id": 2})
+        );
+    }
+
+    #[tokio::test]
+    async fn logs_failed_responses() {
+        let long_body: &'static str = "x".repeat(2 * MAX_LOGGED_BODY_LEN).leak();
+        let (addr, _received) = subscriber(500, long_body);
+        let dispatcher = WebhookDispatcher::new();
+
+        let result = dispatcher
+            .send(&delivery(format!("http://{}/", addr)))
+            .await;
+        assert_eq!(result.status_code, Some(500));
+        let error = result.error.expect("a failure");
+        assert!(error.starts_with("HTTP 500 Internal Server Error: xxx"));
+        assert!(error.len() < MAX_LOGGED_BODY_LEN + 50);
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_subscribers() {
+        // Nothing listens on the port once the listener is dropped
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("a free port");
+        let dispatcher = WebhookDispatcher::new();
+
+        let result = dispatcher
+            .send(&delivery(format!("http://{}/", addr)))
+            .await;
+        assert_eq!(result.status_code, None);
+        assert!(result.error.is_some());
+    }
+}